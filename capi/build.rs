@@ -0,0 +1,7 @@
+// Target of the C compiler in the tests
+fn main() {
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+}
@@ -0,0 +1,2 @@
+//! Shared and static C library of `v_eval`, see [`v_eval::ffi`]
+pub use v_eval::ffi::*;
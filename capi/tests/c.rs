@@ -0,0 +1,33 @@
+//! Compile and run a C program with the header and the shared library
+#![cfg(unix)]
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn test_smoke() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("smoke");
+    // Next to the test executable
+    let exe = env::current_exe().unwrap();
+    let lib = exe.parent().unwrap();
+
+    let status = cc::Build::new()
+        .target(env!("TARGET"))
+        .host(env!("TARGET"))
+        .opt_level(0)
+        .cargo_metadata(false)
+        .get_compiler()
+        .to_command()
+        .arg(manifest.join("tests/smoke.c"))
+        .arg("-I")
+        .arg(manifest.join("../include"))
+        .arg("-o")
+        .arg(&out)
+        .arg(format!("-L{}", lib.display()))
+        .arg("-lv_eval_capi")
+        .arg(format!("-Wl,-rpath,{}", lib.display()))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(Command::new(&out).status().unwrap().code(), Some(0));
+}
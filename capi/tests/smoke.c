@@ -0,0 +1,36 @@
+#include <string.h>
+
+#include "v_eval.h"
+
+int main(void) {
+  VEval *e = v_eval_new();
+  char *error = NULL;
+  if (v_eval_insert(e, "adult", "age >= 18", &error) != 0) {
+    return 1;
+  }
+  if (v_eval_set_int(e, "age", 20, &error) != 0) {
+    return 2;
+  }
+
+  VEvalValue *v = v_eval_eval(e, "adult && age < 65", &error);
+  bool adult = false;
+  if (v == NULL || !v_eval_value_bool(v, &adult) || !adult) {
+    return 3;
+  }
+  v_eval_value_free(v);
+
+  v_eval_none_mode(e, V_EVAL_NONE_MODE_STRICT);
+  if (v_eval_eval(e, "missing", &error) != NULL ||
+      strcmp(error, "undefined identifier `missing`") != 0) {
+    return 4;
+  }
+  v_eval_limits(e, 0, 3, 0, 0);
+  if (v_eval_eval(e, "1 + 2 + 3 + 4", &error) != NULL ||
+      strcmp(error, "steps limit exceeded") != 0) {
+    return 5;
+  }
+
+  v_eval_string_free(error);
+  v_eval_free(e);
+  return 0;
+}
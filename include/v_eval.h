@@ -0,0 +1,199 @@
+#ifndef V_EVAL_H
+#define V_EVAL_H
+
+/* Generated with cbindgen, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// How `None` and undefined identifiers are evaluated, see `NoneMode`
+typedef enum VEvalNoneMode {
+  V_EVAL_NONE_MODE_LENIENT,
+  V_EVAL_NONE_MODE_STRICT,
+  V_EVAL_NONE_MODE_PROPAGATE,
+} VEvalNoneMode;
+
+// Kind of a `VEvalValue`
+typedef enum VEvalKind {
+  V_EVAL_KIND_NONE,
+  V_EVAL_KIND_BOOL,
+  V_EVAL_KIND_INT,
+  V_EVAL_KIND_FLOAT,
+  V_EVAL_KIND_STR,
+  V_EVAL_KIND_RANGE,
+  V_EVAL_KIND_FLOAT_RANGE,
+  V_EVAL_KIND_CHAR_RANGE,
+  V_EVAL_KIND_VEC,
+  V_EVAL_KIND_TUPLE,
+  V_EVAL_KIND_MAP,
+} VEvalKind;
+
+// Evaluator with context
+typedef struct VEval VEval;
+
+// Result of an evaluation
+typedef struct VEvalValue VEvalValue;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// New evaluator with empty context, freed with `v_eval_free`
+struct VEval *v_eval_new(void);
+
+// Free an evaluator
+//
+// # Safety
+// `e` is null or returned by `v_eval_new`, and not used after
+void v_eval_free(struct VEval *e);
+
+// Negative indexes count from the end, see [`Eval::negative_index`]
+//
+// # Safety
+// `e` is null or a valid evaluator
+void v_eval_negative_index(struct VEval *e, bool on);
+
+// How `None` and undefined identifiers are evaluated, see [`Eval::none_mode`]
+//
+// # Safety
+// `e` is null or a valid evaluator
+void v_eval_none_mode(struct VEval *e, enum VEvalNoneMode mode);
+
+// Resource budgets of each evaluation, see [`Eval::limits`], 0 is unlimited
+//
+// # Safety
+// `e` is null or a valid evaluator
+void v_eval_limits(struct VEval *e,
+                   size_t max_depth,
+                   size_t max_steps,
+                   size_t max_size,
+                   uint64_t timeout_ms);
+
+// Parse and insert in context name - expression, 0 on success
+//
+// # Safety
+// `e` is null or a valid evaluator, strings are null or NUL terminated and
+// `error` is null or valid to write
+int v_eval_insert(struct VEval *e, const char *name, const char *src, char **error);
+
+// Insert in context name - `None`, 0 on success
+//
+// # Safety
+// As `v_eval_insert`
+int v_eval_set_none(struct VEval *e, const char *name, char **error);
+
+// Insert in context name - bool, 0 on success
+//
+// # Safety
+// As `v_eval_insert`
+int v_eval_set_bool(struct VEval *e, const char *name, bool value, char **error);
+
+// Insert in context name - integer, 0 on success
+//
+// # Safety
+// As `v_eval_insert`
+int v_eval_set_int(struct VEval *e, const char *name, int64_t value, char **error);
+
+// Insert in context name - float, 0 on success
+//
+// # Safety
+// As `v_eval_insert`
+int v_eval_set_float(struct VEval *e, const char *name, double value, char **error);
+
+// Insert in context name - string, 0 on success
+//
+// # Safety
+// As `v_eval_insert`
+int v_eval_set_str(struct VEval *e, const char *name, const char *value, char **error);
+
+// Remove name in context
+//
+// # Safety
+// `e` is null or a valid evaluator and `name` is null or NUL terminated
+void v_eval_remove(struct VEval *e, const char *name);
+
+// Evaluate expression with current context, null on failure, freed with
+// `v_eval_value_free`
+//
+// # Safety
+// As `v_eval_insert`
+struct VEvalValue *v_eval_eval(const struct VEval *e, const char *src, char **error);
+
+// Free a value
+//
+// # Safety
+// `v` is null or returned by this library, and not used after
+void v_eval_value_free(struct VEvalValue *v);
+
+// Free an error or string
+//
+// # Safety
+// `s` is null or returned by this library, and not used after
+void v_eval_string_free(char *s);
+
+// Kind of a value, `None` with a null value
+//
+// # Safety
+// `v` is null or a valid value
+enum VEvalKind v_eval_value_kind(const struct VEvalValue *v);
+
+// Write a bool value in `out`, false with other kinds
+//
+// # Safety
+// `v` is null or a valid value and `out` is valid to write
+bool v_eval_value_bool(const struct VEvalValue *v, bool *out);
+
+// Write an integer value in `out`, false with other kinds
+//
+// # Safety
+// `v` is null or a valid value and `out` is valid to write
+bool v_eval_value_int(const struct VEvalValue *v, int64_t *out);
+
+// Write a float value in `out`, integers are converted, false with other kinds
+//
+// # Safety
+// `v` is null or a valid value and `out` is valid to write
+bool v_eval_value_float(const struct VEvalValue *v, double *out);
+
+// New string of a string value, null with other kinds or holding a NUL, freed
+// with `v_eval_string_free`
+//
+// # Safety
+// `v` is null or a valid value
+char *v_eval_value_str(const struct VEvalValue *v);
+
+// Number of elements of a `Vec` or `Tuple` value or entries of a `Map` value, 0
+// with other kinds
+//
+// # Safety
+// `v` is null or a valid value
+size_t v_eval_value_len(const struct VEvalValue *v);
+
+// New value of an element of a `Vec` or `Tuple` value, null out of bounds or with
+// other kinds, freed with `v_eval_value_free`
+//
+// # Safety
+// `v` is null or a valid value
+struct VEvalValue *v_eval_value_get(const struct VEvalValue *v, size_t index);
+
+// New value of the entry of a key in a `Map` value, null without the key or with
+// other kinds, freed with `v_eval_value_free`
+//
+// # Safety
+// `v` is null or a valid value and `key` is null or NUL terminated
+struct VEvalValue *v_eval_value_field(const struct VEvalValue *v, const char *key);
+
+// New string of the display of a value, freed with `v_eval_string_free`
+//
+// # Safety
+// `v` is null or a valid value
+char *v_eval_value_to_string(const struct VEvalValue *v);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* V_EVAL_H */
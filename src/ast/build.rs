@@ -0,0 +1,304 @@
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec::Vec};
+use core::ops::{Add, Div, Mul, Neg, Not, Rem, Sub};
+
+use super::{is_ident, BinOp, Expr, Lit, Member, Pat, UnOp};
+use crate::{Error, Value};
+
+/// Context name or local binding
+///
+/// Expressions are built with the operators `+ - * / % ! -`, the comparison
+/// and logic methods, and `Into<Expr>` for the literals and values,
+/// giving the same tree of the parsed source
+///
+/// ```rust
+///# use v_eval::{ast::{closure, var}, Eval, Value};
+///# fn main() -> Result<(), v_eval::Error> {
+/// let rule = var("age")
+///     .gt(18)
+///     .and(var("country").eq("E\"S"))
+///     .or(var("score").method("map_or", vec![0.into(), closure(&["s"], var("s") * -2)]).ge(10));
+///
+/// assert_eq!(
+///     rule.to_string(),
+///     r#"(age > 18 && country == "E\"S") || score.map_or(0, |s| s * -2) >= 10"#
+/// );
+/// let e = Eval::default();
+/// assert_eq!(e.parse(&rule.to_string())?, rule);
+///
+/// let e = e.insert("age", "20").unwrap().insert("country", r#""E\"S""#).unwrap();
+/// assert_eq!(e.compile_ast(&rule)?.eval(&e), Some(Value::Bool(true)));
+///# Ok(())
+///# }
+/// ```
+///
+/// The name is not checked, a tree with a name that is not an identifier, as
+/// `a-b` or a keyword, is rejected by [`Eval::compile_ast`](crate::Eval::compile_ast)
+/// and [`Eval::insert_ast`](crate::Eval::insert_ast), see [`try_var`]
+pub fn var(name: &str) -> Expr {
+    Expr::Var(name.to_owned())
+}
+
+/// Context name or local binding, `Error::Parse` when `name` is not an
+/// identifier
+///
+/// ```rust
+///# use v_eval::{ast::try_var, Error};
+/// assert!(try_var("user_name").is_ok());
+/// assert_eq!(try_var("user-name"), Err(Error::Parse));
+/// ```
+pub fn try_var(name: &str) -> Result<Expr, Error> {
+    ident(name).map(Expr::Var)
+}
+
+/// Name checked as an identifier
+fn ident(name: &str) -> Result<String, Error> {
+    if is_ident(name) {
+        Ok(name.to_owned())
+    } else {
+        Err(Error::Parse)
+    }
+}
+
+/// `None`
+pub fn none() -> Expr {
+    Expr::Lit(Lit::None)
+}
+
+/// `[a, b, ..]`
+pub fn array<T: Into<Expr>>(items: impl IntoIterator<Item = T>) -> Expr {
+    Expr::Array(items.into_iter().map(Into::into).collect())
+}
+
+/// `(a, b, ..)`
+pub fn tuple<T: Into<Expr>>(items: impl IntoIterator<Item = T>) -> Expr {
+    Expr::Tuple(items.into_iter().map(Into::into).collect())
+}
+
+/// `{"key": value, ..}`
+pub fn map<K: Into<String>, T: Into<Expr>>(entries: impl IntoIterator<Item = (K, T)>) -> Expr {
+    Expr::Map(
+        entries
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect(),
+    )
+}
+
+/// `|params| body`, argument of the methods taking closures
+///
+/// The parameters are not checked, as the name of [`var`], see [`try_closure`]
+pub fn closure(params: &[&str], body: impl Into<Expr>) -> Expr {
+    let params = params.iter().map(|p| param(p)).collect();
+
+    Expr::Closure(params, Box::new(body.into()))
+}
+
+/// `|params| body`, `Error::Parse` when a parameter is not an identifier or `_`
+pub fn try_closure(params: &[&str], body: impl Into<Expr>) -> Result<Expr, Error> {
+    let params = params
+        .iter()
+        .map(|p| match *p {
+            "_" => Ok(Pat::Wild),
+            p => ident(p).map(Pat::Ident),
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Expr::Closure(params, Box::new(body.into())))
+}
+
+/// Pattern of a closure parameter
+fn param(p: &str) -> Pat {
+    match p {
+        "_" => Pat::Wild,
+        p => Pat::Ident(p.to_owned()),
+    }
+}
+
+impl Expr {
+    fn binary(self, op: BinOp, rhs: impl Into<Expr>) -> Expr {
+        Expr::Binary(Box::new(self), op, Box::new(rhs.into()))
+    }
+
+    /// `self == rhs`
+    pub fn eq(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Eq, rhs)
+    }
+
+    /// `self != rhs`
+    pub fn ne(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Ne, rhs)
+    }
+
+    /// `self < rhs`
+    pub fn lt(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Lt, rhs)
+    }
+
+    /// `self <= rhs`
+    pub fn le(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Le, rhs)
+    }
+
+    /// `self > rhs`
+    pub fn gt(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Gt, rhs)
+    }
+
+    /// `self >= rhs`
+    pub fn ge(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Ge, rhs)
+    }
+
+    /// `self && rhs`
+    pub fn and(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::And, rhs)
+    }
+
+    /// `self || rhs`
+    pub fn or(self, rhs: impl Into<Expr>) -> Expr {
+        self.binary(BinOp::Or, rhs)
+    }
+
+    /// `self[index]`
+    pub fn at(self, index: impl Into<Expr>) -> Expr {
+        Expr::Index(Box::new(self), Box::new(index.into()))
+    }
+
+    /// `self.name`, the name is not checked as in [`var`]
+    pub fn field(self, name: &str) -> Expr {
+        Expr::Field(Box::new(self), Member::Named(name.to_owned()))
+    }
+
+    /// `self.i`
+    pub fn nth(self, i: u32) -> Expr {
+        Expr::Field(Box::new(self), Member::Unnamed(i))
+    }
+
+    /// `self.name(args)`, the name is not checked as in [`var`]
+    pub fn method(self, name: &str, args: Vec<Expr>) -> Expr {
+        Expr::MethodCall(Box::new(self), name.to_owned(), args)
+    }
+
+    /// `self?`, the method, field or index applied gives `None` when `self` is
+    pub fn optional(self) -> Expr {
+        Expr::Try(Box::new(self))
+    }
+
+    /// `self..to`
+    pub fn range(self, to: impl Into<Expr>) -> Expr {
+        Expr::Range {
+            from: Some(Box::new(self)),
+            to: Some(Box::new(to.into())),
+            closed: false,
+        }
+    }
+
+    /// `self..=to`
+    pub fn range_closed(self, to: impl Into<Expr>) -> Expr {
+        Expr::Range {
+            from: Some(Box::new(self)),
+            to: Some(Box::new(to.into())),
+            closed: true,
+        }
+    }
+}
+
+macro_rules! binary {
+    ($($t:ident $m:ident $op:ident,)*) => {$(
+        impl<T: Into<Expr>> $t<T> for Expr {
+            type Output = Expr;
+
+            fn $m(self, rhs: T) -> Expr {
+                self.binary(BinOp::$op, rhs)
+            }
+        }
+    )*};
+}
+
+binary! {
+    Add add Add,
+    Sub sub Sub,
+    Mul mul Mul,
+    Div div Div,
+    Rem rem Rem,
+}
+
+impl Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr::Unary(UnOp::Neg, Box::new(self))
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        Expr::Unary(UnOp::Not, Box::new(self))
+    }
+}
+
+impl From<Lit> for Expr {
+    fn from(l: Lit) -> Self {
+        match l {
+            // Negative numbers are parsed as negations
+            Lit::Int(n) if n < 0 && n != i64::MIN => -Expr::Lit(Lit::Int(-n)),
+            Lit::Float(n) if n.is_sign_negative() && !n.is_nan() => -Expr::Lit(Lit::Float(-n)),
+            l => Expr::Lit(l),
+        }
+    }
+}
+
+macro_rules! from_lit {
+    ($($t:ty: $v:ident => $e:expr,)*) => {$(
+        impl From<$t> for Expr {
+            fn from($v: $t) -> Self {
+                Lit::from($v).into()
+            }
+        }
+
+        impl From<$t> for Lit {
+            fn from($v: $t) -> Self {
+                $e
+            }
+        }
+    )*};
+}
+
+from_lit! {
+    bool: b => Lit::Bool(b),
+    i32: n => Lit::Int(n.into()),
+    i64: n => Lit::Int(n),
+    u32: n => Lit::Int(n.into()),
+    f64: n => Lit::Float(n),
+    char: c => Lit::Char(c),
+    &str: s => Lit::Str(s.to_owned()),
+    String: s => Lit::Str(s),
+}
+
+impl From<Value> for Expr {
+    fn from(v: Value) -> Self {
+        let bounds = |from: Expr, to: Expr, closed| Expr::Range {
+            from: Some(Box::new(from)),
+            to: Some(Box::new(to)),
+            closed,
+        };
+        match v {
+            Value::None => none(),
+            Value::Bool(b) => b.into(),
+            Value::Int(n) => n.into(),
+            Value::Float(n) => n.into(),
+            Value::Str(s) => s.into(),
+            Value::Vec(v) => array(v),
+            Value::Tuple(v) => tuple(v),
+            Value::Map(m) => map(m),
+            Value::Range(r) => bounds(r.start.into(), r.end.into(), false),
+            Value::FloatRange(r) => bounds(r.start.into(), r.end.into(), false),
+            Value::CharRange(r) => bounds(r.start.into(), r.end.into(), false),
+            Value::RangeInclusive(r) => bounds((*r.start()).into(), (*r.end()).into(), true),
+            Value::FloatRangeInclusive(r) => bounds((*r.start()).into(), (*r.end()).into(), true),
+            Value::CharRangeInclusive(r) => bounds((*r.start()).into(), (*r.end()).into(), true),
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Tree of the Rust expressions parsed by `syn`, with the `rust-syntax` feature
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::convert::TryFrom;
+
+use proc_macro2::{Delimiter, Group, Ident, Punct, Spacing, Span, TokenStream, TokenTree};
+
+use super::{Arm, BinOp, Expr, Lit, Member, Pat, UnOp};
+use crate::{limits, Error, Limit};
+
+/// Label of the blocks standing for the map literals, not Rust syntax
+const MAP: &str = "v_eval_map";
+
+/// Tree of a source, `Error::Parse` with syntax not evaluated
+///
+/// `syn` parses recursively, so the nesting is bounded before parsing,
+/// `Error::Limit` over `max_depth`
+pub(crate) fn parse(src: &str, max_depth: Option<usize>) -> Result<Expr, Error> {
+    if max_depth.is_some_and(|max| limits::height(src) > max) {
+        return Err(Error::Limit(Limit::Depth));
+    }
+    let tokens = src.parse::<TokenStream>().map_err(|_| Error::Parse)?;
+    syn::parse2::<syn::Expr>(maps(tokens)?)
+        .map_err(|_| Error::Parse)
+        .and_then(|e| from_syn(&e))
+}
+
+/// Tokens with each map literal `{"key": e, ..}` as the labeled block
+/// `'v_eval_map: {["key", e, ..]}`, parsed by `syn`
+fn maps(tokens: TokenStream) -> Result<TokenStream, Error> {
+    let mut out = TokenStream::new();
+    for t in tokens {
+        match t {
+            TokenTree::Group(g) => {
+                let inner = maps(g.stream())?;
+                if g.delimiter() == Delimiter::Brace && is_map(&inner) {
+                    out.extend(map_block(inner)?);
+                } else {
+                    out.extend(Some(TokenTree::Group(Group::new(g.delimiter(), inner))));
+                }
+            }
+            t => out.extend(Some(t)),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Starts as a map literal, with a string key and `:`
+fn is_map(tokens: &TokenStream) -> bool {
+    let mut tokens = tokens.clone().into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(k)), Some(TokenTree::Punct(p))) => {
+            k.to_string().starts_with('"') && p.as_char() == ':' && p.spacing() == Spacing::Alone
+        }
+        _ => false,
+    }
+}
+
+/// Labeled block of the entries of a map literal
+fn map_block(entries: TokenStream) -> Result<TokenStream, Error> {
+    let mut items = TokenStream::new();
+    let mut tokens = entries.into_iter().peekable();
+    while let Some(k) = tokens.next() {
+        match (k, tokens.next()) {
+            (TokenTree::Literal(k), Some(TokenTree::Punct(p)))
+                if k.to_string().starts_with('"') && p.as_char() == ':' =>
+            {
+                items.extend(Some(TokenTree::Literal(k)));
+            }
+            _ => return Err(Error::Parse),
+        }
+        items.extend(Some(TokenTree::Punct(Punct::new(',', Spacing::Alone))));
+        let mut value = false;
+        for t in tokens.by_ref() {
+            let comma = matches!(&t, TokenTree::Punct(p) if p.as_char() == ',');
+            value |= !comma;
+            items.extend(Some(t));
+            if comma {
+                break;
+            }
+        }
+        if !value {
+            return Err(Error::Parse);
+        }
+    }
+
+    Ok(vec![
+        TokenTree::Punct(Punct::new('\'', Spacing::Joint)),
+        TokenTree::Ident(Ident::new(MAP, Span::call_site())),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Group(Group::new(
+            Delimiter::Brace,
+            TokenTree::Group(Group::new(Delimiter::Bracket, items)).into(),
+        )),
+    ]
+    .into_iter()
+    .collect())
+}
+
+/// Tree of a `syn` expression, `Error::Parse` with syntax not evaluated
+///
+/// ```rust
+///# use std::{collections::BTreeMap, convert::TryFrom};
+///# use v_eval::{ast::Expr, Eval, Value};
+/// let e: syn::Expr = syn::parse_str("1 + 2").unwrap();
+/// let mut ctx = BTreeMap::new();
+/// ctx.insert("a".to_owned(), Expr::try_from(&e).unwrap());
+///
+/// assert_eq!(Eval::new(ctx).eval("a"), Some(Value::Int(3)));
+/// ```
+impl TryFrom<&syn::Expr> for Expr {
+    type Error = Error;
+
+    fn try_from(e: &syn::Expr) -> Result<Self, Error> {
+        from_syn(e)
+    }
+}
+
+fn from_syn(e: &syn::Expr) -> Result<Expr, Error> {
+    from_node(&node(e))
+}
+
+fn boxed(e: &syn::Expr) -> Result<Box<Expr>, Error> {
+    from_syn(e).map(Box::new)
+}
+
+fn list<'a>(v: impl IntoIterator<Item = &'a syn::Expr>) -> Result<Vec<Expr>, Error> {
+    v.into_iter().map(from_syn).collect()
+}
+
+/// Expression with its binary operators grouped as evaluated
+enum Node<'a> {
+    Expr(&'a syn::Expr),
+    Binary(Box<Node<'a>>, BinOp, Box<Node<'a>>),
+}
+
+enum Item<'a> {
+    Node(Node<'a>),
+    Op(BinOp),
+}
+
+/// Group a binary chain by the precedence of the evaluation, left associative,
+/// the parentheses of the source only delimit the chains
+///
+/// Chains with operators not evaluated are kept as they are
+fn node(e: &syn::Expr) -> Node<'_> {
+    match e {
+        syn::Expr::Paren(syn::ExprParen { expr, .. })
+        | syn::Expr::Group(syn::ExprGroup { expr, .. }) => node(expr),
+        syn::Expr::Binary(_) => {
+            let mut items = vec![];
+            if flatten(e, &mut items).is_none() {
+                return Node::Expr(e);
+            }
+
+            let mut nodes = vec![];
+            let mut ops: Vec<BinOp> = vec![];
+            for item in items {
+                match item {
+                    Item::Node(n) => nodes.push(n),
+                    Item::Op(op) => {
+                        while ops
+                            .last()
+                            .is_some_and(|last| !op.operator().gt_preference(last.operator()))
+                        {
+                            reduce(&mut nodes, &mut ops);
+                        }
+                        ops.push(op);
+                    }
+                }
+            }
+            while !ops.is_empty() {
+                reduce(&mut nodes, &mut ops);
+            }
+
+            nodes.pop().expect("binary node")
+        }
+        e => Node::Expr(e),
+    }
+}
+
+fn flatten<'a>(e: &'a syn::Expr, items: &mut Vec<Item<'a>>) -> Option<()> {
+    match e {
+        syn::Expr::Binary(syn::ExprBinary {
+            left, op, right, ..
+        }) => {
+            flatten(left, items)?;
+            items.push(Item::Op(bin_op(op)?));
+            flatten(right, items)
+        }
+        e => {
+            items.push(Item::Node(node(e)));
+            Some(())
+        }
+    }
+}
+
+fn reduce(nodes: &mut Vec<Node>, ops: &mut Vec<BinOp>) {
+    let op = ops.pop().expect("operator");
+    let right = nodes.pop().expect("right operand");
+    let left = nodes.pop().expect("left operand");
+    nodes.push(Node::Binary(Box::new(left), op, Box::new(right)));
+}
+
+fn bin_op(op: &syn::BinOp) -> Option<BinOp> {
+    Some(match op {
+        syn::BinOp::Add(_) => BinOp::Add,
+        syn::BinOp::Sub(_) => BinOp::Sub,
+        syn::BinOp::Mul(_) => BinOp::Mul,
+        syn::BinOp::Div(_) => BinOp::Div,
+        syn::BinOp::Rem(_) => BinOp::Rem,
+        syn::BinOp::Eq(_) => BinOp::Eq,
+        syn::BinOp::Ne(_) => BinOp::Ne,
+        syn::BinOp::Lt(_) => BinOp::Lt,
+        syn::BinOp::Le(_) => BinOp::Le,
+        syn::BinOp::Gt(_) => BinOp::Gt,
+        syn::BinOp::Ge(_) => BinOp::Ge,
+        syn::BinOp::And(_) => BinOp::And,
+        syn::BinOp::Or(_) => BinOp::Or,
+        _ => return None,
+    })
+}
+
+fn from_node(n: &Node) -> Result<Expr, Error> {
+    match n {
+        Node::Expr(e) => from_expr(e),
+        Node::Binary(left, op, right) => Ok(Expr::Binary(
+            Box::new(from_node(left)?),
+            *op,
+            Box::new(from_node(right)?),
+        )),
+    }
+}
+
+fn from_expr(e: &syn::Expr) -> Result<Expr, Error> {
+    use syn::Expr::*;
+    Ok(match e {
+        Array(a) => Expr::Array(list(&a.elems)?),
+        Block(b) if b.label.as_ref().is_some_and(|l| l.name.ident == MAP) => {
+            match b.block.stmts.as_slice() {
+                [syn::Stmt::Expr(Array(a))] => Expr::Map(
+                    a.elems
+                        .iter()
+                        .collect::<Vec<_>>()
+                        .chunks(2)
+                        .map(|entry| match entry {
+                            [Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(k),
+                                ..
+                            }), v] => Ok((k.value(), from_syn(v)?)),
+                            _ => Err(Error::Parse),
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                _ => return Err(Error::Parse),
+            }
+        }
+        Block(b) if b.label.is_none() && b.block.stmts.is_empty() => Expr::Map(vec![]),
+        Block(b) if b.label.is_none() => {
+            let (last, stmts) = b.block.stmts.split_last().ok_or(Error::Parse)?;
+            let lets = stmts
+                .iter()
+                .map(|stmt| match stmt {
+                    syn::Stmt::Local(syn::Local {
+                        pat,
+                        init: Some((_, init)),
+                        ..
+                    }) => Ok((from_pat(pat)?, from_syn(init)?)),
+                    _ => Err(Error::Parse),
+                })
+                .collect::<Result<_, _>>()?;
+            match last {
+                syn::Stmt::Expr(e) => Expr::Block(lets, boxed(e)?),
+                _ => return Err(Error::Parse),
+            }
+        }
+        Closure(c) => Expr::Closure(
+            c.inputs.iter().map(from_pat).collect::<Result<_, _>>()?,
+            boxed(&c.body)?,
+        ),
+        Field(f) => Expr::Field(
+            boxed(&f.base)?,
+            match &f.member {
+                syn::Member::Named(n) => Member::Named(n.to_string()),
+                syn::Member::Unnamed(i) => Member::Unnamed(i.index),
+            },
+        ),
+        Index(i) => Expr::Index(boxed(&i.expr)?, boxed(&i.index)?),
+        Lit(l) => Expr::Lit(from_lit(&l.lit)?),
+        Match(m) => Expr::Match(
+            boxed(&m.expr)?,
+            m.arms
+                .iter()
+                .map(|arm| {
+                    Ok(Arm {
+                        pat: from_pat(&arm.pat)?,
+                        guard: match &arm.guard {
+                            Some((_, g)) => Some(from_syn(g)?),
+                            None => None,
+                        },
+                        body: from_syn(&arm.body)?,
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        MethodCall(m) if m.turbofish.is_none() => {
+            Expr::MethodCall(boxed(&m.receiver)?, m.method.to_string(), list(&m.args)?)
+        }
+        Path(p) if p.qself.is_none() => match p.path.get_ident() {
+            Some(i) if i == "None" => Expr::Lit(self::Lit::None),
+            Some(i) => Expr::Var(i.to_string()),
+            None => return Err(Error::Parse),
+        },
+        Range(r) => Expr::Range {
+            from: r.from.as_deref().map(boxed).transpose()?,
+            to: r.to.as_deref().map(boxed).transpose()?,
+            closed: matches!(r.limits, syn::RangeLimits::Closed(_)),
+        },
+        Reference(r) => from_syn(&r.expr)?,
+        Try(t) => Expr::Try(boxed(&t.expr)?),
+        Tuple(t) => Expr::Tuple(list(&t.elems)?),
+        Unary(u) => Expr::Unary(
+            match u.op {
+                syn::UnOp::Not(_) => UnOp::Not,
+                syn::UnOp::Neg(_) => UnOp::Neg,
+                _ => return Err(Error::Parse),
+            },
+            boxed(&u.expr)?,
+        ),
+        _ => return Err(Error::Parse),
+    })
+}
+
+fn from_lit(l: &syn::Lit) -> Result<Lit, Error> {
+    Ok(match l {
+        syn::Lit::Bool(b) => Lit::Bool(b.value),
+        syn::Lit::Int(i) => Lit::Int(i.base10_parse().map_err(|_| Error::Parse)?),
+        syn::Lit::Float(f) => Lit::Float(f.base10_parse().map_err(|_| Error::Parse)?),
+        syn::Lit::Str(s) => Lit::Str(s.value()),
+        syn::Lit::Char(c) => Lit::Char(c.value()),
+        _ => return Err(Error::Parse),
+    })
+}
+
+/// Literal of a pattern, numbers can be negative
+fn pat_lit(e: &syn::Expr) -> Result<Lit, Error> {
+    match from_syn(e)? {
+        Expr::Lit(l) => Ok(l),
+        Expr::Unary(UnOp::Neg, e) => match *e {
+            Expr::Lit(Lit::Int(n)) => Ok(Lit::Int(-n)),
+            Expr::Lit(Lit::Float(n)) => Ok(Lit::Float(-n)),
+            _ => Err(Error::Parse),
+        },
+        _ => Err(Error::Parse),
+    }
+}
+
+fn from_pat(p: &syn::Pat) -> Result<Pat, Error> {
+    let list = |v: &syn::punctuated::Punctuated<syn::Pat, _>| {
+        v.iter().map(from_pat).collect::<Result<_, _>>()
+    };
+    Ok(match p {
+        syn::Pat::Wild(_) => Pat::Wild,
+        syn::Pat::Ident(syn::PatIdent {
+            by_ref: None,
+            mutability: None,
+            ident,
+            subpat: None,
+            ..
+        }) if ident == "None" => Pat::Lit(Lit::None),
+        syn::Pat::Ident(syn::PatIdent {
+            by_ref: None,
+            mutability: None,
+            ident,
+            subpat: None,
+            ..
+        }) => Pat::Ident(ident.to_string()),
+        syn::Pat::Ident(syn::PatIdent {
+            by_ref: None,
+            mutability: None,
+            ident,
+            subpat: Some((_, p)),
+            ..
+        }) => Pat::Bind(ident.to_string(), Box::new(from_pat(p)?)),
+        syn::Pat::Rest(_) => Pat::Rest,
+        syn::Pat::Lit(l) => Pat::Lit(pat_lit(&l.expr)?),
+        syn::Pat::Range(r) => Pat::Range {
+            lo: pat_lit(&r.lo)?,
+            hi: pat_lit(&r.hi)?,
+            closed: matches!(r.limits, syn::RangeLimits::Closed(_)),
+        },
+        // A parenthesized pattern is not a tuple
+        syn::Pat::Tuple(t) if t.elems.len() == 1 && !t.elems.trailing_punct() => {
+            from_pat(&t.elems[0])?
+        }
+        syn::Pat::Tuple(t) => Pat::Tuple(list(&t.elems)?),
+        syn::Pat::Slice(s) => Pat::Slice(list(&s.elems)?),
+        syn::Pat::Or(o) => Pat::Or(o.cases.iter().map(from_pat).collect::<Result<_, _>>()?),
+        _ => return Err(Error::Parse),
+    })
+}
@@ -0,0 +1,640 @@
+//! Syntax tree of the evaluated expressions
+//!
+//! ```rust
+//!# use v_eval::{ast::{self, BinOp, Expr, Fold, Lit}, Eval, Value};
+//!# fn main() -> Result<(), v_eval::Error> {
+//! let e = Eval::default();
+//! let rule = e.parse("age >= 18 && country == \"ES\"")?;
+//!
+//! // Replace the age threshold
+//! struct Threshold(i64);
+//! impl Fold for Threshold {
+//!     fn fold_expr(&mut self, e: Expr) -> Expr {
+//!         match e {
+//!             Expr::Binary(l, BinOp::Ge, r) if *l == Expr::Var("age".into()) => {
+//!                 Expr::Binary(l, BinOp::Ge, Box::new(Expr::Lit(Lit::Int(self.0))))
+//!             }
+//!             e => ast::fold_expr(self, e),
+//!         }
+//!     }
+//! }
+//! let rule = Threshold(21).fold_expr(rule);
+//! assert_eq!(rule.to_string(), "age >= 21 && country == \"ES\"");
+//!
+//! let e = e.insert("age", "20").unwrap().insert("country", "\"ES\"").unwrap();
+//! assert_eq!(e.compile_ast(&rule)?.eval(&e), Some(Value::Bool(false)));
+//!# Ok(())
+//!# }
+//! ```
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use crate::{operator::Operator, print::grouped, Error};
+
+mod build;
+#[cfg(feature = "rust-syntax")]
+mod from_syn;
+#[cfg(not(feature = "rust-syntax"))]
+mod parse;
+
+pub use self::build::{array, closure, map, none, try_closure, try_var, tuple, var};
+
+/// Expression, with the binary operators grouped as evaluated
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Lit(Lit),
+    /// Context name or local binding
+    Var(String),
+    Unary(UnOp, Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Array(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    /// `{"key": e, ...}`, a later entry replaces one with the same key
+    Map(Vec<(String, Expr)>),
+    /// `from..to`, or `from..=to` when closed
+    Range {
+        from: Option<Box<Expr>>,
+        to: Option<Box<Expr>>,
+        closed: bool,
+    },
+    /// `e[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `e.name` or `e.0`
+    Field(Box<Expr>, Member),
+    /// `receiver.method(args)`
+    MethodCall(Box<Expr>, String, Vec<Expr>),
+    /// `e?`, as receiver of a method, field or index gives `None` when `e` is `None`
+    Try(Box<Expr>),
+    /// `|pats| body`, argument of the methods taking closures
+    Closure(Vec<Pat>, Box<Expr>),
+    /// `{ let pat = init; ...; e }`
+    Block(Vec<(Pat, Expr)>, Box<Expr>),
+    Match(Box<Expr>, Vec<Arm>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lit {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Char(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnOp {
+    Not,
+    Neg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Member {
+    Named(String),
+    Unnamed(u32),
+}
+
+/// `pat if guard => body`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arm {
+    pub pat: Pat,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pat {
+    /// `_`
+    Wild,
+    /// Binding of the matched value
+    Ident(String),
+    /// `name @ pat`, binding of the value matching `pat`
+    Bind(String, Box<Pat>),
+    /// Equal value, `None` matches `None`
+    Lit(Lit),
+    /// `lo..hi`, or `lo..=hi` when closed
+    Range {
+        lo: Lit,
+        hi: Lit,
+        closed: bool,
+    },
+    Tuple(Vec<Pat>),
+    Slice(Vec<Pat>),
+    /// `a | b`
+    Or(Vec<Pat>),
+    /// `..`, the remaining elements of a tuple or slice
+    Rest,
+}
+
+impl Pat {
+    /// `..` or `name @ ..`
+    pub(crate) fn is_rest(&self) -> bool {
+        match self {
+            Pat::Bind(_, p) => **p == Pat::Rest,
+            p => *p == Pat::Rest,
+        }
+    }
+}
+
+impl BinOp {
+    pub(crate) fn operator(self) -> Operator {
+        match self {
+            BinOp::Add => Operator::Add,
+            BinOp::Sub => Operator::Sub,
+            BinOp::Mul => Operator::Mul,
+            BinOp::Div => Operator::Div,
+            BinOp::Rem => Operator::Rem,
+            BinOp::Eq => Operator::Eq,
+            BinOp::Ne => Operator::Ne,
+            BinOp::Lt => Operator::Lt,
+            BinOp::Le => Operator::Le,
+            BinOp::Gt => Operator::Gt,
+            BinOp::Ge => Operator::Ge,
+            BinOp::And => Operator::And,
+            BinOp::Or => Operator::Or,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
+impl Expr {
+    /// Tree of a source, `Error::Parse` with syntax not evaluated
+    pub(crate) fn parse(src: &str) -> Result<Expr, Error> {
+        Expr::parse_limited(src, None)
+    }
+
+    /// Tree of a source nesting up to `max_depth`, `Error::Limit` over it
+    pub(crate) fn parse_limited(src: &str, max_depth: Option<usize>) -> Result<Expr, Error> {
+        #[cfg(feature = "rust-syntax")]
+        return from_syn::parse(src, max_depth);
+        #[cfg(not(feature = "rust-syntax"))]
+        return parse::parse(src, max_depth);
+    }
+
+    /// Names are all identifiers, so the tree is a source
+    pub(crate) fn has_valid_names(&self) -> bool {
+        let mut names = Names(true);
+        names.visit_expr(self);
+        names.0
+    }
+
+    /// Parsed as a unit before any operator
+    fn is_primary(&self) -> bool {
+        match self {
+            Expr::Lit(l) => !l.is_negative(),
+            Expr::Unary(..) | Expr::Binary(..) | Expr::Range { .. } | Expr::Closure(..) => false,
+            _ => true,
+        }
+    }
+
+    /// Parsed as a unit in a binary chain
+    fn is_operand(&self) -> bool {
+        matches!(self, Expr::Lit(_) | Expr::Unary(..)) || self.is_primary()
+    }
+}
+
+impl Lit {
+    fn is_negative(&self) -> bool {
+        match self {
+            Lit::Int(n) => *n < 0,
+            Lit::Float(n) => n.is_sign_negative(),
+            _ => false,
+        }
+    }
+}
+
+fn list<T: Display>(f: &mut Formatter, v: &[T]) -> fmt::Result {
+    for (i, x) in v.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", x)?;
+    }
+    Ok(())
+}
+
+fn paren(f: &mut Formatter, on: bool, e: &Expr) -> fmt::Result {
+    if on {
+        write!(f, "({})", e)
+    } else {
+        write!(f, "{}", e)
+    }
+}
+
+/// Canonical source, the same of [`format`](crate::format)
+///
+/// A name that is not an identifier is printed as it is, so it can't be parsed
+/// back, see [`Eval::compile_ast`](crate::Eval::compile_ast)
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let postfix = |f: &mut Formatter, e: &Expr| paren(f, !e.is_primary(), e);
+        let bound = |f: &mut Formatter, e: &Option<Box<Expr>>| match e {
+            Some(e) => paren(f, !e.is_operand() && !matches!(**e, Expr::Binary(..)), e),
+            None => Ok(()),
+        };
+
+        match self {
+            Expr::Lit(l) => write!(f, "{}", l),
+            Expr::Var(n) => f.write_str(n),
+            Expr::Unary(op, e) => {
+                f.write_str(match op {
+                    UnOp::Not => "!",
+                    UnOp::Neg => "-",
+                })?;
+                // `--` would read as a decrement
+                let neg = *op == UnOp::Neg
+                    && match &**e {
+                        Expr::Unary(UnOp::Neg, _) => true,
+                        Expr::Lit(l) => l.is_negative(),
+                        _ => false,
+                    };
+                paren(f, neg || !e.is_operand(), e)
+            }
+            Expr::Binary(left, op, right) => {
+                let op = *op;
+                let grouped = |e: &Expr, right: bool| match e {
+                    Expr::Binary(_, child, _) => grouped(op.operator(), child.operator(), right),
+                    e => !e.is_operand(),
+                };
+                paren(f, grouped(left, false), left)?;
+                write!(f, " {} ", op.as_str())?;
+                paren(f, grouped(right, true), right)
+            }
+            Expr::Array(v) => {
+                f.write_str("[")?;
+                list(f, v)?;
+                f.write_str("]")
+            }
+            Expr::Tuple(v) => {
+                f.write_str("(")?;
+                list(f, v)?;
+                if v.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Expr::Map(entries) => {
+                f.write_str("{")?;
+                for (i, (k, e)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{:?}: {}", k, e)?;
+                }
+                f.write_str("}")
+            }
+            Expr::Range { from, to, closed } => {
+                bound(f, from)?;
+                f.write_str(if *closed { "..=" } else { ".." })?;
+                bound(f, to)
+            }
+            Expr::Index(e, i) => {
+                postfix(f, e)?;
+                write!(f, "[{}]", i)
+            }
+            Expr::Field(e, m) => {
+                postfix(f, e)?;
+                match m {
+                    Member::Named(n) => write!(f, ".{}", n),
+                    Member::Unnamed(i) => write!(f, ".{}", i),
+                }
+            }
+            Expr::MethodCall(e, m, args) => {
+                postfix(f, e)?;
+                write!(f, ".{}(", m)?;
+                list(f, args)?;
+                f.write_str(")")
+            }
+            Expr::Try(e) => {
+                postfix(f, e)?;
+                f.write_str("?")
+            }
+            Expr::Closure(pats, body) => {
+                f.write_str("|")?;
+                list(f, pats)?;
+                write!(f, "| {}", body)
+            }
+            Expr::Block(lets, e) => {
+                f.write_str("{ ")?;
+                for (pat, init) in lets {
+                    write!(f, "let {} = {}; ", pat, init)?;
+                }
+                write!(f, "{} }}", e)
+            }
+            Expr::Match(e, arms) => {
+                write!(f, "match {} {{ ", e)?;
+                list(f, arms)?;
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+impl Display for Lit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Lit::None => f.write_str("None"),
+            Lit::Bool(b) => write!(f, "{}", b),
+            // Out of the literals range
+            Lit::Int(i64::MIN) => write!(f, "({} - 1)", i64::MIN + 1),
+            Lit::Int(n) => write!(f, "{}", n),
+            Lit::Float(n) if n.is_nan() => f.write_str("(0.0 / 0.0)"),
+            Lit::Float(n) if n.is_infinite() => {
+                f.write_str(if *n > 0.0 { "1e400" } else { "-1e400" })
+            }
+            Lit::Float(n) => write!(f, "{:?}", n),
+            Lit::Str(s) => write!(f, "{:?}", s),
+            Lit::Char(c) => write!(f, "{:?}", c),
+        }
+    }
+}
+
+impl Display for Arm {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.pat)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {}", guard)?;
+        }
+        write!(f, " => {}", self.body)
+    }
+}
+
+impl Display for Pat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Pat::Wild => f.write_str("_"),
+            Pat::Ident(n) => f.write_str(n),
+            Pat::Bind(n, p) => write!(f, "{} @ {}", n, p),
+            Pat::Lit(l) => write!(f, "{}", l),
+            Pat::Range { lo, hi, closed } => {
+                write!(f, "{}{}{}", lo, if *closed { "..=" } else { ".." }, hi)
+            }
+            Pat::Tuple(v) => {
+                f.write_str("(")?;
+                list(f, v)?;
+                if v.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Pat::Slice(v) => {
+                f.write_str("[")?;
+                list(f, v)?;
+                f.write_str("]")
+            }
+            Pat::Or(v) => {
+                for (i, p) in v.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" | ")?;
+                    }
+                    write!(f, "{}", p)?;
+                }
+                Ok(())
+            }
+            Pat::Rest => f.write_str(".."),
+        }
+    }
+}
+
+/// Reserved words, not valid as names
+const KEYWORDS: &[&str] = &[
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Name of a variable, method or field, printed as parsed back, not a keyword
+/// nor `None`
+///
+/// ```rust
+///# use v_eval::ast::is_ident;
+/// assert!(is_ident("user_name") && is_ident("é"));
+/// assert!(!is_ident("user-name") && !is_ident("match") && !is_ident("None"));
+/// ```
+pub fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(is_ident_start)
+        && chars.all(is_ident_continue)
+        && s != "None"
+        && !KEYWORDS.contains(&s)
+}
+
+/// Names of a tree are all identifiers
+struct Names(bool);
+
+impl Visit for Names {
+    fn visit_expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Var(n) | Expr::Field(_, Member::Named(n)) | Expr::MethodCall(_, n, _)
+                if !is_ident(n) =>
+            {
+                self.0 = false
+            }
+            e if self.0 => visit_expr(self, e),
+            _ => (),
+        }
+    }
+
+    fn visit_pat(&mut self, p: &Pat) {
+        match p {
+            Pat::Ident(n) | Pat::Bind(n, _) if !is_ident(n) => self.0 = false,
+            p if self.0 => visit_pat(self, p),
+            _ => (),
+        }
+    }
+}
+
+/// Traversal of an expression, the default methods visit the children
+pub trait Visit {
+    fn visit_expr(&mut self, e: &Expr) {
+        visit_expr(self, e)
+    }
+
+    fn visit_pat(&mut self, p: &Pat) {
+        visit_pat(self, p)
+    }
+}
+
+/// Visit the children of an expression
+pub fn visit_expr<V: Visit + ?Sized>(v: &mut V, e: &Expr) {
+    match e {
+        Expr::Lit(_) | Expr::Var(_) => (),
+        Expr::Unary(_, e) | Expr::Field(e, _) | Expr::Try(e) => v.visit_expr(e),
+        Expr::Binary(l, _, r) | Expr::Index(l, r) => {
+            v.visit_expr(l);
+            v.visit_expr(r);
+        }
+        Expr::Array(es) | Expr::Tuple(es) => es.iter().for_each(|e| v.visit_expr(e)),
+        Expr::Map(entries) => entries.iter().for_each(|(_, e)| v.visit_expr(e)),
+        Expr::Range { from, to, .. } => {
+            from.iter().chain(to).for_each(|e| v.visit_expr(e));
+        }
+        Expr::MethodCall(e, _, args) => {
+            v.visit_expr(e);
+            args.iter().for_each(|e| v.visit_expr(e));
+        }
+        Expr::Closure(pats, body) => {
+            pats.iter().for_each(|p| v.visit_pat(p));
+            v.visit_expr(body);
+        }
+        Expr::Block(lets, e) => {
+            for (pat, init) in lets {
+                v.visit_expr(init);
+                v.visit_pat(pat);
+            }
+            v.visit_expr(e);
+        }
+        Expr::Match(e, arms) => {
+            v.visit_expr(e);
+            for arm in arms {
+                v.visit_pat(&arm.pat);
+                if let Some(guard) = &arm.guard {
+                    v.visit_expr(guard);
+                }
+                v.visit_expr(&arm.body);
+            }
+        }
+    }
+}
+
+/// Visit the children of a pattern
+pub fn visit_pat<V: Visit + ?Sized>(v: &mut V, p: &Pat) {
+    match p {
+        Pat::Tuple(ps) | Pat::Slice(ps) | Pat::Or(ps) => ps.iter().for_each(|p| v.visit_pat(p)),
+        Pat::Bind(_, p) => v.visit_pat(p),
+        _ => (),
+    }
+}
+
+/// Rewrite of an expression, the default methods rewrite the children
+pub trait Fold {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        fold_expr(self, e)
+    }
+
+    fn fold_pat(&mut self, p: Pat) -> Pat {
+        fold_pat(self, p)
+    }
+}
+
+/// Rewrite the children of an expression
+pub fn fold_expr<F: Fold + ?Sized>(f: &mut F, e: Expr) -> Expr {
+    let mut fold = |e: Box<Expr>| Box::new(f.fold_expr(*e));
+    match e {
+        e @ Expr::Lit(_) | e @ Expr::Var(_) => e,
+        Expr::Unary(op, e) => Expr::Unary(op, fold(e)),
+        Expr::Binary(l, op, r) => {
+            let l = fold(l);
+            Expr::Binary(l, op, fold(r))
+        }
+        Expr::Array(es) => Expr::Array(es.into_iter().map(|e| f.fold_expr(e)).collect()),
+        Expr::Tuple(es) => Expr::Tuple(es.into_iter().map(|e| f.fold_expr(e)).collect()),
+        Expr::Map(entries) => Expr::Map(
+            entries
+                .into_iter()
+                .map(|(k, e)| (k, f.fold_expr(e)))
+                .collect(),
+        ),
+        Expr::Range { from, to, closed } => {
+            let from = from.map(&mut fold);
+            Expr::Range {
+                from,
+                to: to.map(fold),
+                closed,
+            }
+        }
+        Expr::Index(e, i) => {
+            let e = fold(e);
+            Expr::Index(e, fold(i))
+        }
+        Expr::Field(e, m) => Expr::Field(fold(e), m),
+        Expr::MethodCall(e, m, args) => Expr::MethodCall(
+            Box::new(f.fold_expr(*e)),
+            m,
+            args.into_iter().map(|e| f.fold_expr(e)).collect(),
+        ),
+        Expr::Try(e) => Expr::Try(fold(e)),
+        Expr::Closure(pats, body) => {
+            let pats = pats.into_iter().map(|p| f.fold_pat(p)).collect();
+            Expr::Closure(pats, Box::new(f.fold_expr(*body)))
+        }
+        Expr::Block(lets, e) => {
+            let lets = lets
+                .into_iter()
+                .map(|(pat, init)| {
+                    let init = f.fold_expr(init);
+                    (f.fold_pat(pat), init)
+                })
+                .collect();
+            Expr::Block(lets, Box::new(f.fold_expr(*e)))
+        }
+        Expr::Match(e, arms) => {
+            let e = Box::new(f.fold_expr(*e));
+            let arms = arms
+                .into_iter()
+                .map(|arm| Arm {
+                    pat: f.fold_pat(arm.pat),
+                    guard: arm.guard.map(|g| f.fold_expr(g)),
+                    body: f.fold_expr(arm.body),
+                })
+                .collect();
+            Expr::Match(e, arms)
+        }
+    }
+}
+
+/// Rewrite the children of a pattern
+pub fn fold_pat<F: Fold + ?Sized>(f: &mut F, p: Pat) -> Pat {
+    let mut fold = |ps: Vec<Pat>| ps.into_iter().map(|p| f.fold_pat(p)).collect();
+    match p {
+        Pat::Tuple(ps) => Pat::Tuple(fold(ps)),
+        Pat::Slice(ps) => Pat::Slice(fold(ps)),
+        Pat::Or(ps) => Pat::Or(fold(ps)),
+        Pat::Bind(n, p) => Pat::Bind(n, Box::new(f.fold_pat(*p))),
+        p => p,
+    }
+}
@@ -0,0 +1,775 @@
+//! Parser of the evaluated grammar, a subset of the Rust expressions
+//!
+//! Binary operators are grouped by the precedence of the evaluation,
+//! see [`Operator`](crate::operator::Operator)
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+
+use super::{
+    is_ident_continue, is_ident_start, Arm, BinOp, Expr, Lit, Member, Pat, UnOp, KEYWORDS,
+};
+use crate::{Error, Limit};
+
+/// Tree of a source, `Error::Parse` with syntax not evaluated
+///
+/// The tree nests up to `max_depth` expressions, operators and patterns,
+/// `Error::Limit` over it
+pub(crate) fn parse(src: &str, max_depth: Option<usize>) -> Result<Expr, Error> {
+    let tokens = tokenize(src).ok_or(Error::Parse)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+        max_depth: max_depth.unwrap_or(usize::MAX),
+        too_deep: false,
+    };
+    match parser.expr() {
+        Some(e) if parser.pos == parser.tokens.len() => Ok(e),
+        _ if parser.too_deep => Err(Error::Limit(Limit::Depth)),
+        _ => Err(Error::Parse),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Lit(Lit),
+    Punct(&'static str),
+}
+
+/// Longest first
+const PUNCTS: &[&str] = &[
+    "..=", "...", "::", "..", "&&", "||", "==", "!=", "<=", ">=", "=>", "+", "-", "*", "/", "%",
+    "<", ">", "!", "&", "|", "=", ".", ",", ";", ":", "?", "@", "(", ")", "[", "]", "{", "}",
+];
+
+const INT_SUFFIXES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+fn tokenize(src: &str) -> Option<Vec<Token>> {
+    let mut lexer = Lexer { src, pos: 0 };
+    let mut tokens = vec![];
+    loop {
+        lexer.trivia()?;
+        let c = match lexer.peek() {
+            Some(c) => c,
+            None => break,
+        };
+        let token = match c {
+            // Tuple index, `t.0.1` is not a float
+            '0'..='9' if tokens.last() == Some(&Token::Punct(".")) => {
+                Token::Lit(Lit::Int(lexer.take(|c| c.is_ascii_digit()).parse().ok()?))
+            }
+            '0'..='9' => lexer.number()?,
+            '"' => lexer.string()?,
+            'r' if matches!(lexer.nth(1), Some('"') | Some('#')) => lexer.raw_string()?,
+            '\'' => lexer.char()?,
+            c if is_ident_start(c) => Token::Ident(lexer.take(is_ident_continue).to_owned()),
+            _ => lexer.punct()?,
+        };
+        tokens.push(token);
+    }
+
+    Some(tokens)
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Skip whitespace and comments, `None` with an unterminated comment
+    fn trivia(&mut self) -> Option<()> {
+        loop {
+            self.take(char::is_whitespace);
+            if self.eat("//") {
+                self.take(|c| c != '\n');
+            } else if self.eat("/*") {
+                let mut depth = 1;
+                while depth > 0 {
+                    if self.eat("/*") {
+                        depth += 1;
+                    } else if self.eat("*/") {
+                        depth -= 1;
+                    } else {
+                        self.bump()?;
+                    }
+                }
+            } else {
+                return Some(());
+            }
+        }
+    }
+
+    /// Literal suffixes are only valid on numbers
+    fn no_suffix(&self) -> Option<()> {
+        match self.peek() {
+            Some(c) if is_ident_start(c) => None,
+            _ => Some(()),
+        }
+    }
+
+    fn number(&mut self) -> Option<Token> {
+        let radix = match self.rest().get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+            let digits = self
+                .take(|c| {
+                    c == '_' || (c.is_ascii_hexdigit() && (radix == 16 || c.is_ascii_digit()))
+                })
+                .replace('_', "");
+            let suffix = self.take(is_ident_continue);
+            if !suffix.is_empty() && !INT_SUFFIXES.contains(&suffix) {
+                return None;
+            }
+            let n = u64::from_str_radix(&digits, radix).ok()?;
+            return i64::try_from(n).ok().map(|n| Token::Lit(Lit::Int(n)));
+        }
+
+        let start = self.pos;
+        let mut float = false;
+        self.take(|c| c == '_' || c.is_ascii_digit());
+        // `1..2` is a range and `1.max(2)` a method call
+        if self.peek() == Some('.')
+            && !matches!(self.nth(1), Some(c) if c == '.' || is_ident_start(c))
+        {
+            float = true;
+            self.bump();
+            self.take(|c| c == '_' || c.is_ascii_digit());
+        }
+        let exponent = match (self.peek(), self.nth(1), self.nth(2)) {
+            (Some('e') | Some('E'), Some(c), _) if c.is_ascii_digit() => true,
+            (Some('e') | Some('E'), Some('+') | Some('-'), Some(c)) => c.is_ascii_digit(),
+            _ => false,
+        };
+        if exponent {
+            float = true;
+            self.pos += 2;
+            self.take(|c| c == '_' || c.is_ascii_digit());
+        }
+        let digits = self.src[start..self.pos].replace('_', "");
+
+        match self.take(is_ident_continue) {
+            "f32" | "f64" => float = true,
+            "" => (),
+            s if !float && INT_SUFFIXES.contains(&s) => (),
+            _ => return None,
+        }
+        Some(Token::Lit(if float {
+            Lit::Float(digits.parse().ok()?)
+        } else {
+            Lit::Int(digits.parse().ok()?)
+        }))
+    }
+
+    fn escape(&mut self) -> Option<char> {
+        Some(match self.bump()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '\\' => '\\',
+            '0' => '\0',
+            '\'' => '\'',
+            '"' => '"',
+            'x' => {
+                let hex = self.rest().get(..2)?;
+                self.pos += 2;
+                match u8::from_str_radix(hex, 16).ok()? {
+                    n if n < 0x80 => char::from(n),
+                    _ => return None,
+                }
+            }
+            'u' => {
+                if !self.eat("{") {
+                    return None;
+                }
+                let hex = self
+                    .take(|c| c == '_' || c.is_ascii_hexdigit())
+                    .replace('_', "");
+                if hex.is_empty() || hex.len() > 6 || !self.eat("}") {
+                    return None;
+                }
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            _ => return None,
+        })
+    }
+
+    fn string(&mut self) -> Option<Token> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump()? {
+                '"' => break,
+                // Line continuation
+                '\\' if matches!(self.peek(), Some('\n') | Some('\r')) => {
+                    self.take(char::is_whitespace);
+                }
+                '\\' => s.push(self.escape()?),
+                c => s.push(c),
+            }
+        }
+        self.no_suffix()?;
+
+        Some(Token::Lit(Lit::Str(s)))
+    }
+
+    /// `r"..."` or `r#"..."#`
+    fn raw_string(&mut self) -> Option<Token> {
+        self.bump();
+        let hashes = self.take(|c| c == '#').len();
+        if !self.eat("\"") {
+            return None;
+        }
+        let end = format!("\"{}", "#".repeat(hashes));
+        let len = self.rest().find(&end)?;
+        let s = self.rest()[..len].to_owned();
+        self.pos += len + end.len();
+        self.no_suffix()?;
+
+        Some(Token::Lit(Lit::Str(s)))
+    }
+
+    fn char(&mut self) -> Option<Token> {
+        self.bump();
+        let c = match self.bump()? {
+            '\\' => self.escape()?,
+            '\'' => return None,
+            c => c,
+        };
+        // Labels and lifetimes are not evaluated
+        if !self.eat("'") {
+            return None;
+        }
+        self.no_suffix()?;
+
+        Some(Token::Lit(Lit::Char(c)))
+    }
+
+    fn punct(&mut self) -> Option<Token> {
+        let p = PUNCTS.iter().find(|p| self.rest().starts_with(*p))?;
+        self.pos += p.len();
+
+        Some(Token::Punct(p))
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Nesting of the tree being parsed
+    depth: usize,
+    max_depth: usize,
+    /// The nesting exceeded `max_depth`
+    too_deep: bool,
+}
+
+impl Parser {
+    /// Check the tree nests `n` levels more
+    fn nest(&mut self, n: usize) -> Option<()> {
+        if self.depth.saturating_add(n) > self.max_depth {
+            self.too_deep = true;
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Parse `n` levels deeper in the tree
+    fn nested<T>(&mut self, n: usize, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        self.nest(n)?;
+        self.depth += n;
+        let e = f(self);
+        self.depth -= n;
+        e
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn is(&self, p: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(q)) if *q == p)
+    }
+
+    fn eat(&mut self, p: &str) -> bool {
+        let is = self.is(p);
+        if is {
+            self.pos += 1;
+        }
+        is
+    }
+
+    fn expect(&mut self, p: &str) -> Option<()> {
+        if self.eat(p) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn eat_keyword(&mut self, k: &str) -> bool {
+        let is = matches!(self.peek(), Some(Token::Ident(i)) if i == k);
+        if is {
+            self.pos += 1;
+        }
+        is
+    }
+
+    /// Name of a variable, method or field
+    fn name(&mut self) -> Option<String> {
+        match self.next()? {
+            Token::Ident(i) if !KEYWORDS.contains(&i.as_str()) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// No expression follows, as the open end of a range
+    fn ends(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(Token::Punct(p)) => matches!(*p, ")" | "]" | "}" | "," | ";" | "=>"),
+            _ => false,
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        if self.is("|") || self.is("||") {
+            return self.closure();
+        }
+        let from = if self.is("..") || self.is("..=") {
+            None
+        } else {
+            let e = self.binary()?;
+            if !self.is("..") && !self.is("..=") {
+                return Some(e);
+            }
+            Some(Box::new(e))
+        };
+
+        let closed = self.eat("..=");
+        if !closed {
+            self.expect("..")?;
+        }
+        let to = if self.ends() && !closed {
+            None
+        } else {
+            Some(Box::new(self.binary()?))
+        };
+
+        Some(Expr::Range { from, to, closed })
+    }
+
+    fn closure(&mut self) -> Option<Expr> {
+        let mut pats = vec![];
+        if !self.eat("||") {
+            self.expect("|")?;
+            while !self.eat("|") {
+                pats.push(self.pat_case()?);
+                if !self.eat(",") {
+                    self.expect("|")?;
+                    break;
+                }
+            }
+        }
+
+        Some(Expr::Closure(pats, Box::new(self.nested(1, Self::expr)?)))
+    }
+
+    fn bin_op(&mut self) -> Option<BinOp> {
+        let op = match self.peek()? {
+            Token::Punct(p) => match *p {
+                "+" => BinOp::Add,
+                "-" => BinOp::Sub,
+                "*" => BinOp::Mul,
+                "/" => BinOp::Div,
+                "%" => BinOp::Rem,
+                "==" => BinOp::Eq,
+                "!=" => BinOp::Ne,
+                "<" => BinOp::Lt,
+                "<=" => BinOp::Le,
+                ">" => BinOp::Gt,
+                ">=" => BinOp::Ge,
+                "&&" => BinOp::And,
+                "||" => BinOp::Or,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.pos += 1;
+
+        Some(op)
+    }
+
+    /// Binary chain grouped by precedence, left associative
+    fn binary(&mut self) -> Option<Expr> {
+        let mut operands = vec![(self.unary()?, 0)];
+        let mut ops: Vec<BinOp> = vec![];
+        while let Some(op) = self.bin_op() {
+            while ops
+                .last()
+                .is_some_and(|last| !op.operator().gt_preference(last.operator()))
+            {
+                self.reduce(&mut operands, &mut ops)?;
+            }
+            ops.push(op);
+            operands.push((self.unary()?, 0));
+        }
+        while !ops.is_empty() {
+            self.reduce(&mut operands, &mut ops)?;
+        }
+
+        operands.pop().map(|(e, _)| e)
+    }
+
+    /// Group the last operator with its operands, each with the height of
+    /// its binary chain
+    fn reduce(&mut self, operands: &mut Vec<(Expr, usize)>, ops: &mut Vec<BinOp>) -> Option<()> {
+        let op = ops.pop()?;
+        let (right, r) = operands.pop()?;
+        let (left, l) = operands.pop()?;
+        let height = l.max(r) + 1;
+        self.nest(height)?;
+        operands.push((Expr::Binary(Box::new(left), op, Box::new(right)), height));
+
+        Some(())
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        let mut ops = vec![];
+        loop {
+            if self.eat("!") {
+                ops.push(UnOp::Not);
+            } else if self.eat("-") {
+                ops.push(UnOp::Neg);
+            } else if self.eat("&") || self.eat("&&") {
+                // References evaluate as their value
+                self.eat_keyword("mut");
+            } else {
+                break;
+            }
+        }
+
+        let e = self.nested(ops.len(), Self::postfix)?;
+        Some(
+            ops.into_iter()
+                .rev()
+                .fold(e, |e, op| Expr::Unary(op, Box::new(e))),
+        )
+    }
+
+    fn postfix(&mut self) -> Option<Expr> {
+        let mut e = self.primary()?;
+        let mut height = 0;
+        loop {
+            e = if self.eat("?") {
+                Expr::Try(Box::new(e))
+            } else if self.eat("[") {
+                let index = self.nested(1, Self::expr)?;
+                self.expect("]")?;
+                Expr::Index(Box::new(e), Box::new(index))
+            } else if self.eat(".") {
+                if let Some(Token::Lit(Lit::Int(i))) = self.peek() {
+                    let i = u32::try_from(*i).ok()?;
+                    self.pos += 1;
+                    Expr::Field(Box::new(e), Member::Unnamed(i))
+                } else {
+                    let name = self.name()?;
+                    if self.eat("(") {
+                        Expr::MethodCall(Box::new(e), name, self.nested(1, |p| p.list(")"))?)
+                    } else {
+                        Expr::Field(Box::new(e), Member::Named(name))
+                    }
+                }
+            } else {
+                return Some(e);
+            };
+            height += 1;
+            self.nest(height)?;
+        }
+    }
+
+    fn primary(&mut self) -> Option<Expr> {
+        Some(match self.peek()? {
+            Token::Lit(l) => {
+                let l = l.clone();
+                self.pos += 1;
+                Expr::Lit(l)
+            }
+            Token::Ident(i) => match i.as_str() {
+                "true" | "false" => {
+                    let b = i == "true";
+                    self.pos += 1;
+                    Expr::Lit(Lit::Bool(b))
+                }
+                "None" => {
+                    self.pos += 1;
+                    Expr::Lit(Lit::None)
+                }
+                "match" => {
+                    self.pos += 1;
+                    self.nested(1, Self::match_arms)?
+                }
+                _ => Expr::Var(self.name()?),
+            },
+            Token::Punct("(") => {
+                self.pos += 1;
+                self.nested(1, Self::paren)?
+            }
+            Token::Punct("[") => {
+                self.pos += 1;
+                Expr::Array(self.nested(1, |p| p.list("]"))?)
+            }
+            Token::Punct("{") => {
+                self.pos += 1;
+                if self.is_map() {
+                    self.nested(1, Self::map)?
+                } else {
+                    self.nested(1, Self::block)?
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    /// Parenthesized expression or tuple, after the parenthesis
+    fn paren(&mut self) -> Option<Expr> {
+        if self.eat(")") {
+            return Some(Expr::Tuple(vec![]));
+        }
+        let e = self.expr()?;
+        if self.eat(")") {
+            return Some(e);
+        }
+        self.expect(",")?;
+        let mut items = vec![e];
+        items.extend(self.list(")")?);
+
+        Some(Expr::Tuple(items))
+    }
+
+    /// Comma separated expressions up to `close`
+    fn list(&mut self, close: &str) -> Option<Vec<Expr>> {
+        let mut items = vec![];
+        while !self.eat(close) {
+            items.push(self.expr()?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+
+        Some(items)
+    }
+
+    /// A map follows the brace, it is empty or a key and a colon start it
+    fn is_map(&self) -> bool {
+        self.is("}")
+            || matches!(
+                (self.peek(), self.tokens.get(self.pos + 1)),
+                (Some(Token::Lit(Lit::Str(_))), Some(Token::Punct(":")))
+            )
+    }
+
+    /// `{"key": e, ...}`, after the brace
+    fn map(&mut self) -> Option<Expr> {
+        let mut entries = vec![];
+        while !self.eat("}") {
+            let k = match self.next()? {
+                Token::Lit(Lit::Str(k)) => k,
+                _ => return None,
+            };
+            self.expect(":")?;
+            entries.push((k, self.expr()?));
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+
+        Some(Expr::Map(entries))
+    }
+
+    /// `{ let pat = init; ...; e }`, after the brace
+    fn block(&mut self) -> Option<Expr> {
+        let mut lets = vec![];
+        while self.eat_keyword("let") {
+            let pat = self.pat()?;
+            self.expect("=")?;
+            let init = self.expr()?;
+            self.expect(";")?;
+            lets.push((pat, init));
+        }
+        let e = self.expr()?;
+        self.expect("}")?;
+
+        Some(Expr::Block(lets, Box::new(e)))
+    }
+
+    /// `e { arms }`, after `match`
+    fn match_arms(&mut self) -> Option<Expr> {
+        let e = self.expr()?;
+        self.expect("{")?;
+        let mut arms = vec![];
+        while !self.eat("}") {
+            let pat = self.pat()?;
+            let guard = if self.eat_keyword("if") {
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            self.expect("=>")?;
+            let body = self.expr()?;
+            // The comma is optional after a block
+            let block = matches!(body, Expr::Block(..) | Expr::Match(..));
+            arms.push(Arm { pat, guard, body });
+            if !self.eat(",") && !block {
+                self.expect("}")?;
+                break;
+            }
+        }
+
+        Some(Expr::Match(Box::new(e), arms))
+    }
+
+    /// Pattern with alternatives
+    fn pat(&mut self) -> Option<Pat> {
+        self.eat("|");
+        let mut cases = vec![self.pat_case()?];
+        while self.eat("|") {
+            cases.push(self.pat_case()?);
+        }
+
+        Some(if cases.len() == 1 {
+            cases.pop()?
+        } else {
+            Pat::Or(cases)
+        })
+    }
+
+    fn pat_case(&mut self) -> Option<Pat> {
+        Some(match self.peek()? {
+            Token::Punct("(") => {
+                self.pos += 1;
+                let (pats, comma) = self.nested(1, |p| p.pats(")"))?;
+                // A parenthesized pattern is not a tuple
+                if pats.len() == 1 && !comma {
+                    return pats.into_iter().next();
+                }
+                Pat::Tuple(pats)
+            }
+            Token::Punct("[") => {
+                self.pos += 1;
+                Pat::Slice(self.nested(1, |p| p.pats("]"))?.0)
+            }
+            Token::Ident(i) if i == "_" => {
+                self.pos += 1;
+                Pat::Wild
+            }
+            Token::Ident(i) if i == "None" => {
+                self.pos += 1;
+                Pat::Lit(Lit::None)
+            }
+            Token::Punct("..") => {
+                self.pos += 1;
+                Pat::Rest
+            }
+            Token::Ident(i) if i != "true" && i != "false" => {
+                let name = self.name()?;
+                if self.eat("@") {
+                    Pat::Bind(name, Box::new(self.nested(1, Self::pat_case)?))
+                } else {
+                    Pat::Ident(name)
+                }
+            }
+            _ => {
+                let lo = self.pat_lit()?;
+                let closed = self.eat("..=");
+                if closed || self.eat("..") {
+                    Pat::Range {
+                        lo,
+                        hi: self.pat_lit()?,
+                        closed,
+                    }
+                } else {
+                    Pat::Lit(lo)
+                }
+            }
+        })
+    }
+
+    /// Comma separated patterns up to `close`, and whether a comma ends them
+    fn pats(&mut self, close: &str) -> Option<(Vec<Pat>, bool)> {
+        let mut pats = vec![];
+        let mut comma = false;
+        while !self.eat(close) {
+            pats.push(self.pat()?);
+            comma = self.eat(",");
+            if !comma {
+                self.expect(close)?;
+                break;
+            }
+        }
+
+        Some((pats, comma))
+    }
+
+    /// Literal of a pattern, numbers can be negative
+    fn pat_lit(&mut self) -> Option<Lit> {
+        let neg = self.eat("-");
+        match (self.next()?, neg) {
+            (Token::Lit(Lit::Int(n)), true) => Some(Lit::Int(-n)),
+            (Token::Lit(Lit::Float(n)), true) => Some(Lit::Float(-n)),
+            (Token::Lit(l), false) => Some(l),
+            (Token::Ident(b), false) if b == "true" || b == "false" => Some(Lit::Bool(b == "true")),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{reflect::Ctx, CompiledExpr, Error, Eval, Value};
+
+/// Evaluator of compiled expressions record by record, see [`Eval::batch`]
+pub struct Batch<'a> {
+    base: &'a Eval,
+    values: BTreeMap<String, Value>,
+    exprs: &'a [CompiledExpr],
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(base: &'a Eval, exprs: &'a [CompiledExpr]) -> Self {
+        Self {
+            base,
+            values: BTreeMap::new(),
+            exprs,
+        }
+    }
+
+    /// Results of the expressions in order, with the record bound in a scope
+    /// over the context
+    pub fn eval<I>(&mut self, record: I) -> Vec<Result<Value, Error>>
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        self.values.clear();
+        self.values.extend(record);
+
+        let ctx = Ctx::scoped(&self.base.0, &self.values);
+        self.exprs
+            .iter()
+            .map(|c| c.try_eval_ctx(ctx, self.base.1))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{borrow::ToOwned, vec};
+
+    use super::*;
+
+    #[test]
+    fn test_batch() {
+        let e = Eval::default()
+            .insert("a", "1")
+            .unwrap()
+            .insert("b", "a + 1")
+            .unwrap();
+        let exprs = [e.compile("a + b").unwrap(), e.compile("c").unwrap()];
+        let records = vec![
+            vec![
+                ("a".to_owned(), Value::Int(2)),
+                ("c".to_owned(), Value::Bool(true)),
+            ],
+            vec![
+                ("a".to_owned(), Value::Int(3)),
+                ("a".to_owned(), Value::Int(4)),
+            ],
+        ];
+
+        let results: Vec<_> = e.eval_batch(&exprs, records).collect();
+        assert_eq!(
+            results,
+            [
+                vec![Ok(Value::Int(5)), Ok(Value::Bool(true))],
+                vec![Ok(Value::Int(9)), Err(Error::None)]
+            ]
+        );
+        assert_eq!(
+            e.eval("(a, b)"),
+            Some(Value::Tuple(vec![1.into(), 2.into()]))
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Batch evaluation of CSV and JSON-lines records
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+};
+
+use serde_json::{Map, Value as Json};
+use v_eval::{ast, CompiledExpr, Error, Eval, Value};
+
+use crate::json;
+
+/// Format of the records
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// With a header, fields are typed as literals or else strings
+    Csv,
+    /// One JSON object by line
+    JsonLines,
+}
+
+/// Evaluate the expressions for each record of input, written with a new
+/// field by name for each result, `Ok(false)` when an evaluation fails
+pub fn run(
+    e: &Eval,
+    format: Format,
+    names: &[String],
+    exprs: &[CompiledExpr],
+    input: impl BufRead,
+    output: impl Write,
+) -> Result<bool, String> {
+    match format {
+        Format::Csv => run_csv(e, names, exprs, input, output),
+        Format::JsonLines => run_json_lines(e, names, exprs, input, output),
+    }
+}
+
+fn run_csv(
+    e: &Eval,
+    names: &[String],
+    exprs: &[CompiledExpr],
+    input: impl BufRead,
+    output: impl Write,
+) -> Result<bool, String> {
+    let mut reader = csv::Reader::from_reader(input);
+    let mut writer = csv::Writer::from_writer(output);
+    let headers = reader.headers().map_err(error)?.clone();
+    writer
+        .write_record(headers.iter().chain(names.iter().map(String::as_str)))
+        .map_err(error)?;
+
+    let mut batch = e.batch(exprs);
+    let mut ok = true;
+    for (n, record) in reader.records().enumerate() {
+        let record = record.map_err(error)?;
+        let bound = headers
+            .iter()
+            .zip(&record)
+            .map(|(k, v)| (k.to_owned(), field(v)));
+        let results = batch
+            .eval(bound)
+            .into_iter()
+            .zip(names)
+            .map(|(v, name)| match v {
+                Ok(Value::Str(s)) => s,
+                Ok(Value::None) => String::new(),
+                Ok(v) => ast::Expr::from(v).to_string(),
+                Err(err) => {
+                    ok = report(n, name, err);
+                    String::new()
+                }
+            });
+        let results: Vec<_> = results.collect();
+        writer
+            .write_record(record.iter().chain(results.iter().map(String::as_str)))
+            .map_err(error)?;
+    }
+    writer.flush().map_err(error)?;
+
+    Ok(ok)
+}
+
+fn run_json_lines(
+    e: &Eval,
+    names: &[String],
+    exprs: &[CompiledExpr],
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<bool, String> {
+    let mut batch = e.batch(exprs);
+    let mut ok = true;
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut record: Map<String, Json> =
+            serde_json::from_str(&line).map_err(|err| format!("line {}: {}", n + 1, err))?;
+        let bound = record.iter().map(|(k, v)| (k.clone(), json::to_value(v)));
+        for (v, name) in batch.eval(bound).into_iter().zip(names) {
+            let v = match v {
+                Ok(v) => json::from_value(&v),
+                Err(err) => {
+                    ok = report(n, name, err);
+                    Json::Null
+                }
+            };
+            record.insert(name.clone(), v);
+        }
+        serde_json::to_writer(&mut output, &record).map_err(error)?;
+        writeln!(output).map_err(error)?;
+    }
+    output.flush().map_err(error)?;
+
+    Ok(ok)
+}
+
+/// Value of a CSV field: empty is `None`, a `bool` or number literal is its
+/// value, otherwise a string
+fn field(s: &str) -> Value {
+    if s.is_empty() {
+        Value::None
+    } else if let Ok(b) = s.parse() {
+        Value::Bool(b)
+    } else if let Ok(n) = s.parse() {
+        Value::Int(n)
+    } else {
+        match s.parse::<f64>() {
+            Ok(n) if n.is_finite() => Value::Float(n),
+            _ => Value::Str(s.to_owned()),
+        }
+    }
+}
+
+/// Report the failed evaluation of the `n` record, always `false`
+fn report(n: usize, name: &str, err: Error) -> bool {
+    eprintln!("error: record {}: `{}`: {}", n + 1, name, err);
+    false
+}
+
+fn error(e: impl Display) -> String {
+    e.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(format: Format, input: &str) -> (bool, String) {
+        let e = Eval::default().insert("limit", "100").unwrap();
+        let names = ["over".to_owned(), "label".to_owned()];
+        let exprs = [
+            e.compile("amount > limit").unwrap(),
+            e.compile("name.to_uppercase()").unwrap(),
+        ];
+        let mut out = vec![];
+        let ok = super::run(&e, format, &names, &exprs, input.as_bytes(), &mut out).unwrap();
+
+        (ok, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_csv() {
+        let (ok, out) = run(Format::Csv, "name,amount\nfoo,150\n\"b,ar\",99.5\n");
+        assert!(ok);
+        assert_eq!(
+            out,
+            "name,amount,over,label\nfoo,150,true,FOO\n\"b,ar\",99.5,false,\"B,AR\"\n"
+        );
+
+        let (ok, out) = run(Format::Csv, "name,amount\n,1\n");
+        assert!(!ok);
+        assert_eq!(out, "name,amount,over,label\n,1,false,\n");
+
+        assert_eq!(field("true"), Value::Bool(true));
+        assert_eq!(field("-2"), Value::Int(-2));
+        assert_eq!(field("1e3"), Value::Float(1000.0));
+        assert_eq!(field("inf"), Value::Str("inf".into()));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let input = "{\"name\": \"foo\", \"amount\": 150}\n\n{\"amount\": 1, \"o\": {}}\n";
+        let (ok, out) = run(Format::JsonLines, input);
+        assert!(!ok);
+        assert_eq!(
+            out,
+            "{\"amount\":150,\"label\":\"FOO\",\"name\":\"foo\",\"over\":true}\n\
+             {\"amount\":1,\"label\":null,\"o\":{},\"over\":false}\n"
+        );
+
+        let e = Eval::default();
+        let err = super::run(&e, Format::JsonLines, &[], &[], "[1]".as_bytes(), vec![]);
+        assert!(err.unwrap_err().starts_with("line 1: "));
+    }
+}
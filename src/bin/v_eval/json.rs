@@ -0,0 +1,80 @@
+//! Values of JSON values
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value as Json};
+use v_eval::{ast, Value};
+
+/// Value of a JSON value, objects are maps
+pub fn to_value(v: &Json) -> Value {
+    match v {
+        Json::Null => Value::None,
+        Json::Bool(b) => Value::Bool(*b),
+        Json::Number(n) => match n.as_i64() {
+            Some(n) => Value::Int(n),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(s) => Value::Str(s.clone()),
+        Json::Array(v) => Value::Vec(v.iter().map(to_value).collect()),
+        Json::Object(o) => Value::Map(o.iter().map(|(k, v)| (k.clone(), to_value(v))).collect()),
+    }
+}
+
+/// JSON value of a value, ranges are `{"start", "end"}` objects with
+/// `"inclusive": true` when the end is in the range
+pub fn from_value(v: &Value) -> Json {
+    match v {
+        Value::None => Json::Null,
+        Value::Bool(b) => json!(b),
+        Value::Int(n) => json!(n),
+        Value::Float(n) => json!(n),
+        Value::Str(s) => json!(s),
+        Value::Vec(v) | Value::Tuple(v) => Json::Array(v.iter().map(from_value).collect()),
+        Value::Map(m) => Json::Object(m.iter().map(|(k, v)| (k.clone(), from_value(v))).collect()),
+        Value::Range(r) => json!({"start": r.start, "end": r.end}),
+        Value::FloatRange(r) => json!({"start": r.start, "end": r.end}),
+        Value::CharRange(r) => json!({"start": r.start, "end": r.end}),
+        Value::RangeInclusive(r) => json!({"start": r.start(), "end": r.end(), "inclusive": true}),
+        Value::FloatRangeInclusive(r) => {
+            json!({"start": r.start(), "end": r.end(), "inclusive": true})
+        }
+        Value::CharRangeInclusive(r) => {
+            json!({"start": r.start(), "end": r.end(), "inclusive": true})
+        }
+    }
+}
+
+/// Context of the members of a JSON object
+pub fn context(src: &str) -> Result<BTreeMap<String, ast::Expr>, String> {
+    match serde_json::from_str(src).map_err(|e| e.to_string())? {
+        Json::Object(o) => Ok(o
+            .iter()
+            .map(|(k, v)| (k.clone(), to_value(v).into()))
+            .collect()),
+        _ => Err("context is not an object".into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_context() {
+        let c =
+            context(r#"{"a": 1, "b": [2.5, "c", null, true], "d": 18446744073709551615}"#).unwrap();
+        let c: Vec<_> = c.iter().map(|(k, v)| format!("{} = {}", k, v)).collect();
+        assert_eq!(
+            c,
+            [
+                "a = 1",
+                r#"b = [2.5, "c", None, true]"#,
+                "d = 1.8446744073709552e19"
+            ]
+        );
+
+        assert!(context("[1]").is_err());
+        assert!(context("{").is_err());
+        let c = context(r#"{"a": {"b": 1, "c": [{}]}}"#).unwrap();
+        assert_eq!(c["a"].to_string(), r#"{"b": 1, "c": [{}]}"#);
+    }
+}
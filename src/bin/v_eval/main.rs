@@ -0,0 +1,405 @@
+//! Command-line evaluator, with the `cli` feature
+//!
+//! ```sh
+//! $ v_eval 'a + 1' --var a=2
+//! 3
+//! $ v_eval --context ctx.json < rules.txt
+//! $ v_eval --csv history.csv -e flagged='amount > 100' -e 'score=amount / 10'
+//! $ v_eval
+//! > :let a = [1, 2]
+//! > :type a.len()
+//! Int
+//! ```
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write},
+    mem, process,
+};
+
+use v_eval::{ast, CompiledExpr, Eval, NoneMode, Schema};
+
+use self::batch::Format;
+
+mod batch;
+mod json;
+
+const USAGE: &str = "\
+Usage: v_eval [OPTIONS] [EXPR]
+
+Evaluate EXPR, else each line of stdin, else start a REPL
+
+With --csv or --jsonl, evaluate EXPR, as the `result` field, and each --eval
+for each record of FILE, `-` for stdin, binding its fields as variables, and
+write the records with the results as new fields
+
+Options:
+  -v, --var NAME=EXPR     Insert in context NAME with expression EXPR
+  -c, --context FILE      Insert in context the members of a JSON object
+      --csv FILE          Evaluate for each record of a CSV file with header
+      --jsonl FILE        Evaluate for each object of a JSON-lines file
+  -e, --eval NAME=EXPR    Evaluate EXPR for each record into the NAME field
+  -n, --negative-index    Negative indexes count from the end
+  -s, --strict            Undefined identifiers fail the evaluation
+  -h, --help              Print this help
+  -V, --version           Print the version
+";
+
+const COMMANDS: &str = "\
+Commands, also read from stdin:
+  :let NAME = EXPR        Insert in context NAME with expression EXPR
+  :vars                   List the context
+  :type EXPR              Static type of EXPR
+  :help                   Print the commands
+  :quit                   Exit
+";
+
+/// Parsed command line
+enum Cmd {
+    Help,
+    Version,
+    Run(Option<String>, Eval),
+    Batch {
+        format: Format,
+        input: String,
+        names: Vec<String>,
+        exprs: Vec<CompiledExpr>,
+        eval: Eval,
+    },
+}
+
+/// Reply to a line of input
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Print(String),
+    Done,
+    Quit,
+}
+
+fn main() {
+    let (expr, mut e) = match parse_args(env::args().skip(1)) {
+        Ok(Cmd::Help) => return print!("{}\n{}", USAGE, COMMANDS),
+        Ok(Cmd::Version) => return println!("v_eval {}", env!("CARGO_PKG_VERSION")),
+        Ok(Cmd::Run(expr, e)) => (expr, e),
+        Ok(Cmd::Batch {
+            format,
+            input,
+            names,
+            exprs,
+            eval,
+        }) => {
+            let output = BufWriter::new(io::stdout().lock());
+            let res = open(&input)
+                .and_then(|input| batch::run(&eval, format, &names, &exprs, input, output));
+            match res {
+                Ok(true) => return,
+                Ok(false) => process::exit(1),
+                Err(msg) => {
+                    eprintln!("v_eval: {}", msg);
+                    process::exit(1);
+                }
+            }
+        }
+        Err(msg) => {
+            eprintln!("v_eval: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let ok = match expr {
+        Some(expr) => match e.try_eval(&expr) {
+            Ok(v) => {
+                println!("{}", ast::Expr::from(v));
+                true
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                false
+            }
+        },
+        None => {
+            let stdin = io::stdin();
+            let interactive = stdin.is_terminal();
+            run(&mut e, stdin.lock(), interactive).unwrap_or_else(|err| {
+                eprintln!("v_eval: {}", err);
+                false
+            })
+        }
+    };
+    if !ok {
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Cmd, String> {
+    let mut args = args.into_iter();
+    let mut expr = None;
+    let mut vars = vec![];
+    let mut contexts = vec![];
+    let mut input = None;
+    let mut outputs = vec![];
+    let mut negative_index = false;
+    let mut strict = false;
+
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_owned(), Some(value.into())),
+            _ => (arg, None),
+        };
+        let value = |name: &str| {
+            value
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("`{}` needs a value", name))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Cmd::Help),
+            "-V" | "--version" => return Ok(Cmd::Version),
+            "-v" | "--var" => vars.push(value(&flag)?),
+            "-c" | "--context" => contexts.push(value(&flag)?),
+            "--csv" => input = Some((Format::Csv, value(&flag)?)),
+            "--jsonl" => input = Some((Format::JsonLines, value(&flag)?)),
+            "-e" | "--eval" => outputs.push(value(&flag)?),
+            "-n" | "--negative-index" => negative_index = true,
+            "-s" | "--strict" => strict = true,
+            "--" => expr = args.next(),
+            _ if flag.starts_with('-') && flag.len() > 1 && expr.is_none() => {
+                return Err(format!("unknown option `{}`", flag))
+            }
+            _ if expr.is_none() => expr = Some(flag),
+            _ => return Err(format!("unexpected argument `{}`", flag)),
+        }
+    }
+
+    let mut context = BTreeMap::new();
+    for path in contexts {
+        let src = fs::read_to_string(&path).map_err(|err| format!("`{}`: {}", path, err))?;
+        context.extend(json::context(&src).map_err(|err| format!("`{}`: {}", path, err))?);
+    }
+    let mut e = Eval::new(context);
+    for var in vars {
+        let (name, src) = var
+            .split_once('=')
+            .ok_or_else(|| format!("`{}` is not NAME=EXPR", var))?;
+        let_var(&mut e, name.trim(), src).map_err(|err| format!("`{}`: {}", var, err))?;
+    }
+    if negative_index {
+        e = e.negative_index(true);
+    }
+    if strict {
+        e = e.none_mode(NoneMode::Strict);
+    }
+
+    let (format, input) = match input {
+        Some(input) => input,
+        None if outputs.is_empty() => return Ok(Cmd::Run(expr, e)),
+        None => return Err("`--eval` needs `--csv` or `--jsonl`".into()),
+    };
+    let mut names = vec![];
+    let mut exprs = vec![];
+    let outputs = outputs.iter().map(|output| {
+        output
+            .split_once('=')
+            .map(|(name, src)| (name.trim(), src))
+            .ok_or_else(|| format!("`{}` is not NAME=EXPR", output))
+    });
+    for output in expr
+        .as_deref()
+        .map(|src| Ok(("result", src)))
+        .into_iter()
+        .chain(outputs)
+    {
+        let (name, src) = output?;
+        names.push(name.to_owned());
+        exprs.push(
+            e.compile(src)
+                .map_err(|err| format!("`{}`: {}", src, err))?,
+        );
+    }
+    if names.is_empty() {
+        return Err("nothing to evaluate, an EXPR or `--eval` is needed".into());
+    }
+
+    Ok(Cmd::Batch {
+        format,
+        input,
+        names,
+        exprs,
+        eval: e,
+    })
+}
+
+/// Buffered reader of a file, of stdin for `-`
+fn open(path: &str) -> Result<Box<dyn BufRead>, String> {
+    match path {
+        "-" => Ok(Box::new(io::stdin().lock())),
+        path => fs::File::open(path)
+            .map(|f| Box::new(BufReader::new(f)) as Box<dyn BufRead>)
+            .map_err(|err| format!("`{}`: {}", path, err)),
+    }
+}
+
+/// Evaluate each line of input, `true` when all of them succeed
+fn run(e: &mut Eval, input: impl BufRead, interactive: bool) -> io::Result<bool> {
+    let prompt = || -> io::Result<()> {
+        if interactive {
+            print!("> ");
+            io::stdout().flush()?;
+        }
+        Ok(())
+    };
+
+    let mut ok = true;
+    prompt()?;
+    for (n, line) in input.lines().enumerate() {
+        match command(e, &line?) {
+            Ok(Reply::Print(out)) => println!("{}", out),
+            Ok(Reply::Done) => (),
+            Ok(Reply::Quit) => return Ok(ok),
+            Err(err) if interactive => eprintln!("error: {}", err),
+            Err(err) => {
+                eprintln!("error: line {}: {}", n + 1, err);
+                ok = false;
+            }
+        }
+        prompt()?;
+    }
+    if interactive {
+        println!();
+    }
+
+    Ok(ok)
+}
+
+/// Run a REPL command or evaluate an expression
+fn command(e: &mut Eval, line: &str) -> Result<Reply, String> {
+    let line = line.trim();
+    let (cmd, arg) = match line.strip_prefix(':') {
+        Some(cmd) => cmd.split_once(char::is_whitespace).unwrap_or((cmd, "")),
+        None if line.is_empty() => return Ok(Reply::Done),
+        None => {
+            return e
+                .try_eval(line)
+                .map(|v| Reply::Print(ast::Expr::from(v).to_string()))
+                .map_err(|err| err.to_string())
+        }
+    };
+
+    match cmd {
+        "let" => {
+            let (name, src) = arg.split_once('=').ok_or("expected `:let NAME = EXPR`")?;
+            let_var(e, name.trim(), src)?;
+            Ok(Reply::Done)
+        }
+        "vars" => Ok(Reply::Print(
+            e.vars()
+                .map(|(k, v)| format!("{} = {}", k, v))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )),
+        "type" => e
+            .type_check(&Schema::default(), arg)
+            .map(|t| Reply::Print(t.to_string()))
+            .map_err(|err| err.to_string()),
+        "help" => Ok(Reply::Print(COMMANDS.trim_end().into())),
+        "quit" | "q" => Ok(Reply::Quit),
+        _ => Err(format!("unknown command `:{}`, see `:help`", cmd)),
+    }
+}
+
+/// Parse and insert in context name - expression
+fn let_var(e: &mut Eval, name: &str, src: &str) -> Result<(), String> {
+    if !ast::is_ident(name) {
+        return Err(format!("`{}` is not an identifier", name));
+    }
+    let expr = e.parse(src).map_err(|err| err.to_string())?;
+    *e = mem::take(e)
+        .insert_ast(name, expr)
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Cmd, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_args() {
+        let eval = |a: &[&str]| match args(a) {
+            Ok(Cmd::Run(Some(expr), e)) => e.try_eval(&expr).map_err(|e| e.to_string()),
+            _ => panic!("expected an expression in {:?}", a),
+        };
+        assert_eq!(eval(&["a + 1", "--var", "a=2"]), Ok(3.into()));
+        assert_eq!(eval(&["-v", "a = 2", "--var=b=a * 2", "b"]), Ok(4.into()));
+        assert_eq!(eval(&["-n", "[1, 2][-1]"]), Ok(2.into()));
+        assert_eq!(eval(&["--", "-1"]), Ok((-1).into()));
+        assert_eq!(
+            eval(&["-s", "missing"]),
+            Err("undefined identifier `missing`".into())
+        );
+
+        assert!(matches!(args(&[]), Ok(Cmd::Run(None, _))));
+        assert!(matches!(args(&["a", "-h"]), Ok(Cmd::Help)));
+        assert!(matches!(args(&["--version"]), Ok(Cmd::Version)));
+        assert!(matches!(
+            args(&["--csv", "-", "-e", "x = a * 2", "a + 1"]),
+            Ok(Cmd::Batch { format: Format::Csv, names, .. }) if names == ["result", "x"]
+        ));
+        for a in [
+            &["--var"][..],
+            &["--var", "a"],
+            &["--var", "a=1 +"],
+            &["--var", "1=1"],
+            &["--var", "true=1"],
+            &["--var", "None=1"],
+            &["--nope"],
+            &["a", "b"],
+            &["--context", "not_exist.json"],
+            &["-e", "x=1"],
+            &["--jsonl", "-"],
+            &["--jsonl", "-", "-e", "x"],
+            &["--jsonl", "-", "-e", "x=1 +"],
+        ] {
+            assert!(args(a).is_err(), "{:?}", a);
+        }
+    }
+
+    #[test]
+    fn test_command() {
+        let mut e = Eval::default();
+        let mut run = |line| command(&mut e, line);
+        let print = |s: &str| Ok(Reply::Print(s.into()));
+
+        assert_eq!(run(""), Ok(Reply::Done));
+        assert_eq!(run(":let a = [1, 2]"), Ok(Reply::Done));
+        assert_eq!(run(":let b = a.len() + 1"), Ok(Reply::Done));
+        assert_eq!(run("(b, a)"), print("(3, [1, 2])"));
+        assert_eq!(run(":vars"), print("a = [1, 2]\nb = a.len() + 1"));
+        assert_eq!(run(":type b"), print("Int"));
+        assert_eq!(
+            run(":type a[0] + \"\""),
+            Err("type error: mismatched types `Int` and `Str`".into())
+        );
+        assert_eq!(run("1 +"), Err("invalid expression".into()));
+        assert_eq!(run(":let c = 1 +"), Err("invalid expression".into()));
+        assert_eq!(run(":let c"), Err("expected `:let NAME = EXPR`".into()));
+        assert_eq!(
+            run(":let match = 1"),
+            Err("`match` is not an identifier".into())
+        );
+        assert_eq!(
+            run(":let None = 2"),
+            Err("`None` is not an identifier".into())
+        );
+        assert_eq!(
+            run(":nope"),
+            Err("unknown command `:nope`, see `:help`".into())
+        );
+        assert_eq!(run(":quit"), Ok(Reply::Quit));
+        assert_eq!(run("b"), print("3"));
+    }
+}
@@ -0,0 +1,408 @@
+use alloc::collections::BTreeMap;
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+
+use crate::{
+    ast::{Arm, Expr, Lit, Member, Pat, UnOp},
+    method::{option_t::Lazy, HasArg, Method},
+    operator::Operator,
+    reflect::{optional, NoneMode, Options},
+    types::{Infer, Schema, Type},
+    Error,
+};
+
+type Check = Result<Type, Error>;
+
+fn type_err<T>(msg: String) -> Result<T, Error> {
+    Err(Error::Type(msg))
+}
+
+/// Type inference with the rules of the evaluation
+pub(crate) struct Checker<'a> {
+    ctx: &'a BTreeMap<String, Expr>,
+    schema: &'a Schema,
+    opts: Options,
+    /// Local bindings
+    scopes: Vec<BTreeMap<String, Type>>,
+    /// Types of the context variables already checked
+    memo: BTreeMap<String, Type>,
+    /// Context variables being checked
+    path: Vec<String>,
+}
+
+impl<'a> Checker<'a> {
+    pub(crate) fn check(
+        ctx: &'a BTreeMap<String, Expr>,
+        schema: &'a Schema,
+        opts: Options,
+        e: &Expr,
+    ) -> Check {
+        Checker {
+            ctx,
+            schema,
+            opts,
+            scopes: vec![],
+            memo: BTreeMap::new(),
+            path: vec![],
+        }
+        .expr(e)
+    }
+
+    fn expr(&mut self, e: &Expr) -> Check {
+        match e {
+            Expr::Binary(left, op, right) => {
+                let a = self.expr(left)?;
+                let b = self.expr(right)?;
+                self.operator(op.operator(), &a, &b)
+                    .ok_or_else(|| Error::Type(format!("mismatched types `{}` and `{}`", a, b)))
+            }
+            Expr::Lit(l) => Ok(lit(l)),
+            Expr::Var(name) => self.var(name),
+            Expr::Unary(op, e) => {
+                let t = self.expr(e)?;
+                match op {
+                    UnOp::Not => self.operator(Operator::Not, &t, &Type::Bool),
+                    UnOp::Neg => self.operator(Operator::Neg, &t, &Type::Int),
+                }
+                .ok_or_else(|| Error::Type(format!("unsupported unary operand `{}`", t)))
+            }
+            Expr::Array(elems) => {
+                let mut e = None;
+                for elem in elems {
+                    let t = self.expr(elem)?;
+                    e = Some(e.map_or(t.clone(), |e| Type::unify(e, t)));
+                }
+                Ok(Type::Vec(Box::new(e.unwrap_or(Type::Any))))
+            }
+            Expr::Range { from, to, .. } => self.range(from, to),
+            Expr::Index(e, index) => self.index(e, index),
+            Expr::MethodCall(receiver, method, args) => self.method(receiver, method, args),
+            Expr::Field(base, member) => self.field(base, member),
+            Expr::Tuple(elems) => elems
+                .iter()
+                .map(|e| self.expr(e))
+                .collect::<Result<_, _>>()
+                .map(Type::Tuple),
+            Expr::Map(entries) => entries
+                .iter()
+                .map(|(k, e)| Ok((k.clone(), self.expr(e)?)))
+                .collect::<Result<_, _>>()
+                .map(Type::Map),
+            Expr::Block(lets, e) => self.block(lets, e),
+            Expr::Match(e, arms) => self.match_arms(e, arms),
+            Expr::Try(e) => self.expr(e),
+            Expr::Closure(..) => type_err("unsupported expression".into()),
+        }
+    }
+
+    /// Operation type, `None` operands only propagate in `NoneMode::Propagate`
+    fn operator(&self, op: Operator, a: &Type, b: &Type) -> Option<Type> {
+        let nullable = a.is_nullable() || b.is_nullable();
+        if nullable && self.opts.none != NoneMode::Propagate {
+            return None;
+        }
+        let t = match (a.some(), b.some()) {
+            (Type::None, Type::None) => return Some(Type::None),
+            (Type::None, t) | (t, Type::None) => op.infer(t, t)?,
+            (a, b) => op.infer(a, b)?,
+        };
+
+        Some(if nullable { Type::option(t) } else { t })
+    }
+
+    fn var(&mut self, name: &str) -> Check {
+        if let Some(t) = self.scopes.iter().rev().find_map(|s| s.get(name)) {
+            return Ok(t.clone());
+        }
+        if let Some(t) = self.memo.get(name) {
+            return Ok(t.clone());
+        }
+
+        if let Some(e) = self.ctx.get(name) {
+            if self.path.iter().any(|p| p == name) {
+                let mut path = self.path.clone();
+                path.push(name.to_owned());
+                return Err(Error::Cycle(path));
+            }
+            self.path.push(name.to_owned());
+            let scopes = core::mem::take(&mut self.scopes);
+            let t = self.expr(e);
+            self.scopes = scopes;
+            self.path.pop();
+            let t = t?;
+            self.memo.insert(name.to_owned(), t.clone());
+            Ok(t)
+        } else if let Some(t) = self.schema.get(name) {
+            Ok(t.clone())
+        } else {
+            Err(Error::Undefined(name.to_owned()))
+        }
+    }
+
+    /// Receiver of a method call, field or index, `None` only with optional chaining
+    fn receiver(&self, t: Type, chain: bool) -> Result<(Type, bool), Error> {
+        if !t.is_nullable() {
+            Ok((t, false))
+        } else if chain {
+            Ok((t.some().clone(), true))
+        } else {
+            type_err(format!("`{}` may be None", t))
+        }
+    }
+
+    fn range(&mut self, from: &Option<Box<Expr>>, to: &Option<Box<Expr>>) -> Check {
+        let (from, to) = match (from, to) {
+            (Some(from), Some(to)) => (self.expr(from)?, self.expr(to)?),
+            _ => return type_err("open range".into()),
+        };
+        Ok(match (&from, &to) {
+            (Type::Int, Type::Int) => Type::Range,
+            (Type::Str, Type::Str) => Type::CharRange,
+            (Type::Int | Type::Float, Type::Int | Type::Float) => Type::FloatRange,
+            (Type::Any, _) | (_, Type::Any) => Type::Any,
+            _ => return type_err(format!("mismatched range `{}..{}`", from, to)),
+        })
+    }
+
+    fn index(&mut self, e: &Expr, index: &Expr) -> Check {
+        let (e, chain) = optional(e);
+        let t = self.expr(e)?;
+        let (t, wrap) = self.receiver(t, chain)?;
+
+        let at = match index {
+            Expr::Range { from, to, .. } => {
+                for e in from.iter().chain(to.iter()) {
+                    match self.expr(e)? {
+                        Type::Int | Type::Any => (),
+                        i => return type_err(format!("`{}` index", i)),
+                    }
+                }
+                Type::Range
+            }
+            e => self.expr(e)?,
+        };
+
+        let e = match (&t, &at) {
+            (Type::Vec(e), Type::Int) => (**e).clone(),
+            (Type::Vec(_), Type::Range) | (Type::Str, Type::Range) => t.clone(),
+            (Type::Range, Type::Int) => Type::Int,
+            (Type::Range, Type::Range) => Type::Range,
+            (Type::Map(m), Type::Str) => match index {
+                Expr::Lit(Lit::Str(k)) => match m.get(k) {
+                    Some(e) => e.clone(),
+                    None => return type_err(format!("no field `{}` in `{}`", k, t)),
+                },
+                _ => Type::Any,
+            },
+            (Type::Any, _) | (_, Type::Any) => Type::Any,
+            _ => return type_err(format!("cannot index `{}` with `{}`", t, at)),
+        };
+
+        Ok(if wrap { Type::option(e) } else { e })
+    }
+
+    fn field(&mut self, base: &Expr, member: &Member) -> Check {
+        let (base, chain) = optional(base);
+        let t = self.expr(base)?;
+        let (t, wrap) = self.receiver(t, chain)?;
+
+        let e = match (member, &t) {
+            (Member::Named(n), Type::Map(m)) => m.get(n).cloned(),
+            (Member::Unnamed(i), Type::Tuple(e)) => e.get(*i as usize).cloned(),
+            (_, Type::Any) => Some(Type::Any),
+            _ => None,
+        };
+        match e {
+            Some(e) if wrap => Ok(Type::option(e)),
+            Some(e) => Ok(e),
+            None => type_err(format!("no field in `{}`", t)),
+        }
+    }
+
+    fn method(&mut self, receiver: &Expr, name: &str, args: &[Expr]) -> Check {
+        let (receiver, chain) = optional(receiver);
+        let t = self.expr(receiver)?;
+
+        if let Ok(lazy) = name.parse::<Lazy>() {
+            return self.lazy(lazy, t, args);
+        }
+        let m: Method = match name.parse() {
+            Ok(m) => m,
+            Err(_) => return type_err(format!("no method `{}`", name)),
+        };
+        if args.len() != m.has_arg() as usize {
+            return type_err(format!("wrong number of arguments to `{}`", name));
+        }
+        let arg = match args.first() {
+            Some(a) => Some(self.expr(a)?),
+            None => None,
+        };
+
+        // Option and dynamic type methods accept `None`
+        let nullable = matches!(m, Method::Option(_) | Method::DynType(_));
+        let (t, wrap) = if chain || !nullable {
+            self.receiver(t, chain)?
+        } else {
+            (t, false)
+        };
+        if !nullable && arg.as_ref().is_some_and(Type::is_nullable) {
+            return type_err(format!("argument of `{}` may be None", name));
+        }
+
+        let r = if t == Type::Any || arg == Some(Type::Any) {
+            Type::Any
+        } else {
+            match m.infer(&t, arg.as_ref()) {
+                Some(r) => r,
+                None => return type_err(format!("no method `{}` for `{}`", name, t)),
+            }
+        };
+
+        Ok(if wrap { Type::option(r) } else { r })
+    }
+
+    fn lazy(&mut self, method: Lazy, t: Type, args: &[Expr]) -> Check {
+        match (method, args.len()) {
+            (Lazy::UnwrapOrElse, 1) => {
+                let f = self.closure(&args[0], &[])?;
+                Ok(Type::or(&t, f))
+            }
+            (Lazy::MapOr, 2) => {
+                let default = self.expr(&args[0])?;
+                if t == Type::None {
+                    return Ok(default);
+                }
+                let f = self.closure(&args[1], &[t.some().clone()])?;
+                Ok(if t.is_nullable() {
+                    Type::unify(default, f)
+                } else {
+                    f
+                })
+            }
+            (Lazy::Filter, 1) => {
+                if t == Type::None {
+                    return Ok(t);
+                }
+                match self.closure(&args[0], &[t.some().clone()])? {
+                    Type::Bool | Type::Any => Ok(Type::option(t)),
+                    f => type_err(format!("filter predicate is `{}`", f)),
+                }
+            }
+            _ => type_err("wrong number of arguments".into()),
+        }
+    }
+
+    fn closure(&mut self, e: &Expr, args: &[Type]) -> Check {
+        let (pats, body) = match e {
+            Expr::Closure(pats, body) if pats.len() == args.len() => (pats, body),
+            _ => return type_err("expected a closure".into()),
+        };
+        self.scopes.push(BTreeMap::new());
+        for (pat, t) in pats.iter().zip(args) {
+            self.bind(pat, t);
+        }
+        let t = self.expr(body);
+        self.scopes.pop();
+
+        t
+    }
+
+    fn block(&mut self, lets: &[(Pat, Expr)], e: &Expr) -> Check {
+        self.scopes.push(BTreeMap::new());
+        let t = lets
+            .iter()
+            .try_for_each(|(pat, init)| {
+                let t = self.expr(init)?;
+                self.bind(pat, &t);
+                Ok(())
+            })
+            .and_then(|_| self.expr(e));
+        self.scopes.pop();
+
+        t
+    }
+
+    fn match_arms(&mut self, e: &Expr, arms: &[Arm]) -> Check {
+        let t = self.expr(e)?;
+        let mut r: Option<Type> = None;
+        for Arm { pat, guard, body } in arms {
+            self.scopes.push(BTreeMap::new());
+            self.bind(pat, &t);
+            let arm = guard
+                .as_ref()
+                .map_or(Ok(()), |guard| match self.expr(guard)? {
+                    Type::Bool | Type::Any => Ok(()),
+                    g => type_err(format!("match guard is `{}`", g)),
+                })
+                .and_then(|_| self.expr(body));
+            self.scopes.pop();
+            let arm = arm?;
+            r = Some(r.map_or(arm.clone(), |r| Type::unify(r, arm)));
+        }
+
+        r.ok_or_else(|| Error::Type("match without arms".into()))
+    }
+
+    /// Bind the identifiers of a pattern matching a value of type `t`
+    fn bind(&mut self, pat: &Pat, t: &Type) {
+        match pat {
+            Pat::Ident(name) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), t.clone());
+                }
+            }
+            Pat::Bind(name, p) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), t.clone());
+                }
+                self.bind(p, t);
+            }
+            Pat::Tuple(pats) => {
+                let rest = pats.iter().position(Pat::is_rest);
+                for (i, p) in pats.iter().enumerate() {
+                    let e = match (t, rest) {
+                        (Type::Tuple(e), Some(r)) if i == r => {
+                            let end = e.len().saturating_sub(pats.len() - i - 1);
+                            Some(Type::Tuple(e.get(r..end).unwrap_or_default().to_vec()))
+                        }
+                        (Type::Tuple(e), Some(r)) if i > r => e
+                            .len()
+                            .checked_sub(pats.len() - i)
+                            .and_then(|i| e.get(i))
+                            .cloned(),
+                        (Type::Tuple(e), _) => e.get(i).cloned(),
+                        _ => None,
+                    };
+                    self.bind(p, &e.unwrap_or(Type::Any));
+                }
+            }
+            Pat::Slice(pats) => {
+                let e = match t {
+                    Type::Vec(e) => (**e).clone(),
+                    _ => Type::Any,
+                };
+                for p in pats {
+                    match p {
+                        p if p.is_rest() => self.bind(p, t),
+                        p => self.bind(p, &e),
+                    }
+                }
+            }
+            Pat::Or(pats) => {
+                if let Some(p) = pats.first() {
+                    self.bind(p, t)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn lit(l: &Lit) -> Type {
+    match l {
+        Lit::None => Type::None,
+        Lit::Int(_) => Type::Int,
+        Lit::Float(_) => Type::Float,
+        Lit::Bool(_) => Type::Bool,
+        Lit::Str(_) | Lit::Char(_) => Type::Str,
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::collections::BTreeMap;
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    ast::{Expr, Fold},
+    deps::Dependencies,
+    optimize::Optimizer,
+    reflect::{eval_with, Ctx, Options},
+    Error, Eval, Scope, Value,
+};
+
+/// Parsed expression, evaluated many times without parse it again
+///
+/// ```rust
+///# use v_eval::{Value, Eval};
+///# fn main() -> Result<(), v_eval::Error> {
+/// let e = Eval::default().insert("adult", "age >= 18").unwrap();
+/// let rule = e.compile("adult && name.len() > 0")?;
+///
+/// assert_eq!(rule.dependencies().variables, ["adult", "age", "name"].iter().map(|s| s.to_string()).collect());
+/// assert_eq!(rule.dependencies().methods, ["len"].iter().map(|s| s.to_string()).collect());
+///
+/// let e = e.insert("age", "20").unwrap().insert("name", "\"foo\"").unwrap();
+/// assert_eq!(rule.eval(&e).unwrap(), Value::Bool(true));
+///# Ok(())
+///# }
+/// ```
+#[derive(Clone)]
+pub struct CompiledExpr {
+    pub(crate) expr: Expr,
+    deps: Dependencies,
+    /// Options of the compiling evaluator
+    opts: Options,
+}
+
+impl CompiledExpr {
+    pub(crate) fn new(e: &Eval, expr: Expr) -> Self {
+        let deps = Dependencies::new(&e.0, &expr);
+        Self {
+            expr,
+            deps,
+            opts: e.1,
+        }
+    }
+
+    /// Context names and methods used, through the context at compile time
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.deps
+    }
+
+    /// Fold the sub-expressions not depending on context into literals,
+    /// remove double negations and boolean identities of `&&` and `||`
+    ///
+    /// Constants are evaluated with the options of the compiling evaluator
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), v_eval::Error> {
+    /// let e = Eval::default();
+    /// let src = r#"!!(x > 2 * (1 + 1)) && "ab".len() < 10.log10().round() + 2 && true"#;
+    /// let rule = e.compile(src)?.optimize();
+    ///
+    /// assert_eq!(rule.dependencies(), e.free_variables(src).as_ref().unwrap());
+    /// for x in &["3", "5"] {
+    ///     let e = e.clone().insert("x", x).unwrap();
+    ///     assert_eq!(rule.eval(&e), e.eval(src));
+    /// }
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn optimize(mut self) -> Self {
+        self.expr = Optimizer::new(&BTreeMap::new(), self.opts, false).fold_expr(self.expr);
+
+        self
+    }
+
+    /// Evaluate with the context of an evaluator
+    pub fn eval(&self, e: &Eval) -> Option<Value> {
+        self.try_eval(e).ok()
+    }
+
+    /// Evaluate with the context of an evaluator, with the reason of failure
+    pub fn try_eval(&self, e: &Eval) -> Result<Value, Error> {
+        eval_with(&e.0, e.1, &self.expr)
+    }
+
+    /// Evaluate with the names of a scope over the context of its evaluator
+    pub fn eval_scoped(&self, s: &Scope) -> Option<Value> {
+        self.try_eval_scoped(s).ok()
+    }
+
+    /// Evaluate with the names of a scope over the context of its evaluator,
+    /// with the reason of failure
+    pub fn try_eval_scoped(&self, s: &Scope) -> Result<Value, Error> {
+        self.try_eval_ctx(s.ctx(), s.base.1)
+    }
+
+    pub(crate) fn try_eval_ctx(&self, ctx: Ctx, opts: Options) -> Result<Value, Error> {
+        eval_with(ctx, opts, &self.expr)
+    }
+}
+
+/// Canonical source, see [`format`](crate::format)
+impl Display for CompiledExpr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
@@ -0,0 +1,120 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+
+use crate::ast::{visit_expr, visit_pat, Expr, Pat, Visit};
+
+/// Context names and methods used by an expression
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dependencies {
+    /// Free identifiers, read from the context or undefined,
+    /// including the ones used by the context expressions
+    pub variables: BTreeSet<String>,
+    /// Called methods
+    pub methods: BTreeSet<String>,
+}
+
+impl Dependencies {
+    pub(crate) fn new(ctx: &BTreeMap<String, Expr>, e: &Expr) -> Self {
+        let mut deps = Deps {
+            ctx,
+            scopes: vec![],
+            deps: Dependencies::default(),
+        };
+        deps.visit_expr(e);
+
+        deps.deps
+    }
+}
+
+struct Deps<'a> {
+    ctx: &'a BTreeMap<String, Expr>,
+    /// Local bindings
+    scopes: Vec<Vec<String>>,
+    deps: Dependencies,
+}
+
+impl<'a> Deps<'a> {
+    fn bound(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.iter().any(|b| b == name))
+    }
+
+    fn bind(&mut self, pat: &Pat) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.extend(binds(pat));
+        }
+    }
+
+    fn var(&mut self, name: &str) {
+        if self.bound(name) || self.deps.variables.contains(name) {
+            return;
+        }
+
+        self.deps.variables.insert(name.to_owned());
+        if let Some(e) = self.ctx.get(name) {
+            let scopes = core::mem::take(&mut self.scopes);
+            self.visit_expr(e);
+            self.scopes = scopes;
+        }
+    }
+}
+
+impl<'a> Visit for Deps<'a> {
+    fn visit_expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Var(name) => self.var(name),
+            Expr::MethodCall(_, method, _) => {
+                self.deps.methods.insert(method.clone());
+                visit_expr(self, e);
+            }
+            Expr::Closure(pats, body) => {
+                self.scopes.push(vec![]);
+                for pat in pats {
+                    self.bind(pat);
+                }
+                self.visit_expr(body);
+                self.scopes.pop();
+            }
+            Expr::Block(lets, e) => {
+                self.scopes.push(vec![]);
+                for (pat, init) in lets {
+                    self.visit_expr(init);
+                    self.bind(pat);
+                }
+                self.visit_expr(e);
+                self.scopes.pop();
+            }
+            Expr::Match(e, arms) => {
+                self.visit_expr(e);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    self.bind(&arm.pat);
+                    if let Some(guard) = &arm.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            e => visit_expr(self, e),
+        }
+    }
+}
+
+/// Identifiers bound by a pattern
+pub(crate) fn binds(pat: &Pat) -> Vec<String> {
+    let mut binds = Binds(vec![]);
+    binds.visit_pat(pat);
+
+    binds.0
+}
+
+struct Binds(Vec<String>);
+
+impl Visit for Binds {
+    fn visit_pat(&mut self, p: &Pat) {
+        if let Pat::Ident(name) | Pat::Bind(name, _) = p {
+            self.0.push(name.clone());
+        }
+        visit_pat(self, p);
+    }
+}
@@ -0,0 +1,40 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use crate::Limit;
+
+/// Evaluation error
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Source is not a valid expression
+    Parse,
+    /// Expression can't be evaluated
+    Eval,
+    /// Result is `None` or holds `None` elements
+    None,
+    /// Undefined identifier in strict mode
+    Undefined(String),
+    /// Context variables reference each other, with the reference path
+    Cycle(Vec<String>),
+    /// Evaluation exceeded one of its [`EvalLimits`](crate::EvalLimits)
+    Limit(Limit),
+    /// Static type error, see [`Eval::type_check`](crate::Eval::type_check)
+    Type(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::Error::*;
+        match self {
+            Parse => f.write_str("invalid expression"),
+            Eval => f.write_str("expression can't be evaluated"),
+            None => f.write_str("result is None"),
+            Undefined(name) => write!(f, "undefined identifier `{}`", name),
+            Cycle(path) => write!(f, "reference cycle `{}`", path.join(" -> ")),
+            Limit(limit) => write!(f, "{} limit exceeded", limit),
+            Type(msg) => write!(f, "type error: {}", msg),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,643 @@
+//! C bindings, with the `ffi` feature
+//!
+//! The header is `include/v_eval.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/v_eval.h`, and the shared and
+//! static libraries, `v_eval_capi`, are built with `cargo build --release -p v_eval-capi`
+//!
+//! ```c
+//! VEval *e = v_eval_new();
+//! char *error = NULL;
+//! if (v_eval_insert(e, "adult", "age >= 18", &error) != 0) { ... }
+//! v_eval_set_int(e, "age", 20, &error);
+//!
+//! VEvalValue *v = v_eval_eval(e, "adult && age < 65", &error);
+//! bool adult;
+//! if (v != NULL && v_eval_value_bool(v, &adult)) { ... }
+//!
+//! v_eval_value_free(v);
+//! v_eval_string_free(error);
+//! v_eval_free(e);
+//! ```
+//!
+//! Functions taking `char **error` set it, when it is not null, to the reason of a
+//! failure, freed with `v_eval_string_free`. A previous error in it is freed, so it
+//! is null or an error of this library
+//!
+//! A panic is not unwound into C, the function fails instead
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    string::{String, ToString},
+};
+use core::{mem, ptr, time::Duration};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{ast, Eval, EvalLimits, NoneMode, Value};
+
+/// Evaluator with context
+pub struct VEval(Eval);
+
+/// Result of an evaluation
+pub struct VEvalValue(Value);
+
+/// Kind of a `VEvalValue`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEvalKind {
+    None,
+    Bool,
+    Int,
+    Float,
+    Str,
+    Range,
+    FloatRange,
+    CharRange,
+    Vec,
+    Tuple,
+    Map,
+}
+
+/// How `None` and undefined identifiers are evaluated, see `NoneMode`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VEvalNoneMode {
+    Lenient,
+    Strict,
+    Propagate,
+}
+
+/// Set the error of a failure, freeing the previous one
+unsafe fn fail(error: *mut *mut c_char, msg: &str) -> c_int {
+    if !error.is_null() {
+        v_eval_string_free(*error);
+        *error = CString::new(msg).map_or(ptr::null_mut(), CString::into_raw);
+    }
+
+    -1
+}
+
+/// Call `f`, failing with `failed` instead of unwinding a panic into C
+unsafe fn catch<T>(error: *mut *mut c_char, failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        fail(error, "panicked");
+        failed
+    })
+}
+
+/// UTF-8 string of a C string
+unsafe fn str<'a>(s: *const c_char) -> Result<&'a str, &'static str> {
+    if s.is_null() {
+        return Err("null string");
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| "invalid UTF-8")
+}
+
+/// Insert in context name - expression
+unsafe fn set(
+    e: *mut VEval,
+    name: *const c_char,
+    expr: impl FnOnce(&Eval) -> Result<ast::Expr, String>,
+    error: *mut *mut c_char,
+) -> c_int {
+    catch(error, -1, || {
+        let e = match e.as_mut() {
+            Some(e) => e,
+            None => return fail(error, "null evaluator"),
+        };
+        let name = match str(name) {
+            Ok(name) => name,
+            Err(msg) => return fail(error, msg),
+        };
+        match expr(&e.0) {
+            Ok(expr) => {
+                e.0 .0.insert(name.to_owned(), expr);
+                0
+            }
+            Err(msg) => fail(error, &msg),
+        }
+    })
+}
+
+/// New evaluator with empty context, freed with `v_eval_free`
+#[no_mangle]
+pub extern "C" fn v_eval_new() -> *mut VEval {
+    unsafe {
+        catch(ptr::null_mut(), ptr::null_mut(), || {
+            Box::into_raw(Box::new(VEval(Eval::default())))
+        })
+    }
+}
+
+/// Free an evaluator
+///
+/// # Safety
+/// `e` is null or returned by `v_eval_new`, and not used after
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_free(e: *mut VEval) {
+    catch(ptr::null_mut(), (), || {
+        if !e.is_null() {
+            drop(Box::from_raw(e));
+        }
+    })
+}
+
+/// Negative indexes count from the end, see [`Eval::negative_index`]
+///
+/// # Safety
+/// `e` is null or a valid evaluator
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_negative_index(e: *mut VEval, on: bool) {
+    catch(ptr::null_mut(), (), || {
+        if let Some(e) = e.as_mut() {
+            e.0 = mem::take(&mut e.0).negative_index(on);
+        }
+    })
+}
+
+/// How `None` and undefined identifiers are evaluated, see [`Eval::none_mode`]
+///
+/// # Safety
+/// `e` is null or a valid evaluator
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_none_mode(e: *mut VEval, mode: VEvalNoneMode) {
+    catch(ptr::null_mut(), (), || {
+        if let Some(e) = e.as_mut() {
+            e.0 = mem::take(&mut e.0).none_mode(match mode {
+                VEvalNoneMode::Lenient => NoneMode::Lenient,
+                VEvalNoneMode::Strict => NoneMode::Strict,
+                VEvalNoneMode::Propagate => NoneMode::Propagate,
+            });
+        }
+    })
+}
+
+/// Resource budgets of each evaluation, see [`Eval::limits`], 0 is unlimited
+///
+/// # Safety
+/// `e` is null or a valid evaluator
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_limits(
+    e: *mut VEval,
+    max_depth: usize,
+    max_steps: usize,
+    max_size: usize,
+    timeout_ms: u64,
+) {
+    catch(ptr::null_mut(), (), || {
+        if let Some(e) = e.as_mut() {
+            let mut limits = EvalLimits::default();
+            if max_depth != 0 {
+                limits = limits.max_depth(max_depth);
+            }
+            if max_steps != 0 {
+                limits = limits.max_steps(max_steps);
+            }
+            if max_size != 0 {
+                limits = limits.max_size(max_size);
+            }
+            if timeout_ms != 0 {
+                limits = limits.timeout(Duration::from_millis(timeout_ms));
+            }
+            e.0 = mem::take(&mut e.0).limits(limits);
+        }
+    })
+}
+
+/// Parse and insert in context name - expression, 0 on success
+///
+/// # Safety
+/// `e` is null or a valid evaluator, strings are null or NUL terminated and
+/// `error` is null or valid to write
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_insert(
+    e: *mut VEval,
+    name: *const c_char,
+    src: *const c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(
+        e,
+        name,
+        |e| e.parse(str(src)?).map_err(|e| e.to_string()),
+        error,
+    )
+}
+
+/// Insert in context name - `None`, 0 on success
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_set_none(
+    e: *mut VEval,
+    name: *const c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(e, name, |_| Ok(Value::None.into()), error)
+}
+
+/// Insert in context name - bool, 0 on success
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_set_bool(
+    e: *mut VEval,
+    name: *const c_char,
+    value: bool,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(e, name, |_| Ok(value.into()), error)
+}
+
+/// Insert in context name - integer, 0 on success
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_set_int(
+    e: *mut VEval,
+    name: *const c_char,
+    value: i64,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(e, name, |_| Ok(value.into()), error)
+}
+
+/// Insert in context name - float, 0 on success
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_set_float(
+    e: *mut VEval,
+    name: *const c_char,
+    value: f64,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(e, name, |_| Ok(value.into()), error)
+}
+
+/// Insert in context name - string, 0 on success
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_set_str(
+    e: *mut VEval,
+    name: *const c_char,
+    value: *const c_char,
+    error: *mut *mut c_char,
+) -> c_int {
+    set(e, name, |_| Ok(str(value)?.into()), error)
+}
+
+/// Remove name in context
+///
+/// # Safety
+/// `e` is null or a valid evaluator and `name` is null or NUL terminated
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_remove(e: *mut VEval, name: *const c_char) {
+    catch(ptr::null_mut(), (), || {
+        if let (Some(e), Ok(name)) = (e.as_mut(), str(name)) {
+            e.0 .0.remove(name);
+        }
+    })
+}
+
+/// Evaluate expression with current context, null on failure, freed with
+/// `v_eval_value_free`
+///
+/// # Safety
+/// As `v_eval_insert`
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_eval(
+    e: *const VEval,
+    src: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut VEvalValue {
+    catch(error, ptr::null_mut(), || {
+        let e = match e.as_ref() {
+            Some(e) => e,
+            None => {
+                fail(error, "null evaluator");
+                return ptr::null_mut();
+            }
+        };
+        let result = match str(src) {
+            Ok(src) => e.0.try_eval(src).map_err(|e| e.to_string()),
+            Err(msg) => Err(msg.to_owned()),
+        };
+        match result {
+            Ok(v) => Box::into_raw(Box::new(VEvalValue(v))),
+            Err(msg) => {
+                fail(error, &msg);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free a value
+///
+/// # Safety
+/// `v` is null or returned by this library, and not used after
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_free(v: *mut VEvalValue) {
+    catch(ptr::null_mut(), (), || {
+        if !v.is_null() {
+            drop(Box::from_raw(v));
+        }
+    })
+}
+
+/// Free an error or string
+///
+/// # Safety
+/// `s` is null or returned by this library, and not used after
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_string_free(s: *mut c_char) {
+    catch(ptr::null_mut(), (), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+/// Kind of a value, `None` with a null value
+///
+/// # Safety
+/// `v` is null or a valid value
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_kind(v: *const VEvalValue) -> VEvalKind {
+    catch(ptr::null_mut(), VEvalKind::None, || {
+        match v.as_ref().map(|v| &v.0) {
+            None | Some(Value::None) => VEvalKind::None,
+            Some(Value::Bool(_)) => VEvalKind::Bool,
+            Some(Value::Int(_)) => VEvalKind::Int,
+            Some(Value::Float(_)) => VEvalKind::Float,
+            Some(Value::Str(_)) => VEvalKind::Str,
+            Some(Value::Range(_) | Value::RangeInclusive(_)) => VEvalKind::Range,
+            Some(Value::FloatRange(_) | Value::FloatRangeInclusive(_)) => VEvalKind::FloatRange,
+            Some(Value::CharRange(_) | Value::CharRangeInclusive(_)) => VEvalKind::CharRange,
+            Some(Value::Vec(_)) => VEvalKind::Vec,
+            Some(Value::Tuple(_)) => VEvalKind::Tuple,
+            Some(Value::Map(_)) => VEvalKind::Map,
+        }
+    })
+}
+
+/// Write a bool value in `out`, false with other kinds
+///
+/// # Safety
+/// `v` is null or a valid value and `out` is valid to write
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_bool(v: *const VEvalValue, out: *mut bool) -> bool {
+    catch(ptr::null_mut(), false, || match v.as_ref() {
+        Some(VEvalValue(Value::Bool(b))) if !out.is_null() => {
+            *out = *b;
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Write an integer value in `out`, false with other kinds
+///
+/// # Safety
+/// `v` is null or a valid value and `out` is valid to write
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_int(v: *const VEvalValue, out: *mut i64) -> bool {
+    catch(ptr::null_mut(), false, || match v.as_ref() {
+        Some(VEvalValue(Value::Int(n))) if !out.is_null() => {
+            *out = *n;
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Write a float value in `out`, integers are converted, false with other kinds
+///
+/// # Safety
+/// `v` is null or a valid value and `out` is valid to write
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_float(v: *const VEvalValue, out: *mut f64) -> bool {
+    catch(ptr::null_mut(), false, || {
+        match v.as_ref() {
+            Some(VEvalValue(Value::Float(n))) if !out.is_null() => *out = *n,
+            Some(VEvalValue(Value::Int(n))) if !out.is_null() => *out = *n as f64,
+            _ => return false,
+        }
+
+        true
+    })
+}
+
+/// New string of a string value, null with other kinds or holding a NUL, freed
+/// with `v_eval_string_free`
+///
+/// # Safety
+/// `v` is null or a valid value
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_str(v: *const VEvalValue) -> *mut c_char {
+    catch(ptr::null_mut(), ptr::null_mut(), || match v.as_ref() {
+        Some(VEvalValue(Value::Str(s))) => {
+            CString::new(s.as_str()).map_or(ptr::null_mut(), CString::into_raw)
+        }
+        _ => ptr::null_mut(),
+    })
+}
+
+/// Number of elements of a `Vec` or `Tuple` value or entries of a `Map` value, 0
+/// with other kinds
+///
+/// # Safety
+/// `v` is null or a valid value
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_len(v: *const VEvalValue) -> usize {
+    catch(ptr::null_mut(), 0, || match v.as_ref() {
+        Some(VEvalValue(Value::Vec(v) | Value::Tuple(v))) => v.len(),
+        Some(VEvalValue(Value::Map(m))) => m.len(),
+        _ => 0,
+    })
+}
+
+/// New value of an element of a `Vec` or `Tuple` value, null out of bounds or with
+/// other kinds, freed with `v_eval_value_free`
+///
+/// # Safety
+/// `v` is null or a valid value
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_get(v: *const VEvalValue, index: usize) -> *mut VEvalValue {
+    catch(ptr::null_mut(), ptr::null_mut(), || match v.as_ref() {
+        Some(VEvalValue(Value::Vec(v) | Value::Tuple(v))) => {
+            v.get(index).map_or(ptr::null_mut(), |v| {
+                Box::into_raw(Box::new(VEvalValue(v.clone())))
+            })
+        }
+        _ => ptr::null_mut(),
+    })
+}
+
+/// New value of the entry of a key in a `Map` value, null without the key or with
+/// other kinds, freed with `v_eval_value_free`
+///
+/// # Safety
+/// `v` is null or a valid value and `key` is null or NUL terminated
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_field(
+    v: *const VEvalValue,
+    key: *const c_char,
+) -> *mut VEvalValue {
+    catch(ptr::null_mut(), ptr::null_mut(), || {
+        match (v.as_ref(), str(key)) {
+            (Some(VEvalValue(Value::Map(m))), Ok(key)) => m.get(key).map_or(ptr::null_mut(), |v| {
+                Box::into_raw(Box::new(VEvalValue(v.clone())))
+            }),
+            _ => ptr::null_mut(),
+        }
+    })
+}
+
+/// New string of the display of a value, freed with `v_eval_string_free`
+///
+/// # Safety
+/// `v` is null or a valid value
+#[no_mangle]
+pub unsafe extern "C" fn v_eval_value_to_string(v: *const VEvalValue) -> *mut c_char {
+    catch(ptr::null_mut(), ptr::null_mut(), || {
+        v.as_ref()
+            .and_then(|v| CString::new(v.0.to_string().replace('\0', "")).ok())
+            .map_or(ptr::null_mut(), CString::into_raw)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    unsafe fn string(s: *mut c_char) -> String {
+        let owned = CStr::from_ptr(s).to_str().unwrap().to_owned();
+        v_eval_string_free(s);
+
+        owned
+    }
+
+    #[test]
+    fn test_ffi() {
+        let name = |s: &str| CString::new(s).unwrap();
+        unsafe {
+            let e = v_eval_new();
+            let mut error = ptr::null_mut();
+            assert_eq!(
+                v_eval_insert(
+                    e,
+                    name("adult").as_ptr(),
+                    name("age >= 18").as_ptr(),
+                    &mut error
+                ),
+                0
+            );
+            assert_eq!(v_eval_set_int(e, name("age").as_ptr(), 20, &mut error), 0);
+            assert_eq!(
+                v_eval_set_str(e, name("s").as_ptr(), name("foo").as_ptr(), &mut error),
+                0
+            );
+            assert!(error.is_null());
+
+            let v = v_eval_eval(e, name("adult").as_ptr(), &mut error);
+            let mut b = false;
+            assert_eq!(v_eval_value_kind(v), VEvalKind::Bool);
+            assert!(v_eval_value_bool(v, &mut b) && b);
+            let mut n = 0;
+            assert!(!v_eval_value_int(v, &mut n));
+            v_eval_value_free(v);
+
+            let v = v_eval_eval(e, name("(s.to_uppercase(), [age, 1])").as_ptr(), &mut error);
+            assert_eq!(v_eval_value_kind(v), VEvalKind::Tuple);
+            assert_eq!(v_eval_value_len(v), 2);
+            let first = v_eval_value_get(v, 0);
+            assert_eq!(string(v_eval_value_str(first)), "FOO");
+            v_eval_value_free(first);
+            let elems = v_eval_value_get(v, 1);
+            let first = v_eval_value_get(elems, 0);
+            assert!(v_eval_value_int(first, &mut n) && n == 20);
+            assert!(v_eval_value_get(elems, 2).is_null());
+            v_eval_value_free(first);
+            v_eval_value_free(elems);
+            assert_eq!(string(v_eval_value_to_string(v)), "(\"FOO\",[20,1,],)");
+            v_eval_value_free(v);
+
+            let v = v_eval_eval(e, name(r#"{"a": age, "b": s}"#).as_ptr(), &mut error);
+            assert_eq!(v_eval_value_kind(v), VEvalKind::Map);
+            assert_eq!(v_eval_value_len(v), 2);
+            let a = v_eval_value_field(v, name("a").as_ptr());
+            assert!(v_eval_value_int(a, &mut n) && n == 20);
+            v_eval_value_free(a);
+            assert!(v_eval_value_field(v, name("c").as_ptr()).is_null());
+            assert!(v_eval_value_field(v, ptr::null()).is_null());
+            v_eval_value_free(v);
+
+            assert!(v_eval_eval(e, name("1 +").as_ptr(), &mut error).is_null());
+            assert_eq!(string(error), "invalid expression");
+            let mut error = ptr::null_mut();
+            assert_eq!(
+                v_eval_insert(e, name("a").as_ptr(), name("1 +").as_ptr(), &mut error),
+                -1
+            );
+            assert_eq!(string(error), "invalid expression");
+            let mut error = ptr::null_mut();
+            assert!(v_eval_eval(e, name("x").as_ptr(), &mut error).is_null());
+            assert_eq!(string(error), "result is None");
+            assert!(v_eval_eval(e, ptr::null(), ptr::null_mut()).is_null());
+
+            v_eval_remove(e, name("age").as_ptr());
+            let mut error = ptr::null_mut();
+            assert!(v_eval_eval(e, name("adult").as_ptr(), &mut error).is_null());
+            v_eval_string_free(error);
+
+            v_eval_free(e);
+        }
+    }
+
+    #[test]
+    fn test_ffi_options() {
+        let name = |s: &str| CString::new(s).unwrap();
+        unsafe {
+            let e = v_eval_new();
+            let mut error = ptr::null_mut();
+            assert!(v_eval_eval(e, name("1 +").as_ptr(), &mut error).is_null());
+            assert!(v_eval_eval(e, name("x").as_ptr(), &mut error).is_null());
+            assert_eq!(string(error), "result is None");
+
+            let mut error = ptr::null_mut();
+            v_eval_none_mode(e, VEvalNoneMode::Strict);
+            assert!(v_eval_eval(e, name("x").as_ptr(), &mut error).is_null());
+            assert_eq!(string(error), "undefined identifier `x`");
+            v_eval_none_mode(e, VEvalNoneMode::Propagate);
+            let v = v_eval_eval(e, name("[x + 1]").as_ptr(), ptr::null_mut());
+            assert_eq!(string(v_eval_value_to_string(v)), "[None,]");
+            v_eval_value_free(v);
+
+            let mut error = ptr::null_mut();
+            v_eval_limits(e, 0, 3, 0, 0);
+            assert!(v_eval_eval(e, name("1 + 2 + 3 + 4").as_ptr(), &mut error).is_null());
+            assert_eq!(string(error), "steps limit exceeded");
+            v_eval_limits(e, 0, 0, 0, 0);
+            let v = v_eval_eval(e, name("1 + 2 + 3 + 4").as_ptr(), ptr::null_mut());
+            assert_eq!(v_eval_value_kind(v), VEvalKind::Int);
+            v_eval_value_free(v);
+
+            v_eval_free(e);
+
+            let mut error = ptr::null_mut();
+            assert_eq!(catch(&mut error, -1, || panic!("in Rust")), -1);
+            assert_eq!(string(error), "panicked");
+        }
+    }
+}
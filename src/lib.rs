@@ -25,6 +25,20 @@
 //!# Ok(())
 //!# }
 //! ```
+//! ## Optional chaining
+//! A `?` after the receiver of a method call, field or index gives `None`
+//! when the receiver is `None`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//! let e = Eval::default().insert("s", "\"a,b\"")?;
+//!
+//! assert_eq!(e.eval(r#"s?.split(",")?[1]"#).unwrap(), Value::Str("b".into()));
+//! assert_eq!(e.eval(r#"not_exist?.split(",")?[1].is_none()"#).unwrap(), Value::Bool(true));
+//! assert_eq!(e.eval(r#"not_exist?.len().unwrap_or(0)"#).unwrap(), Value::Int(0));
+//!# Ok(())
+//!# }
+//! ```
 //! ## Methods
 //! ### By default
 //! #### Option
@@ -44,6 +58,22 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `filter`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("2.filter(|x| x > 1)").unwrap(), Value::Int(2));
+//! assert_eq!(e.eval("2.filter(|x| x > 2)"), None);
+//! assert_eq!(e.eval("not_exist.filter(|x| x > 2).is_none()").unwrap(), Value::Bool(true));
+//!# Ok(())
+//!# }
+//! ```
 //! - `is_none`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -74,6 +104,21 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `map_or`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("string.map_or(0, |s| s.len())").unwrap(), Value::Int(3));
+//! assert_eq!(e.eval("not_exist.map_or(0, |s| s.len())").unwrap(), Value::Int(0));
+//!# Ok(())
+//!# }
+//! ```
 //! - `or`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -90,6 +135,36 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `unwrap_or`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("string.unwrap_or(\"bar\")").unwrap(), Value::Str("foo".into()));
+//! assert_eq!(e.eval("not_exist.unwrap_or(\"bar\")").unwrap(), Value::Str("bar".into()));
+//!# Ok(())
+//!# }
+//! ```
+//! - `unwrap_or_else`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("not_exist.unwrap_or_else(|| string.len())").unwrap(), Value::Int(3));
+//! assert_eq!(e.eval("foo.unwrap_or_else(|| not_valid + 1)").unwrap(), Value::Bool(true));
+//!# Ok(())
+//!# }
+//! ```
 //! - `xor`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -184,6 +259,21 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `is_tuple`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("(1, foo).is_tuple()").unwrap(), Value::Bool(true));
+//! assert_eq!(e.eval("[1, 2].is_tuple()").unwrap(), Value::Bool(false));
+//!# Ok(())
+//!# }
+//! ```
 //! - `is_vec`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -261,6 +351,8 @@
 //! assert_eq!(e.eval("string.contains(\"oo\")").unwrap(), Value::Bool(true));
 //! assert_eq!(e.eval("[4, 1.0, true, \"foo\"].contains(1)").unwrap(), Value::Bool(true));
 //! assert_eq!(e.eval("(0..20).contains(0)").unwrap(), Value::Bool(true));
+//! assert_eq!(e.eval("(0.5..1.5).contains(1)").unwrap(), Value::Bool(true));
+//! assert_eq!(e.eval("('a'..='z').contains('z')").unwrap(), Value::Bool(true));
 //!# Ok(())
 //!# }
 //! ```
@@ -340,7 +432,7 @@
 //!# Ok(())
 //!# }
 //! ```
-//! - `is_match`
+//! - `is_match`, with the `regex` feature
 //! ```rust
 //!# use v_eval::{Value, Eval};
 //!# fn main() -> Result<(), ()> {
@@ -467,7 +559,53 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `split`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval(r#""a,b".split(",")"#).unwrap(), Value::Vec(vec!["a".into(), "b".into()]));
+//! assert_eq!(e.eval(r#""a,b".split(",")[1]"#).unwrap(), Value::Str("b".into()));
+//!# Ok(())
+//!# }
+//! ```
+//! - `split_once`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval(r#""a=b".split_once("=")"#).unwrap(), Value::Tuple(vec!["a".into(), "b".into()]));
+//! assert_eq!(e.eval(r#""a=b".split_once("=").1"#).unwrap(), Value::Str("b".into()));
+//! assert_eq!(e.eval(r#"string.split_once("=")"#), None);
+//!# Ok(())
+//!# }
+//! ```
 //! ### Vec
+//! - `compact`, also removes the `None` entries of a `Map`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("[1, not_exist, None].compact()").unwrap(), Value::Vec(vec![1.into()]));
+//! assert_eq!(e.eval("[1, [None]].compact()"), None);
+//!# Ok(())
+//!# }
+//! ```
 //! - `first`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -482,6 +620,20 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `flatten_none`, also into the `Map` values
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("[1, [None, 2], None].flatten_none()").unwrap(), Value::Vec(vec![1.into(), vec![2].into()]));
+//!# Ok(())
+//!# }
+//! ```
 //! - `get`
 //! ```rust
 //!# use v_eval::{Value, Eval};
@@ -511,8 +663,59 @@
 //!# Ok(())
 //!# }
 //! ```
+//! - `nth_back`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval(r#"[1, 2, 3].nth_back(0)"#).unwrap(), Value::Int(3));
+//! assert_eq!(e.eval(r#"[1, 2, 3].nth_back(3)"#), None);
+//!# Ok(())
+//!# }
+//! ```
+//! - `sort`
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval(r#"["b", "c", "a"].sort()"#).unwrap(), Value::Vec(vec!["a".into(), "b".into(), "c".into()]));
+//! assert_eq!(e.eval(r#"[2, "a", 1.5, foo].sort()"#).unwrap(), Value::Vec(vec![true.into(), 1.5.into(), 2.into(), "a".into()]));
+//!# Ok(())
+//!# }
+//! ```
+//! ### Range
+//! - `step_by`, the `Vec` of the elements, built before any method chained
+//!   after it, so its length is bounded by [`EvalLimits::max_size`]
+//! ```rust
+//!# use v_eval::{Value, Eval};
+//!# fn main() -> Result<(), ()> {
+//!# let e = Eval::default()
+//!#     .insert("foo", "true")?
+//!#     .insert("string", "\"foo\"")?
+//!#     .insert("opt", "true")?
+//!#     .insert("bar", "false")?;
+//!#
+//! assert_eq!(e.eval("(0..=6).step_by(3)").unwrap(), Value::Vec(vec![0.into(), 3.into(), 6.into()]));
+//! assert_eq!(e.eval("('a'..'f').step_by(2)").unwrap(), Value::Vec(vec!["a".into(), "c".into(), "e".into()]));
+//! assert_eq!(e.eval("(0.0..1.0).step_by(0.5)").unwrap(), Value::Vec(vec![0.0.into(), 0.5.into()]));
+//!# Ok(())
+//!# }
+//! ```
 //! ### Number (i64 and f64)
 //! > See [f64 Rust](https://doc.rust-lang.org/std/primitive.f64.html)
+//!
+//! Without `std`, only `abs`, `max`, `min`, `recip`, `signum`, `to_degrees` and
+//! `to_radians` unless the `libm` feature is enabled
 //! - `abs`
 //! - `acos`
 //! - `acosh`
@@ -565,35 +768,204 @@
 //!# }
 //! ```
 //!
+//! ## Features
+//! - `std`, by default: use the standard library, only `alloc` otherwise, the
+//!   evaluation `timeout` needs it
+//! - `parser`, by default: parse the evaluated grammar without dependencies
+//! - `rust-syntax`: parse with `syn` the Rust expressions instead
+//! - `regex`, by default: `is_match` method of `Str`, with `std`
+//! - `libm`: float functions, as `sqrt` or `ln`, without `std`
+//! - `wasm`: JavaScript bindings, see the `wasm` module
+//! - `ffi`: C bindings, see the `ffi` module
+//! - `python`: Python bindings, see the `python` module
+//! - `cli`: the `v_eval` command-line evaluator, with a REPL and batch
+//!   evaluation of CSV and JSON-lines records
 //!
-use std::collections::BTreeMap;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use syn::parse_str;
+extern crate alloc;
 
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+
+#[cfg(not(any(feature = "parser", feature = "rust-syntax")))]
+compile_error!("one of the features `parser` or `rust-syntax` is required");
+
+pub mod ast;
+mod batch;
+mod check;
+mod compiled;
+mod deps;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod limits;
 mod method;
 mod operator;
+mod optimize;
+mod print;
+#[cfg(feature = "python")]
+pub mod python;
 mod reflect;
+mod scope;
+mod types;
 mod value;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use self::{reflect::eval, value::Value};
+pub use self::{
+    batch::Batch,
+    compiled::CompiledExpr,
+    deps::Dependencies,
+    error::Error,
+    limits::{EvalLimits, Limit},
+    print::format,
+    reflect::{eval, NoneMode},
+    scope::Scope,
+    types::{Schema, Type},
+    value::Value,
+};
+
+use self::{
+    ast::Fold,
+    reflect::{eval_with, Options},
+};
 
 /// Evaluator with context
-pub struct Eval(BTreeMap<String, syn::Expr>);
+#[derive(Clone, Default)]
+pub struct Eval(BTreeMap<String, ast::Expr>, Options);
 
-impl Default for Eval {
-    fn default() -> Self {
-        Self(BTreeMap::new())
+impl Eval {
+    pub fn new(c: BTreeMap<String, ast::Expr>) -> Self {
+        Self(c, Options::default())
     }
-}
 
-impl Eval {
-    pub fn new(c: BTreeMap<String, syn::Expr>) -> Self {
-        Self(c)
+    /// Negative indexes count from the end, Python-style
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default()
+    ///     .negative_index(true)
+    ///     .insert("s", "\"foo bar\"")?;
+    ///
+    /// assert_eq!(e.eval("[1, 2, 3][-1]").unwrap(), Value::Int(3));
+    /// assert_eq!(e.eval("s[-3..]").unwrap(), Value::Str("bar".into()));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn negative_index(mut self, on: bool) -> Self {
+        self.1.negative_index = on;
+
+        self
     }
 
-    /// Parse and insert in context name - syn::Expr
+    /// Set how `None` and undefined identifiers are evaluated, see [`NoneMode`]
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval, NoneMode};
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default().insert("a", "1")?;
+    /// assert_eq!(e.eval("not_exist.is_none()").unwrap(), Value::Bool(true));
+    /// assert_eq!(e.eval("(not_exist + a).is_none()"), None);
+    ///
+    /// let e = e.none_mode(NoneMode::Strict);
+    /// assert_eq!(e.eval("not_exist.is_none()"), None);
+    ///
+    /// let e = e.none_mode(NoneMode::Propagate);
+    /// assert_eq!(e.eval("(not_exist + a).is_none()").unwrap(), Value::Bool(true));
+    /// assert_eq!(e.eval("not_exist > a || true").unwrap(), Value::Bool(true));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn none_mode(mut self, mode: NoneMode) -> Self {
+        self.1.none = mode;
+
+        self
+    }
+
+    /// Accept results holding `None` elements in a `Vec`, `Tuple` or `Map`
+    ///
+    /// Only a `None` result is rejected, see [`Value::is_some`] and [`Value::is_complete`]
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default().insert("a", "1")?;
+    /// assert_eq!(e.eval("[a, missing]"), None);
+    /// assert_eq!(e.eval("[a, missing].compact()").unwrap(), Value::Vec(vec![Value::Int(1)]));
+    ///
+    /// let e = e.nullable_elements(true);
+    /// assert_eq!(e.eval("[a, missing]").unwrap(), Value::Vec(vec![Value::Int(1), Value::None]));
+    /// assert_eq!(e.eval("missing"), None);
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn nullable_elements(mut self, on: bool) -> Self {
+        self.1.nullable_elements = on;
+
+        self
+    }
+
+    /// Set the resource budgets of each evaluation, see [`EvalLimits`]
+    ///
+    /// ```rust
+    ///# use std::time::Duration;
+    ///# use v_eval::{Value, Eval, EvalLimits, Error, Limit};
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default().limits(
+    ///     EvalLimits::default()
+    ///         .max_depth(32)
+    ///         .max_steps(10_000)
+    ///         .max_size(1 << 16)
+    ///         .timeout(Duration::from_millis(100)),
+    /// );
+    ///
+    /// assert_eq!(e.try_eval(r#""a" * 3"#), Ok(Value::Str("aaa".into())));
+    /// assert_eq!(e.try_eval(r#""a" * 1000000000"#), Err(Error::Limit(Limit::Size)));
+    /// assert_eq!(e.try_eval("(0..1000000).step_by(1)"), Err(Error::Limit(Limit::Size)));
+    /// assert_eq!(
+    ///     e.try_eval(&format!("{}1{}", "[".repeat(64), "]".repeat(64))),
+    ///     Err(Error::Limit(Limit::Depth))
+    /// );
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn limits(mut self, limits: EvalLimits) -> Self {
+        self.1.limits = limits;
+
+        self
+    }
+
+    /// Parse and insert in context name - ast::Expr
+    #[allow(clippy::result_unit_err)]
     pub fn insert(mut self, k: &str, v: &str) -> Result<Self, ()> {
-        let e = parse_str::<syn::Expr>(v).map_err(|_| ())?;
+        let e = self.parse_src(v).map_err(|_| ())?;
+        self.0.insert(k.to_owned(), e);
+
+        Ok(self)
+    }
+
+    /// Insert in context name - syntax tree, parsed before or built, see [`ast`]
+    ///
+    /// `Error::Parse` when a name of the tree is not an identifier, as in
+    /// [`Eval::compile_ast`]
+    ///
+    /// ```rust
+    ///# use v_eval::{ast::var, Error, Eval, Value};
+    ///# fn main() -> Result<(), Error> {
+    /// let e = Eval::default();
+    /// let a = e.parse("1 + 2")?;
+    /// let e = e.insert_ast("a", a)?;
+    ///
+    /// assert_eq!(e.try_eval("a * 2")?, Value::Int(6));
+    /// assert_eq!(e.insert_ast("b", var("user-name")).err(), Some(Error::Parse));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn insert_ast(mut self, k: &str, e: ast::Expr) -> Result<Self, Error> {
+        if !e.has_valid_names() {
+            return Err(Error::Parse);
+        }
         self.0.insert(k.to_owned(), e);
 
         Ok(self)
@@ -606,16 +978,260 @@ impl Eval {
         self
     }
 
+    /// Names and expressions in context, ordered by name
+    ///
+    /// ```rust
+    ///# use v_eval::Eval;
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default().insert("b", "a + 1")?.insert("a", "1")?;
+    /// let vars: Vec<_> = e.vars().map(|(k, v)| format!("{} = {}", k, v)).collect();
+    ///
+    /// assert_eq!(vars, ["a = 1", "b = a + 1"]);
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &ast::Expr)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
     /// Evaluate expression with current context
     pub fn eval(&self, src: &str) -> Option<Value> {
-        parse_str::<syn::Expr>(src)
-            .ok()
-            .and_then(|src| eval(&self.0, &src))
+        self.try_eval(src).ok()
+    }
+
+    /// Evaluate expression with current context, with the reason of failure
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval, Error};
+    ///# fn main() -> Result<(), ()> {
+    /// let e = Eval::default()
+    ///     .insert("a", "b")?
+    ///     .insert("b", "a + 1")?;
+    ///
+    /// assert_eq!(
+    ///     e.try_eval("a"),
+    ///     Err(Error::Cycle(vec!["a".into(), "b".into(), "a".into()]))
+    /// );
+    /// assert_eq!(e.try_eval("1 +"), Err(Error::Parse));
+    /// assert_eq!(e.try_eval("1 + true"), Err(Error::Eval));
+    /// assert_eq!(e.try_eval("not_exist"), Err(Error::None));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn try_eval(&self, src: &str) -> Result<Value, Error> {
+        self.parse_src(src)
+            .and_then(|src| eval_with(&self.0, self.1, &src))
+    }
+
+    /// Parse an expression once to evaluate it many times, see [`CompiledExpr`]
+    pub fn compile(&self, src: &str) -> Result<CompiledExpr, Error> {
+        self.parse_src(src).map(|e| CompiledExpr::new(self, e))
+    }
+
+    /// Names layered over the context, to evaluate with the values of each
+    /// request without clone the context, see [`Scope`]
+    ///
+    /// The evaluator is shared in an `Arc` by the scopes, which can be moved to
+    /// other threads
+    ///
+    /// ```rust
+    ///# use std::{sync::Arc, thread};
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), ()> {
+    /// let base = Arc::new(Eval::default().insert("adult", "age >= 18")?);
+    ///
+    /// let handles: Vec<_> = [20, 10]
+    ///     .iter()
+    ///     .map(|&age| {
+    ///         let scope = base.scope().set("age", age);
+    ///         thread::spawn(move || scope.eval("adult"))
+    ///     })
+    ///     .collect();
+    /// let adults: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    ///
+    /// assert_eq!(adults, [Some(Value::Bool(true)), Some(Value::Bool(false))]);
+    /// assert_eq!(base.eval("adult"), None);
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn scope(self: &Arc<Self>) -> Scope {
+        Scope::new(Arc::clone(self))
+    }
+
+    /// Evaluator of compiled expressions record by record, with each record
+    /// bound in a [`Scope`], see [`Eval::eval_batch`]
+    pub fn batch<'a>(&'a self, exprs: &'a [CompiledExpr]) -> Batch<'a> {
+        Batch::new(self, exprs)
+    }
+
+    /// Evaluate compiled expressions for each record of values, bound in
+    /// context over the names of the evaluator
+    ///
+    /// Each item holds the results of the expressions in order
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), v_eval::Error> {
+    /// let e = Eval::default().insert("limit", "100").unwrap();
+    /// let rules = [e.compile("amount > limit")?, e.compile("country == \"ES\"")?];
+    /// let records = vec![
+    ///     vec![("amount".into(), Value::Int(150)), ("country".into(), "ES".into())],
+    ///     vec![("amount".into(), Value::Int(50)), ("country".into(), "FR".into())],
+    /// ];
+    ///
+    /// let results: Vec<_> = e.eval_batch(&rules, records).collect();
+    /// assert_eq!(results[0], [Ok(Value::Bool(true)), Ok(Value::Bool(true))]);
+    /// assert_eq!(results[1], [Ok(Value::Bool(false)), Ok(Value::Bool(false))]);
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn eval_batch<'a, R, I>(
+        &'a self,
+        exprs: &'a [CompiledExpr],
+        records: R,
+    ) -> impl Iterator<Item = Vec<Result<Value, Error>>> + 'a
+    where
+        R: IntoIterator<Item = I>,
+        R::IntoIter: 'a,
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        let mut batch = self.batch(exprs);
+        records.into_iter().map(move |record| batch.eval(record))
+    }
+
+    /// Syntax tree of an expression, see [`ast`]
+    ///
+    /// ```rust
+    ///# use v_eval::{ast::{BinOp, Expr, Lit}, Error, Eval};
+    ///# fn main() -> Result<(), Error> {
+    /// let e = Eval::default();
+    ///
+    /// assert_eq!(
+    ///     e.parse("a + 1")?,
+    ///     Expr::Binary(Box::new(Expr::Var("a".into())), BinOp::Add, Box::new(Expr::Lit(Lit::Int(1))))
+    /// );
+    /// assert_eq!(e.parse("(a || b) && c")?.to_string(), "(a || b) && c");
+    /// assert_eq!(e.parse("a & b"), Err(Error::Parse));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn parse(&self, src: &str) -> Result<ast::Expr, Error> {
+        self.parse_src(src)
+    }
+
+    /// Compile a syntax tree, see [`ast`]
+    ///
+    /// The tree is used as it is, `Error::Parse` when a name is not an
+    /// identifier
+    pub fn compile_ast(&self, e: &ast::Expr) -> Result<CompiledExpr, Error> {
+        if !e.has_valid_names() {
+            return Err(Error::Parse);
+        }
+
+        Ok(CompiledExpr::new(self, e.clone()))
+    }
+
+    /// Evaluate the parts of an expression known by the context
+    ///
+    /// The names without context are unknown and stay in the residual
+    /// expression, with the context expressions using them inlined
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), v_eval::Error> {
+    /// let e = Eval::default().insert("tier", "\"silver\"").unwrap();
+    /// let residual = e.partial_eval(r#"tier == "gold" && amount > 100"#)?;
+    /// assert_eq!(residual.to_string(), "false");
+    ///
+    /// let residual = e.partial_eval(r#"tier == "silver" && amount > 10 * 10"#)?;
+    /// assert_eq!(residual.to_string(), "amount > 100");
+    /// assert_eq!(residual.dependencies().variables, ["amount"].iter().map(|s| s.to_string()).collect());
+    ///
+    /// let e = e.insert("amount", "150").unwrap();
+    /// assert_eq!(residual.eval(&e), Some(Value::Bool(true)));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn partial_eval(&self, src: &str) -> Result<CompiledExpr, Error> {
+        let e = optimize::Optimizer::new(&self.0, self.1, true).fold_expr(self.parse_src(src)?);
+
+        Ok(CompiledExpr::new(self, e))
+    }
+
+    /// Context names, including the ones read through context expressions,
+    /// and methods used by an expression
+    ///
+    /// ```rust
+    ///# use v_eval::{Value, Eval};
+    ///# fn main() -> Result<(), v_eval::Error> {
+    /// let e = Eval::default().insert("total", "price * qty").unwrap();
+    /// let deps = e.free_variables("total.max(min) + [1, 2].iter().map(|x| x * qty).sum()")?;
+    ///
+    /// assert_eq!(
+    ///     deps.variables,
+    ///     ["min", "price", "qty", "total"].iter().map(|s| s.to_string()).collect()
+    /// );
+    /// assert_eq!(
+    ///     deps.methods,
+    ///     ["iter", "map", "max", "sum"].iter().map(|s| s.to_string()).collect()
+    /// );
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn free_variables(&self, src: &str) -> Result<Dependencies, Error> {
+        self.parse_src(src).map(|e| Dependencies::new(&self.0, &e))
+    }
+
+    /// Infer the result type of an expression without evaluate it
+    ///
+    /// Names are typed by their context expression or by the schema,
+    /// with the same rules and `None` semantics of the evaluation
+    ///
+    /// ```rust
+    ///# use v_eval::{Eval, Error, Schema, Type};
+    ///# fn main() -> Result<(), ()> {
+    /// let schema = Schema::default()
+    ///     .insert("age", "Int")?
+    ///     .insert("name", "Option<Str>")?
+    ///     .insert("user", "{tags: Vec<Str>}")?;
+    /// let e = Eval::default().insert("adult", "age >= 18")?;
+    ///
+    /// assert_eq!(e.type_check(&schema, "adult && user.tags.len() > 0"), Ok(Type::Bool));
+    /// assert_eq!(e.type_check(&schema, "name?.len()"), Ok(Type::option(Type::Int)));
+    /// assert_eq!(e.type_check(&schema, "name.unwrap_or(\"\").to_uppercase()"), Ok(Type::Str));
+    /// assert!(matches!(e.type_check(&schema, "age + \"x\""), Err(Error::Type(_))));
+    /// assert!(matches!(e.type_check(&schema, "name.len()"), Err(Error::Type(_))));
+    /// assert_eq!(e.type_check(&schema, "agee > 1"), Err(Error::Undefined("agee".into())));
+    ///# Ok(())
+    ///# }
+    /// ```
+    pub fn type_check(&self, schema: &Schema, src: &str) -> Result<Type, Error> {
+        self.parse_src(src)
+            .and_then(|e| check::Checker::check(&self.0, schema, self.1, &e))
+    }
+
+    fn parse_src(&self, src: &str) -> Result<ast::Expr, Error> {
+        if self.too_deep(src) {
+            return Err(Error::Limit(Limit::Depth));
+        }
+        ast::Expr::parse_limited(src, self.1.limits.depth)
+    }
+
+    /// Source nesting exceeds the depth limit, checked before parse it
+    fn too_deep(&self, src: &str) -> bool {
+        self.1
+            .limits
+            .depth
+            .is_some_and(|max| limits::nesting(src) > max)
     }
 }
 
 #[cfg(test)]
 mod test {
+    extern crate std;
+
+    use std::{boxed::Box, format, string::ToString, vec, vec::Vec};
+
     use super::*;
 
     #[allow(clippy::cognitive_complexity)]
@@ -707,13 +1323,66 @@ mod test {
 
         assert_eq!(e.eval(r#""foo""#).unwrap().to_string(), r#""foo""#,);
         assert_eq!(e.eval("0..1").unwrap().to_string(), "0..1");
-
-        assert_eq!(e.eval("1.log10()").unwrap(), Value::Float(1.0f64.log10()));
-
+        assert_eq!(e.eval("0..=1").unwrap(), Value::RangeInclusive(0..=1));
+        assert_eq!(e.eval("0.0..1").unwrap(), Value::FloatRange(0.0..1.0));
+        assert_eq!(e.eval("'a'..'c'").unwrap(), Value::CharRange('a'..'c'));
+        assert_eq!(
+            e.eval("'a'..='c'").unwrap(),
+            Value::CharRangeInclusive('a'..='c')
+        );
+        assert_eq!(e.eval("'a'..='c'").unwrap().to_string(), "'a'..='c'");
+        assert_eq!(e.eval("0.0..=1.0").unwrap().to_string(), "0.0..=1.0");
+        assert_eq!(
+            e.eval("0..=9223372036854775807").unwrap(),
+            Value::RangeInclusive(0..=i64::MAX)
+        );
+        assert_eq!(
+            e.eval("(0..=9223372036854775807).contains(9223372036854775807)"),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            e.eval("(0..=9223372036854775807)[9223372036854775806..]"),
+            Some(Value::RangeInclusive(i64::MAX - 1..=i64::MAX))
+        );
+        assert_eq!(
+            e.eval("'a'..='\\u{10FFFF}'").unwrap(),
+            Value::CharRangeInclusive('a'..='\u{10FFFF}')
+        );
+        assert_eq!(
+            e.eval("('a'..='\\u{10FFFF}').contains(\"\\u{10FFFF}\")"),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(e.eval("(0..=2) == (0..3)"), None);
+        assert_eq!(
+            e.eval("(0.0..=1.0).contains(1.0)").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            e.eval("(0.0..1.0).contains(1.0)").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            e.eval("('a'..'c').contains(\"c\")").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(e.eval("(1.0..0.0).is_empty()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(0.0..1.0).is_range()").unwrap(), Value::Bool(true));
         assert_eq!(
-            e.eval("1.log10() + 2.0").unwrap(),
-            Value::Float(1.0f64.log10() + 2.0)
+            e.eval("(0..10).step_by(4)").unwrap().to_string(),
+            "[0,4,8,]"
         );
+        assert_eq!(e.eval("(0..10).step_by(0)"), None);
+        assert_eq!(e.eval("'a'..\"bc\""), None);
+        assert_eq!(e.eval("true..false"), None);
+
+        #[cfg(any(feature = "std", feature = "libm"))]
+        {
+            assert_eq!(e.eval("1.log10()").unwrap(), Value::Float(1.0f64.log10()));
+            assert_eq!(
+                e.eval("1.log10() + 2.0").unwrap(),
+                Value::Float(1.0f64.log10() + 2.0)
+            );
+        }
 
         assert_eq!(e.eval("&[true, not_exist]"), None);
         assert_eq!(e.eval("&[true, None]"), None);
@@ -731,12 +1400,1297 @@ mod test {
     }
 
     #[test]
-    fn test_opt() {
-        let e = Eval::default();
-        assert_eq!(e.eval("1.log10()").unwrap(), Value::Float(1.0f64.log10()));
+    fn test_index() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("arr", "[[1, 2], [3, 4]]")?
+            .insert("s", r#""foo,bar""#)?;
+
+        assert_eq!(e.eval("arr[1][0]").unwrap(), Value::Int(3));
+        assert_eq!(e.eval("arr[0][1] + arr[1][1]").unwrap(), Value::Int(6));
+        assert_eq!(e.eval("arr[1][2]"), None);
         assert_eq!(
-            e.eval("None.or(1.log10())"),
-            Some(Value::Float(1.0f64.log10()))
+            e.eval(r#"s.split(",")[0]"#).unwrap(),
+            Value::Str("foo".into())
+        );
+        assert_eq!(
+            e.eval(r#"s.split(",")[1][1..]"#).unwrap(),
+            Value::Str("ar".into())
+        );
+        assert_eq!(e.eval("(0..10)[3]").unwrap(), Value::Int(3));
+        assert_eq!(e.eval("(5..10)[1..3]").unwrap(), Value::Range(6..8));
+        assert_eq!(e.eval("(5..10)[5]"), None);
+        assert_eq!(e.eval("(5..10)[4..6]"), None);
+        assert_eq!(e.eval("(10..5)[0]"), None);
+        assert_eq!(
+            e.negative_index(true).eval("(0..10)[-1]").unwrap(),
+            Value::Int(9)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_map() {
+        let mut user = BTreeMap::new();
+        user.insert("name".to_owned(), Value::from("foo"));
+        user.insert("tags".to_owned(), vec!["a", "b"].into());
+        let mut ctx = BTreeMap::new();
+        ctx.insert("user".to_owned(), Value::Map(user.clone()).into());
+        ctx.insert("key".to_owned(), Value::from("name").into());
+        let e = Eval::new(ctx);
+
+        assert_eq!(e.eval("user").unwrap(), Value::Map(user));
+        assert_eq!(e.eval(r#"user["name"]"#).unwrap(), Value::Str("foo".into()));
+        assert_eq!(e.eval("user[key].len()").unwrap(), Value::Int(3));
+        assert_eq!(
+            e.eval(r#"user["tags"][1]"#).unwrap(),
+            Value::Str("b".into())
+        );
+        assert_eq!(
+            e.eval(r#"user["missing"].is_none()"#).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            e.clone()
+                .none_mode(NoneMode::Strict)
+                .eval(r#"user["missing"].is_none()"#),
+            None
         );
+        assert_eq!(e.eval("user[0]"), None);
+        assert_eq!(e.eval("user.name").unwrap(), Value::Str("foo".into()));
+        assert_eq!(e.eval("user.tags.len()").unwrap(), Value::Int(2));
+        assert_eq!(e.eval("user.missing.is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(
+            e.clone()
+                .none_mode(NoneMode::Strict)
+                .eval("user.missing.is_none()"),
+            None
+        );
+        assert_eq!(e.eval("user == user").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("user < user"), None);
+        assert_eq!(
+            e.eval("user").unwrap().to_string(),
+            r#"{"name":"foo","tags":["a","b",],}"#
+        );
+
+        let m = e
+            .parse(r#"{"b": 1 + 1, "a": [], "b": user["name"],}"#)
+            .unwrap();
+        assert_eq!(m.to_string(), r#"{"b": 1 + 1, "a": [], "b": user["name"]}"#);
+        assert_eq!(
+            e.eval(&m.to_string()).unwrap(),
+            Value::Map(
+                vec![
+                    ("a".to_owned(), Value::Vec(vec![])),
+                    ("b".to_owned(), "foo".into()),
+                ]
+                .into_iter()
+                .collect()
+            )
+        );
+        assert_eq!(e.eval("{}").unwrap(), Value::Map(BTreeMap::new()));
+        assert_eq!(e.eval(r#"{"a": 1}["a"]"#).unwrap(), Value::Int(1));
+        assert_eq!(e.eval(r#"{ "a" }"#).unwrap(), Value::Str("a".into()));
+        assert_eq!(
+            e.eval(r#"match {"a": 1} { m => m["a"] }"#).unwrap(),
+            Value::Int(1)
+        );
+        let nested = e
+            .parse(r#"{"a": {"b": [1, {}]}, "c": (1 + 1, {"d": x})}"#)
+            .unwrap();
+        assert_eq!(e.parse(&nested.to_string()).unwrap(), nested);
+        let folded = e.compile(r#"{"a": {"b": 1 + 1}}"#).unwrap().optimize();
+        assert_eq!(folded.to_string(), r#"{"a": {"b": 2}}"#);
+        assert_eq!(e.eval(&folded.to_string()), e.eval(r#"{"a": {"b": 2}}"#));
+        for src in &[
+            r#"{"a" 1}"#,
+            "{a: 1}",
+            r#"{"a": 1,, }"#,
+            r#"{1: 2}"#,
+            r#"{"a": }"#,
+        ] {
+            assert_eq!(e.parse(src), Err(Error::Parse), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_tuple() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("t", "(1, \"foo\")")?
+            .insert("s", r#""key=value""#)?;
+
+        assert_eq!(e.eval("t").unwrap(), Value::from((1, "foo")));
+        assert_eq!(e.eval("t").unwrap().to_string(), r#"(1,"foo",)"#);
+        assert_eq!(e.eval("t.0 + 1").unwrap(), Value::Int(2));
+        assert_eq!(e.eval("t.1").unwrap(), Value::Str("foo".into()));
+        assert_eq!(e.eval("t.2"), None);
+        assert_eq!(e.eval("((1, 2), 3).0.1").unwrap(), Value::Int(2));
+        assert_eq!(e.eval(r#"t == (1, "foo")"#).unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(1, 2) < (1, 3)").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(2, 0) >= (1, 3)").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(1, 2) + (1, 3)"), None);
+
+        assert_eq!(e.eval("{ let (a, b) = t; a }").unwrap(), Value::Int(1));
+        assert_eq!(
+            e.eval(r#"{ let (k, v) = s.split_once("="); v + k }"#)
+                .unwrap(),
+            Value::Str("valuekey".into())
+        );
+        assert_eq!(
+            e.eval("{ let a = 2; let (b, _) = (a * 2, 0); a + b }")
+                .unwrap(),
+            Value::Int(6)
+        );
+        assert_eq!(e.eval("{ let (a, b) = 1; a }"), None);
+        assert_eq!(e.eval("{ let a = 1; }"), None);
+
+        assert_eq!(
+            e.eval(r#"match t { (0, _) => "zero", (1, x) => x, _ => "other" }"#)
+                .unwrap(),
+            Value::Str("foo".into())
+        );
+        assert_eq!(
+            e.eval("match (2, 5) { (a, b) if a > b => a, (_, b) => b }")
+                .unwrap(),
+            Value::Int(5)
+        );
+        assert_eq!(
+            e.eval("match 7 { 0 | 1 => 0, 2..=9 => 1, _ => 2 }")
+                .unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            e.eval("match 9.5 { 2..=9 => 1, _ => 2 }").unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            e.eval("match -1 { -1 => true, _ => false }").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            e.eval("match [1, 2] { [a, b] => a + b, _ => 0 }").unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            e.eval("match not_exist { None => 0, _ => 1 }").unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(e.eval("match 3 { 0 => 0 }"), None);
+        for (src, v) in &[
+            ("match (1, 2) { (a, ..) => a }", 1),
+            ("match (1, 2, 3) { (.., c) => c }", 3),
+            (
+                "match [1, 2, 3] { [first, rest @ ..] => first + rest.len() }",
+                3,
+            ),
+            ("match [1, 2, 3] { [.., 2, x] => x, _ => 0 }", 3),
+            ("match [1, 2] { [a, .., b] => a + b, _ => 0 }", 3),
+            ("match [1] { [_, _, ..] => 1, _ => 0 }", 0),
+            ("match [1, 2] { [x @ 1, ..] => x, _ => 0 }", 1),
+            ("match 5 { n @ 1..=9 => n * 2, _ => 0 }", 10),
+            ("match 50 { n @ 1..=9 => n * 2, _ => 0 }", 0),
+            ("{ let [a, ..] = [4, 5]; a }", 4),
+        ] {
+            assert_eq!(e.eval(src), Some(Value::Int(*v)), "{}", src);
+        }
+        assert_eq!(
+            e.eval("match [1, 2, 3] { [_, rest @ ..] => rest }")
+                .unwrap(),
+            Value::Vec(vec![2.into(), 3.into()])
+        );
+        assert_eq!(e.eval("match [1, 2] { [.., a, ..] => a }"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order() -> Result<(), ()> {
+        use std::cmp::Ordering;
+
+        let e = Eval::default().insert("name", r#""john""#)?;
+
+        assert_eq!(e.eval(r#"name < "m""#).unwrap(), Value::Bool(true));
+        assert_eq!(e.eval(r#"name >= "johnny""#).unwrap(), Value::Bool(false));
+        assert_eq!(e.eval("[1, 2] < [1, 3]").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("[1, 2] < [1, 2, 0]").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("false < true").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(0..2) > (0..1)").unwrap(), Value::Bool(true));
+        assert_eq!(
+            e.eval("(0.0..2.0) <= (0.0..2.0)").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            e.eval("('a'..'c') < ('b'..'c')").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval(r#"[1, 2] < [1, "a"]"#), None);
+        assert_eq!(e.eval(r#"name < 1"#), None);
+        assert_eq!(
+            e.eval(r#"["b", "a"].sort()"#).unwrap(),
+            Value::Vec(vec!["a".into(), "b".into()])
+        );
+
+        assert_eq!(Value::None.total_cmp(&false.into()), Ordering::Less);
+        assert_eq!(Value::from(1).total_cmp(&1.0.into()), Ordering::Equal);
+        assert_eq!(Value::from(2).total_cmp(&1.5.into()), Ordering::Greater);
+        assert_eq!(
+            Value::from(f64::NAN).total_cmp(&f64::INFINITY.into()),
+            Ordering::Greater
+        );
+        assert_eq!(Value::from(1).total_cmp(&"1".into()), Ordering::Less);
+        assert_eq!(
+            Value::from(vec![1, 2]).total_cmp(&vec![Value::from(1), "a".into()].into()),
+            Ordering::Less
+        );
+        assert_eq!(
+            Value::from((1,)).total_cmp(&vec![1].into()),
+            Ordering::Greater
+        );
+
+        // Over 2^53 an `i64` isn't exact as a float
+        assert_eq!(
+            e.eval("[9007199254740993, 9007199254740992.0, 9007199254740992].sort()")
+                .unwrap()
+                .to_string(),
+            "[9007199254740992,9007199254740992,9007199254740993,]"
+        );
+        assert_eq!(
+            Value::from(9_007_199_254_740_993i64).total_cmp(&9_007_199_254_740_992.0.into()),
+            Ordering::Greater
+        );
+        assert_eq!(Value::from(-3).total_cmp(&(-2.5).into()), Ordering::Less);
+        assert_eq!(Value::from(-2.5).total_cmp(&(-2).into()), Ordering::Less);
+        assert_eq!(
+            Value::from(i64::MAX).total_cmp(&9_223_372_036_854_775_808.0.into()),
+            Ordering::Less
+        );
+        assert_eq!(
+            Value::from(i64::MIN).total_cmp(&(-9_223_372_036_854_775_808.0).into()),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Value::from(i64::MIN).total_cmp(&f64::NEG_INFINITY.into()),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Value::from(i64::MAX).total_cmp(&f64::NAN.into()),
+            Ordering::Less
+        );
+        let mut v: Vec<Value> = vec![
+            9_007_199_254_740_993i64.into(),
+            9_007_199_254_740_992.0.into(),
+            9_007_199_254_740_992i64.into(),
+            9_007_199_254_740_994.0.into(),
+        ];
+        v.sort_by(Value::total_cmp);
+        for w in v.windows(2) {
+            assert_ne!(w[0].total_cmp(&w[1]), Ordering::Greater);
+        }
+        assert_eq!(v[2], Value::Int(9_007_199_254_740_993));
+        for w in v.windows(2) {
+            assert_eq!(w[0].partial_cmp(&w[1]), Some(w[0].total_cmp(&w[1])));
+            assert_eq!(w[0] == w[1], w[0].total_cmp(&w[1]) == Ordering::Equal);
+        }
+        let (int, float) = (
+            Value::Int(9_007_199_254_740_993),
+            Value::Float(9_007_199_254_740_992.0),
+        );
+        assert_ne!(int, float);
+        assert_eq!(int.partial_cmp(&float), Some(Ordering::Greater));
+        assert_eq!(float.partial_cmp(&int), Some(Ordering::Less));
+        assert_eq!(Value::Int(1 << 53), Value::Float(9_007_199_254_740_992.0));
+        assert_eq!(Value::Int(1).partial_cmp(&Value::Float(f64::NAN)), None);
+        assert_eq!(
+            e.eval("9007199254740993 == 9007199254740992.0").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            e.eval("9007199254740992.0 < 9007199254740993").unwrap(),
+            Value::Bool(true)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_none_mode() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("a", "1")?
+            .insert("undef", "not_exist")?;
+
+        assert_eq!(e.eval("not_exist"), None);
+        assert_eq!(e.eval("undef.is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("a.foo.is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("[a, None]"), None);
+
+        let e = e.none_mode(NoneMode::Strict);
+        assert_eq!(e.eval("a + 1").unwrap(), Value::Int(2));
+        assert_eq!(e.eval("None.is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("undef.is_none()"), None);
+        assert_eq!(e.eval("a.foo.is_none()"), None);
+
+        let e = e.none_mode(NoneMode::Propagate);
+        assert_eq!(e.eval("not_exist"), None);
+        assert_eq!(
+            e.eval("(a * not_exist + 1).is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("(-not_exist).is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(!not_exist).is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("(a == None).is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("not_exist && false").unwrap(), Value::Bool(false));
+        assert_eq!(e.eval("false && not_exist").unwrap(), Value::Bool(false));
+        assert_eq!(
+            e.eval("(not_exist && true).is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("true || not_exist").unwrap(), Value::Bool(true));
+        assert_eq!(
+            e.eval("(not_exist || false).is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("not_exist || 1"), None);
+        assert_eq!(e.eval("a + true"), None);
+        assert_eq!(
+            e.eval("[a, None]").unwrap(),
+            Value::Vec(vec![Value::Int(1), Value::None])
+        );
+        assert_eq!(e.eval("[a, None].len()").unwrap(), Value::Int(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optional() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("s", r#""foo""#)?
+            .insert("t", "(1, (2, 3))")?;
+
+        assert_eq!(e.eval("s?.len()").unwrap(), Value::Int(3));
+        assert_eq!(
+            e.eval("not_exist?.len().is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("not_exist.len()"), None);
+        assert_eq!(e.eval("t?.1?.0").unwrap(), Value::Int(2));
+        assert_eq!(
+            e.eval("not_exist?.1?.0.is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            e.eval("not_exist?[0].is_none()").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("not_exist[0]"), None);
+        assert_eq!(e.eval("s?[0..1]").unwrap(), Value::Str("f".into()));
+        assert_eq!(
+            e.eval("not_exist?.foo?.bar.unwrap_or(1)").unwrap(),
+            Value::Int(1)
+        );
+        assert_eq!(
+            e.none_mode(NoneMode::Strict)
+                .eval("s?.foo?.bar.unwrap_or(1)"),
+            None
+        );
+
+        let e = Eval::default().insert("s", r#""foo""#)?;
+        assert_eq!(e.eval("s.map_or(0, |(a, b)| a)"), None);
+        assert_eq!(e.eval("s.map_or(0, |x, y| x)"), None);
+        assert_eq!(e.eval("s.map_or(0, 1)"), None);
+        assert_eq!(e.eval("s.filter(|x| 1)"), None);
+        assert_eq!(
+            e.eval("s.unwrap_or_else(|x| 1)").unwrap(),
+            Value::Str("foo".into())
+        );
+        assert_eq!(e.eval("None.unwrap_or_else(|x| 1)"), None);
+        assert_eq!(
+            e.eval("{ let n = 2; s.map_or(0, |x| x.len() * n) }")
+                .unwrap(),
+            Value::Int(6)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_elements() -> Result<(), ()> {
+        let e = Eval::default().insert("arr", "[1, missing, 3]")?;
+
+        assert_eq!(e.eval("arr"), None);
+        assert_eq!(e.eval("arr[0]"), None);
+        assert_eq!(e.eval("arr.len()").unwrap(), Value::Int(3));
+        assert_eq!(e.eval("arr.compact()[1]").unwrap(), Value::Int(3));
+
+        let e = e.nullable_elements(true);
+        assert_eq!(
+            e.eval("arr").unwrap(),
+            Value::Vec(vec![1.into(), Value::None, 3.into()])
+        );
+        assert_eq!(e.eval("arr[0]").unwrap(), Value::Int(1));
+        assert_eq!(e.eval("arr[1]"), None);
+        assert_eq!(e.eval("arr[1].is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(e.eval("arr.get(1).is_none()").unwrap(), Value::Bool(true));
+        assert_eq!(
+            e.eval("(missing, 1)").unwrap(),
+            Value::Tuple(vec![Value::None, 1.into()])
+        );
+        assert_eq!(
+            e.eval("[None].starts_with([None])").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(e.eval("missing"), None);
+
+        assert!(Value::Vec(vec![Value::None]).is_some());
+        assert!(!Value::Vec(vec![Value::None]).is_complete());
+        assert!(Value::Vec(vec![Value::Vec(vec![])]).is_complete());
+        assert!(Value::None.is_none());
+
+        let mut m = BTreeMap::new();
+        m.insert("a".to_owned(), Value::Int(1));
+        m.insert("b".to_owned(), Value::None);
+        m.insert("c".to_owned(), vec![Value::None].into());
+        let mut ctx = BTreeMap::new();
+        ctx.insert("m".to_owned(), Value::Map(m.clone()).into());
+        let e = Eval::new(ctx);
+        assert!(!Value::Map(m.clone()).is_complete());
+        assert_eq!(e.eval("m"), None);
+        assert_eq!(
+            e.clone().nullable_elements(true).eval("m").unwrap(),
+            Value::Map(m.clone())
+        );
+        m.remove("b");
+        assert_eq!(e.eval("m.compact()"), None);
+        assert_eq!(
+            e.clone()
+                .nullable_elements(true)
+                .eval("m.compact()")
+                .unwrap(),
+            Value::Map(m.clone())
+        );
+        m.insert("c".to_owned(), Value::Vec(vec![]));
+        assert_eq!(e.eval("m.flatten_none()").unwrap(), Value::Map(m));
+        assert_eq!(
+            e.eval(r#"m.flatten_none()["b"].is_none()"#).unwrap(),
+            Value::Bool(true)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("a", "b")?
+            .insert("b", "a + 1")?
+            .insert("c", "c")?
+            .insert("d", "[e, e]")?
+            .insert("e", "f * 2")?
+            .insert("f", "1")?
+            .insert("g", "{ let g = 1; g + 1 }")?;
+
+        assert_eq!(e.eval("a"), None);
+        assert_eq!(
+            e.try_eval("b"),
+            Err(Error::Cycle(vec!["b".into(), "a".into(), "b".into()]))
+        );
+        assert_eq!(
+            e.try_eval("1 + c"),
+            Err(Error::Cycle(vec!["c".into(), "c".into()]))
+        );
+        assert_eq!(e.try_eval("d"), Ok(Value::Vec(vec![2.into(), 2.into()])));
+        assert_eq!(e.try_eval("e + f + e"), Ok(Value::Int(5)));
+        assert_eq!(e.try_eval("g"), Ok(Value::Int(2)));
+
+        // Diamond dependencies are evaluated once, without it this is 2^40 lookups
+        let mut e = Eval::default().insert("v0", "1")?;
+        for i in 1..=40 {
+            e = e.insert(&format!("v{}", i), &format!("v{} + v{}", i - 1, i - 1))?;
+        }
+        assert_eq!(e.eval("v40").unwrap(), Value::Int(1 << 40));
+        assert_eq!(
+            e.none_mode(NoneMode::Strict).try_eval("x + 1"),
+            Err(Error::Undefined("x".into()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_index() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("arr", "[1, 2, 3]")?
+            .insert("s", r#""foo""#)?;
+
+        assert_eq!(e.eval("arr[-1]"), None);
+        assert_eq!(e.eval("s[-2..]"), None);
+        assert_eq!(e.eval("s[1..]").unwrap(), Value::Str("oo".into()));
+        assert_eq!(e.eval("s[..=1]").unwrap(), Value::Str("fo".into()));
+
+        let e = e.negative_index(true);
+        assert_eq!(e.eval("arr[-1]").unwrap(), Value::Int(3));
+        assert_eq!(e.eval("arr[-3]").unwrap(), Value::Int(1));
+        assert_eq!(e.eval("arr[-4]"), None);
+        assert_eq!(
+            e.eval("arr[-2..]").unwrap(),
+            Value::Vec(vec![Value::Int(2), Value::Int(3)])
+        );
+        assert_eq!(
+            e.eval("arr[..-1]").unwrap(),
+            Value::Vec(vec![1.into(), 2.into()])
+        );
+        assert_eq!(e.eval("s[-2..]").unwrap(), Value::Str("oo".into()));
+        assert_eq!(e.eval("s[..=-1]").unwrap(), Value::Str("foo".into()));
+        assert_eq!(e.eval("s[(-3..-1)]").unwrap(), Value::Str("fo".into()));
+        assert_eq!(e.eval("s[(-3..=-1)]").unwrap(), Value::Str("foo".into()));
+
+        let max = "9223372036854775807";
+        assert_eq!(e.eval(&format!("arr[..={}]", max)), None);
+        assert_eq!(e.eval(&format!("s[1..={}]", max)), None);
+        assert_eq!(e.eval(&format!("arr[(0..={})]", max)), None);
+        assert_eq!(e.eval(&format!("arr[{}..]", max)), None);
+        assert_eq!(
+            e.eval(&format!("(0..={0})[..={0}]", max)).unwrap(),
+            Value::RangeInclusive(0..=i64::MAX)
+        );
+        assert_eq!(
+            e.eval(&format!("(0..={0})[{0}]", max)).unwrap(),
+            Value::Int(i64::MAX)
+        );
+        assert_eq!(e.eval("(0..3)[..=3]"), None);
+        assert_eq!(e.eval("(0..3)[2..=1]").unwrap(), Value::Range(2..2));
+        assert_eq!(e.eval("(-2..=2)[..=-2]").unwrap(), Value::Range(-2..2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits() -> Result<(), ()> {
+        let mut e = Eval::default();
+        for i in 0..20 {
+            e = e.insert(&format!("v{}", i), &format!("v{} + 1", i + 1))?;
+        }
+        let e = e.insert("v20", "0")?.insert("s", r#""foo""#)?;
+        assert_eq!(e.eval("v0").unwrap(), Value::Int(20));
+        assert_eq!(e.eval("s * 1000").unwrap(), Value::Str("foo".repeat(1000)));
+
+        let limited = |l: EvalLimits| Eval::new(e.0.clone()).limits(l);
+        let e = limited(EvalLimits::default().max_depth(32));
+        assert_eq!(e.try_eval("v0"), Err(Error::Limit(Limit::Depth)));
+        assert_eq!(e.try_eval("v10"), Ok(Value::Int(10)));
+        assert_eq!(e.try_eval("((((1))))"), Ok(Value::Int(1)));
+        assert_eq!(
+            e.try_eval(&format!("{}1{}", "(".repeat(33), ")".repeat(33))),
+            Err(Error::Limit(Limit::Depth))
+        );
+        assert_eq!(
+            e.try_eval(&format!(r#""{}""#, "(".repeat(40))),
+            Ok(Value::Str("(".repeat(40)))
+        );
+        assert!(e
+            .clone()
+            .insert("deep", &format!("{}1{}", "[".repeat(33), "]".repeat(33)))
+            .is_err());
+        for src in &[
+            format!("{}true", "!".repeat(200_000)),
+            format!("1{}", "+1".repeat(300_000)),
+            format!("{}1", "|| ".repeat(200_000)),
+            format!("a{}", ".b".repeat(200_000)),
+        ] {
+            assert_eq!(e.try_eval(src), Err(Error::Limit(Limit::Depth)));
+        }
+        assert_eq!(
+            e.try_eval("!!true && - -1 + 2 * 3 > 4"),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            e.try_eval(&format!("0{}", " + 1".repeat(20))),
+            Ok(Value::Int(20))
+        );
+
+        let e = limited(EvalLimits::default().max_steps(64));
+        assert_eq!(e.try_eval("v10"), Ok(Value::Int(10)));
+        assert_eq!(e.try_eval("v0"), Err(Error::Limit(Limit::Steps)));
+
+        let e = limited(EvalLimits::default().max_size(9));
+        assert_eq!(e.try_eval("s * 3"), Ok(Value::Str("foofoofoo".into())));
+        assert_eq!(e.try_eval("s * 4"), Err(Error::Limit(Limit::Size)));
+        assert_eq!(e.try_eval("s + s + s + s"), Err(Error::Limit(Limit::Size)));
+        assert_eq!(
+            e.try_eval("s * 9223372036854775807"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(e.try_eval("(0..9).step_by(1).len()"), Ok(Value::Int(9)));
+        for src in &[
+            "(0..9223372036854775807).step_by(2).first()",
+            "(0..20).step_by(2).contains(0)",
+            "('a'..='z').step_by(2).len()",
+        ] {
+            assert_eq!(e.try_eval(src), Err(Error::Limit(Limit::Size)), "{}", src);
+        }
+        assert_eq!(
+            e.try_eval("(0..10).step_by(1)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            e.try_eval("(-9223372036854775807..9223372036854775807).step_by(1)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            e.try_eval("('a'..'z').step_by(1)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            e.try_eval("(0.0..1e300).step_by(1.0)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            e.try_eval("(0.0..=8.0).step_by(1).len()"),
+            Ok(Value::Int(9))
+        );
+        assert_eq!(
+            e.try_eval("(0.0..=9.0).step_by(1)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            limited(EvalLimits::default().max_size(1 << 16)).try_eval("(0.0..1e12).step_by(1)"),
+            Err(Error::Limit(Limit::Size))
+        );
+        assert_eq!(
+            e.try_eval("[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]"),
+            Err(Error::Limit(Limit::Size))
+        );
+
+        #[cfg(feature = "std")]
+        {
+            use std::time::Duration;
+
+            let e = limited(EvalLimits::default().timeout(Duration::from_secs(0)));
+            let src = format!("[{}]", vec!["v0"; 100].join(", "));
+            assert_eq!(e.try_eval(&src), Err(Error::Limit(Limit::Time)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dependencies() -> Result<(), ()> {
+        let set = |s: &[&str]| s.iter().map(|s| s.to_string()).collect();
+        let e = Eval::default()
+            .insert("a", "b + c.len()")?
+            .insert("b", "a")?
+            .insert("x", "1")?;
+
+        let deps = e.free_variables("a").map_err(|_| ())?;
+        assert_eq!(deps.variables, set(&["a", "b", "c"]));
+        assert_eq!(deps.methods, set(&["len"]));
+
+        let deps = e
+            .free_variables("{ let y = x + z; y * w }.is_some() && None.is_none()")
+            .map_err(|_| ())?;
+        assert_eq!(deps.variables, set(&["w", "x", "z"]));
+        assert_eq!(deps.methods, set(&["is_none", "is_some"]));
+
+        let deps = e
+            .free_variables("match (k, v) { (1, n) if n > m => n, (None, _) => x, _ => n }")
+            .map_err(|_| ())?;
+        assert_eq!(deps.variables, set(&["k", "m", "n", "v", "x"]));
+        assert!(deps.methods.is_empty());
+
+        let deps = e
+            .free_variables("v.map_or(x, |v| v.len() + y)")
+            .map_err(|_| ())?;
+        assert_eq!(deps.variables, set(&["v", "x", "y"]));
+        assert_eq!(deps.methods, set(&["len", "map_or"]));
+
+        let c = e.compile("x + 1").map_err(|_| ())?;
+        assert_eq!(c.dependencies().variables, set(&["x"]));
+        assert_eq!(c.eval(&e).unwrap(), Value::Int(2));
+        assert_eq!(c.eval(&e.insert("x", "2")?).unwrap(), Value::Int(3));
+        assert!(matches!(Eval::default().compile("1 +"), Err(Error::Parse)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_check() -> Result<(), ()> {
+        let schema = Schema::default()
+            .insert("age", "Int")?
+            .insert("score", "Float")?
+            .insert("name", "Str")?
+            .insert("nick", "Option<Str>")?
+            .insert("tags", "Vec<Str>")?
+            .insert("pair", "(Int, Str)")?
+            .insert("user", "{ id: Int, emails: Vec<Option<Str>> }")?;
+        let e = Eval::default()
+            .insert("adult", "age >= 18")?
+            .insert("a", "b")?
+            .insert("b", "a")?;
+        let check = |src: &str| e.type_check(&schema, src);
+        let ty = |t: &str| t.parse::<Type>();
+
+        for t in &[
+            "Vec<Option<Float>>",
+            "(Int,)",
+            "(Int, Str)",
+            "{a: Int, b: Vec<Str>}",
+        ] {
+            assert_eq!(ty(t)?.to_string(), *t);
+        }
+        assert_eq!(ty(" Option< Option<Int> > ")?, Type::option(Type::Int));
+        assert!(ty("Vec<Int").is_err() && ty("Foo").is_err() && ty("{a Int}").is_err());
+
+        let ok = |src: &str, t: &str| assert_eq!(check(src), Ok(ty(t).unwrap()), "{}", src);
+        ok("adult && name.len() > 2", "Bool");
+        ok("age * 2 + 1", "Int");
+        ok("\"ab\" * age", "Str");
+        #[cfg(any(feature = "std", feature = "libm"))]
+        ok("score.sqrt().round() + age", "Int");
+        ok("age + score", "Float");
+        ok("tags.first()", "Option<Str>");
+        ok("tags[0].to_uppercase()", "Str");
+        ok("tags[1..]", "Vec<Str>");
+        ok("tags.get(1)?.len()", "Option<Int>");
+        ok("nick?.trim()", "Option<Str>");
+        ok("nick.unwrap_or(name)", "Str");
+        ok("nick.map_or(0, |n| n.len())", "Int");
+        ok("nick.filter(|n| n.is_empty())", "Option<Str>");
+        ok("nick.is_some()", "Bool");
+        ok("name.split_once(\",\")", "Option<(Str, Str)>");
+        ok("pair.1", "Str");
+        ok("user.emails.compact()", "Vec<Str>");
+        ok("user.compact()", "{emails: Vec<Option<Str>>, id: Int}");
+        ok("user.flatten_none().emails", "Vec<Str>");
+        ok("(0..age).step_by(2)", "Vec<Int>");
+        ok("('a'..='z').contains(name)", "Bool");
+        ok("{ let (n, s) = pair; n + s.len() }", "Int");
+        ok(
+            "match tags[..] { [first, rest @ ..] => rest.len(), _ => age }",
+            "Int",
+        );
+        ok("match age { 0..=17 => None, n => n }", "Option<Int>");
+        ok("match pair { (.., s) => s }", "Str");
+        ok("match tags[..] { [.., last] => last, _ => name }", "Str");
+        ok("[age, None]", "Vec<Option<Int>>");
+        ok("[age, name]", "Vec<Any>");
+        ok("user.id + 1", "Int");
+        ok("user == user", "Bool");
+
+        let fails = |src: &str| assert!(matches!(check(src), Err(Error::Type(_))), "{}", src);
+        fails("age + \"x\"");
+        fails("age + tags");
+        fails("nick + name");
+        fails("nick.len()");
+        fails("name.sqrt()");
+        fails("tags.foo()");
+        fails("tags[name]");
+        fails("pair.2");
+        fails("user.name");
+        fails("user < user");
+        fails("user != user.id");
+        fails("user + user");
+        fails("!age");
+        fails("name.filter(|n| n.len())");
+        fails("name.contains(nick)");
+        assert_eq!(check("agee"), Err(Error::Undefined("agee".into())));
+        assert!(matches!(check("a"), Err(Error::Cycle(_))));
+
+        let e = e.none_mode(NoneMode::Propagate);
+        assert_eq!(e.type_check(&schema, "nick + name"), Ok(ty("Option<Str>")?));
+        assert_eq!(e.type_check(&schema, "None > age"), Ok(ty("Option<Bool>")?));
+
+        let e = Eval::default()
+            .insert("n", "3")?
+            .insert("s", "\"a,b\"")?
+            .insert("v", "[1.5, 2.0]")?;
+        for src in &[
+            "n * 2 % 2",
+            "s.split(\",\")",
+            "s.find(\"b\")",
+            "v.sort()[0].floor()",
+            "(n, s.len() > 1, 1..n)",
+            "v.iter().is_none()",
+        ] {
+            if let Some(v) = e.eval(src) {
+                let t = e.type_check(&Schema::default(), src).map_err(|_| ())?;
+                assert_eq!(Type::unify(t.clone(), Type::of(&v)), t, "{}", src);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(any(feature = "std", feature = "libm"))]
+    #[test]
+    fn test_opt() {
+        let e = Eval::default();
+        assert_eq!(e.eval("1.log10()").unwrap(), Value::Float(1.0f64.log10()));
+        assert_eq!(
+            e.eval("None.or(1.log10())"),
+            Some(Value::Float(1.0f64.log10()))
+        );
+    }
+
+    #[test]
+    fn test_optimize() -> Result<(), ()> {
+        let e = Eval::default().insert("x", "3")?.insert("b", "true")?;
+        let optimize = |src: &str| {
+            e.compile(src)
+                .map(|c| c.optimize().to_string())
+                .map_err(|_| ())
+        };
+
+        assert_eq!(e.eval("1 / 0"), None);
+        assert_eq!(e.eval("9223372036854775807 + 1"), None);
+        assert_eq!(e.eval("-(-9223372036854775807 - 1)"), None);
+        assert_eq!(optimize("2 * (1 + 1)")?, "4");
+        #[cfg(any(feature = "std", feature = "libm"))]
+        assert_eq!(optimize("x + \"ab\".len() * 10.log10()")?, "x + 2.0");
+        assert_eq!(optimize("x * (2 - 5)")?, "x * -3");
+        assert_eq!(optimize("(1 - 1.5).abs() + x.pow(2)")?, "0.5 + x.pow(2)");
+        assert_eq!(optimize("!!(x > 1) && true")?, "x > 1");
+        assert_eq!(optimize("false || !(!b)")?, "!!b");
+        assert_eq!(optimize("!!(x > 1) || false && true")?, "x > 1");
+        assert_eq!(optimize("b && true")?, "b && true");
+        assert_eq!(optimize("x && false")?, "x && false");
+        assert_eq!(optimize("!!x")?, "!!x");
+        assert_eq!(
+            optimize("x.unwrap_or_else(|| 1 / 0)")?,
+            "x.unwrap_or_else(|| 1 / 0)"
+        );
+        assert_eq!(
+            optimize("x.map_or(1 + 1, |v| v + 2 * 3)")?,
+            "x.map_or(2, |v| v + 6)"
+        );
+        assert_eq!(
+            optimize("match x { -1 => 1 + 1, _ => 0 }")?,
+            "match x { -1 => 2, _ => 0 }"
+        );
+        assert_eq!(optimize("[1, 1 + 1].len() + x")?, "2 + x");
+        assert_eq!(optimize("None.is_none() && x > 0")?, "x > 0");
+        assert_eq!(
+            optimize("(0..3).step_by(1).contains(x)")?,
+            "(0..3).step_by(1).contains(x)"
+        );
+
+        let c = Eval::default()
+            .negative_index(true)
+            .compile("[1, 2][-1] + x")
+            .map_err(|_| ())?
+            .optimize();
+        assert_eq!(c.to_string(), "2 + x");
+
+        // `&&` and `||` have the same precedence in the evaluation
+        let src = "b || true && false";
+        let c = e.compile(src).map_err(|_| ())?;
+        assert_eq!(c.clone().optimize().to_string(), "(b || true) && false");
+        assert_eq!(c.optimize().eval(&e), e.eval(src));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() -> Result<(), Error> {
+        let cases = [
+            ("a+b*c", "a + b * c"),
+            ("(a + b) * c", "(a + b) * c"),
+            ("a - (b - c) - d", "a - (b - c) - d"),
+            ("((a - b)) - c", "a - b - c"),
+            ("(a == b) == c", "(a == b) == c"),
+            ("a && b && (c && d)", "a && b && (c && d)"),
+            ("-(a + 1).abs()", "-(a + 1).abs()"),
+            ("(-a).abs() + &(b)", "(-a).abs() + b"),
+            ("(0 .. n + 1).contains(x)", "(0..n + 1).contains(x)"),
+            ("x?.len( )", "x?.len()"),
+            ("'\\x41' == \"\\u{41}\"", "'A' == \"A\""),
+            ("0b101 + 1_0 + 2.50e1 + 1e400", "5 + 10 + 25.0 + 1e400"),
+            ("[1,2,(3)] [0]", "[1, 2, 3][0]"),
+            ("(1 ,)", "(1,)"),
+            ("o.map_or(0,|(a,b)|a*b)", "o.map_or(0, |(a, b)| a * b)"),
+            (
+                "match x { 1|2=>{let y=x;y} , n if n>(0)=>n , _=>0 }",
+                "match x { 1 | 2 => { let y = x; y }, n if n > 0 => n, _ => 0 }",
+            ),
+        ];
+        for (src, expected) in cases.iter() {
+            assert_eq!(format(src)?, *expected);
+            assert_eq!(format(expected)?, *expected);
+        }
+        assert_eq!(format("1 +"), Err(Error::Parse));
+
+        let c = Eval::default().compile("((x*3))")?;
+        assert_eq!(c.to_string(), "x * 3");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser() {
+        let e = Eval::default().insert("t", "((1, 2), 3)").unwrap();
+        let cases = [
+            ("0x1F + 0o17 + 0b11 + 1_000", Value::Int(31 + 15 + 3 + 1000)),
+            ("1. + 1e2 + 2E-1 + 3f64", Value::Float(1. + 100. + 0.2 + 3.)),
+            ("1..=2", Value::RangeInclusive(1..=2)),
+            ("t.0.1 + t.1", Value::Int(5)),
+            (
+                "\"a\\tb\\u{2764}\\\n   c\"",
+                Value::Str("a\tb\u{2764}c".into()),
+            ),
+            (
+                "r#\"\"a\"\"# /* outer /* inner */ */",
+                Value::Str("\"a\"".into()),
+            ),
+            ("1 // comment", Value::Int(1)),
+            ("t.1.map_or(0, |x| x + 1)", Value::Int(4)),
+            ("(&mut 2) * &3", Value::Int(6)),
+            (
+                "match 2 { 1 => { 1 } 2 => match 1 { _ => 2 } _ => 3 }",
+                Value::Int(2),
+            ),
+            (
+                "match [1, 2, 3] { [a, _, b] => a + b, _ => 0 }",
+                Value::Int(4),
+            ),
+            (
+                "match (-1, 'a') { (-1, c) => c == 'a', _ => false }",
+                Value::Bool(true),
+            ),
+        ];
+        for (src, expected) in cases.iter() {
+            assert_eq!(e.eval(src).as_ref(), Some(expected), "{}", src);
+        }
+
+        for src in &[
+            "a.b::<u8>()",
+            "f(x)",
+            "x as i64",
+            "{ x; 1 }",
+            "'a: { 1 }",
+            "a << 1",
+            "1 +",
+            "(1 2)",
+            "\"unterminated",
+            "0xFFFF_FFFF_FFFF_FFFF",
+        ] {
+            assert_eq!(e.parse(src), Err(Error::Parse), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_ast() -> Result<(), Error> {
+        use ast::{BinOp, Expr, Lit, Pat, UnOp, Visit};
+
+        let e = Eval::default().insert("x", "2").map_err(|_| Error::Parse)?;
+        let var = |n: &str| Box::new(Expr::Var(n.into()));
+
+        assert_eq!(
+            e.parse("a || b && c")?,
+            Expr::Binary(
+                Box::new(Expr::Binary(var("a"), BinOp::Or, var("b"))),
+                BinOp::And,
+                var("c")
+            )
+        );
+        assert_eq!(e.parse("-&(x)")?, Expr::Unary(UnOp::Neg, var("x")));
+        assert_eq!(e.parse("None")?, Expr::Lit(Lit::None));
+        assert_eq!(
+            e.parse("match x { -1 | None => 0, _ => 1 }")?.to_string(),
+            "match x { -1 | None => 0, _ => 1 }"
+        );
+        if let Expr::Match(_, arms) = e.parse("match x { -1 | None => 0, _ => 1 }")? {
+            assert_eq!(
+                arms[0].pat,
+                Pat::Or(vec![Pat::Lit(Lit::Int(-1)), Pat::Lit(Lit::None)])
+            );
+        } else {
+            panic!("match expression");
+        }
+        for src in &[
+            "a.b::<u8>",
+            "f(x)",
+            "x as i64",
+            "{ x; 1 }",
+            "'a: { 1 }",
+            "a << 1",
+        ] {
+            assert_eq!(e.parse(src), Err(Error::Parse), "{}", src);
+        }
+
+        #[derive(Default)]
+        struct Vars(Vec<String>);
+        impl Visit for Vars {
+            fn visit_expr(&mut self, e: &Expr) {
+                if let Expr::Var(n) = e {
+                    self.0.push(n.clone());
+                }
+                ast::visit_expr(self, e)
+            }
+        }
+        let mut vars = Vars::default();
+        vars.visit_expr(&e.parse("a.map_or(b, |(v, w)| { let y = v; [y, w, c][0] })")?);
+        assert_eq!(vars.0, ["a", "b", "v", "y", "w", "c"]);
+
+        let tree = Expr::Binary(
+            Box::new(Expr::Binary(var("x"), BinOp::Sub, var("x"))),
+            BinOp::Mul,
+            Box::new(Expr::Unary(UnOp::Neg, Box::new(Expr::Lit(Lit::Int(-3))))),
+        );
+        assert_eq!(tree.to_string(), "(x - x) * -(-3)");
+        assert_eq!(e.compile_ast(&tree)?.eval(&e), Some(Value::Int(0)));
+        let min = Expr::Lit(Lit::Int(i64::MIN));
+        assert_eq!(e.compile_ast(&min)?.eval(&e), Some(Value::Int(i64::MIN)));
+        assert_eq!(
+            e.compile_ast(&Expr::Var("not valid".into())).err(),
+            Some(Error::Parse)
+        );
+        let e = e.insert("a", "10").unwrap().insert("b", "3").unwrap();
+        assert_eq!(
+            e.compile_ast(&Expr::Var("a-b".into())).err(),
+            Some(Error::Parse)
+        );
+        let tree = Expr::Binary(var("a"), BinOp::Sub, var("b"));
+        let compiled = e.compile_ast(&tree)?;
+        assert_eq!(compiled.expr, tree);
+        assert_eq!(compiled.eval(&e), Some(Value::Int(7)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), Error> {
+        use ast::{array, closure, none, try_closure, try_var, tuple, var, Expr, Lit};
+
+        let e = Eval::default();
+        let cases: Vec<(Expr, &str)> = vec![
+            (var("a") + 1 - var("b") * 2.5, "a + 1 - b * 2.5"),
+            (var("a") - (var("b") - var("c")), "a - (b - c)"),
+            (-(var("a") + 1), "-(a + 1)"),
+            (!var("a").eq(none()), "!(a == None)"),
+            (Expr::from(-3).method("abs", vec![]), "(-3).abs()"),
+            (Expr::from(-1.5) * -2, "-1.5 * -2"),
+            (var("a").lt(1).eq(true), "(a < 1) == true"),
+            (var("a").or(var("b")).and(var("c")), "(a || b) && c"),
+            (var("a").or(var("b").and(var("c"))), "a || (b && c)"),
+            (var("t").nth(0).optional().field("x"), "t.0?.x"),
+            (array(vec![1, 2]).at(Expr::from(0).range(1)), "[1, 2][0..1]"),
+            (tuple(vec!['a']).nth(0), "('a',).0"),
+            (
+                var("o").method("filter", vec![closure(&["_"], true)]),
+                "o.filter(|_| true)",
+            ),
+            (
+                Expr::from(1)
+                    .range_closed(var("n") + 1)
+                    .method("contains", vec![var("x")]),
+                "(1..=n + 1).contains(x)",
+            ),
+            (
+                Expr::from(Value::Vec(vec![
+                    Value::Int(-1),
+                    Value::None,
+                    "\"\\\n".into(),
+                ])),
+                "[-1, None, \"\\\"\\\\\\n\"]",
+            ),
+            (Expr::from(Lit::Int(i64::MIN)), "(-9223372036854775807 - 1)"),
+        ];
+        for (tree, src) in cases {
+            assert_eq!(tree.to_string(), src);
+            if let Expr::Lit(Lit::Int(i64::MIN)) = tree {
+                continue;
+            }
+            assert_eq!(e.parse(src)?, tree, "{}", src);
+        }
+
+        let user = "O'Brien \"Bob\"\\";
+        let rule = var("name").eq(user);
+        let e = e
+            .insert("name", &format!("{:?}", user))
+            .map_err(|_| Error::Parse)?;
+        assert_eq!(e.compile_ast(&rule)?.eval(&e), Some(Value::Bool(true)));
+
+        assert_eq!(try_var("a-b"), Err(Error::Parse));
+        assert_eq!(try_var("match"), Err(Error::Parse));
+        assert_eq!(try_closure(&["a, b"], true), Err(Error::Parse));
+        assert_eq!(try_closure(&["a", "_"], true)?, closure(&["a", "_"], true));
+        let trees = [
+            (var("a-b"), "a-b"),
+            (var("None"), "None"),
+            (var("a").field("x) || (true"), "a.x) || (true"),
+            (var("a").method("len() + 1", vec![]), "a.len() + 1()"),
+            (closure(&["a, b"], true), "|a, b| true"),
+        ];
+        for (tree, src) in trees.iter() {
+            assert_eq!(tree.to_string(), *src);
+            assert_eq!(e.compile_ast(tree).err(), Some(Error::Parse));
+            assert_eq!(
+                e.clone().insert_ast("x", tree.clone()).err(),
+                Some(Error::Parse)
+            );
+        }
+        assert_eq!(try_var("_a1")?.field("é").to_string(), "_a1.é");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_eval() -> Result<(), ()> {
+        let e = Eval::default()
+            .insert("tier", "\"silver\"")?
+            .insert("rate", "0.5")?
+            .insert("nums", "[1, 2, 3]")?
+            .insert("big", "amount > 100")?
+            .insert("loop_a", "loop_b + 1")?
+            .insert("loop_b", "loop_a + 1")?;
+        let partial = |src: &str| e.partial_eval(src).map(|c| c.to_string()).map_err(|_| ());
+
+        assert_eq!(partial("tier == \"gold\" && amount > 100")?, "false");
+        assert_eq!(partial("tier != \"gold\" || amount > 100")?, "true");
+        assert_eq!(
+            partial("amount > 100 && tier == \"silver\"")?,
+            "amount > 100"
+        );
+        assert_eq!(partial("flag && tier.len() == 6")?, "flag");
+        assert_eq!(partial("amount * rate * 2.0")?, "amount * 0.5 * 2.0");
+        assert_eq!(
+            partial("nums.get(k).map_or(0, |x| x * rate)")?,
+            "[1, 2, 3].get(k).map_or(0, |x| x * 0.5)"
+        );
+        assert_eq!(partial("nums.last().map_or(0, |x| x * 2)")?, "6");
+        assert_eq!(partial("big || tier == \"gold\"")?, "amount > 100");
+        assert_eq!(partial("|amount| big")?, "|amount| big");
+        assert_eq!(
+            partial("{ let tier = x; tier.len() }")?,
+            "{ let tier = x; tier.len() }"
+        );
+        assert_eq!(
+            partial("match y { tier => tier + rate }")?,
+            "match y { tier => tier + 0.5 }"
+        );
+        assert_eq!(partial("loop_a > k")?, "loop_a + 1 + 1 > k");
+        assert!(e.partial_eval("1 +").is_err());
+
+        let c = e
+            .partial_eval("big && tier == \"silver\"")
+            .map_err(|_| ())?;
+        assert_eq!(
+            c.dependencies().variables,
+            ["amount".to_owned()].iter().cloned().collect()
+        );
+        for amount in &["50", "150"] {
+            let e = e.clone().insert("amount", amount)?;
+            assert_eq!(c.eval(&e), e.eval("big && tier == \"silver\""));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    mod prop {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        fn expr() -> impl Strategy<Value = String> {
+            let leaf = prop_oneof![
+                (-5i64..50).prop_map(|n| n.to_string()),
+                prop_oneof![Just("0.5"), Just("2.0"), Just("1e3")].prop_map(String::from),
+                prop_oneof![Just("true"), Just("false"), Just("None")].prop_map(String::from),
+                prop_oneof![Just("\"ab\""), Just("\"a,b\"")].prop_map(String::from),
+                prop_oneof![Just("x"), Just("y"), Just("s"), Just("b"), Just("n")]
+                    .prop_map(String::from),
+            ];
+            leaf.prop_recursive(4, 32, 2, |inner| {
+                let op = prop_oneof![
+                    Just("+"),
+                    Just("-"),
+                    Just("*"),
+                    Just("/"),
+                    Just("%"),
+                    Just("=="),
+                    Just("!="),
+                    Just("<"),
+                    Just(">="),
+                    Just("&&"),
+                    Just("||"),
+                ];
+                let method = prop_oneof![
+                    Just("len()"),
+                    Just("abs()"),
+                    Just("log10()"),
+                    Just("round()"),
+                    Just("is_some()"),
+                    Just("to_uppercase()"),
+                ];
+                prop_oneof![
+                    (inner.clone(), op.clone(), inner.clone())
+                        .prop_map(|(a, op, b)| format!("({} {} {})", a, op, b)),
+                    (inner.clone(), op, inner.clone())
+                        .prop_map(|(a, op, b)| format!("{} {} {}", a, op, b)),
+                    inner.clone().prop_map(|a| format!("!{}", a)),
+                    inner.clone().prop_map(|a| format!("-{}", a)),
+                    (inner.clone(), method).prop_map(|(a, m)| format!("({}).{}", a, m)),
+                    (inner.clone(), inner.clone())
+                        .prop_map(|(a, b)| format!("({}).unwrap_or({})", a, b)),
+                    (inner.clone(), inner.clone())
+                        .prop_map(|(a, b)| format!("[{}, {}].len()", a, b)),
+                    (inner.clone(), inner)
+                        .prop_map(|(a, b)| format!("({}).map_or({}, |v| v)", a, b)),
+                ]
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn optimize_evaluates_identically(src in expr(), mode in 0..3u8) {
+                let e = Eval::default()
+                    .insert("x", "3").unwrap()
+                    .insert("y", "-1.5").unwrap()
+                    .insert("s", "\"foo\"").unwrap()
+                    .insert("b", "true").unwrap()
+                    .none_mode(match mode {
+                        0 => NoneMode::Lenient,
+                        1 => NoneMode::Strict,
+                        _ => NoneMode::Propagate,
+                    });
+                let c = e.compile(&src).unwrap();
+                let expected = c.try_eval(&e);
+                let folded = c.optimize().try_eval(&e);
+                match (&expected, &folded) {
+                    (Ok(a), Ok(b)) => prop_assert_eq!(a.total_cmp(b), std::cmp::Ordering::Equal),
+                    _ => prop_assert_eq!(expected.is_ok(), folded.is_ok()),
+                }
+            }
+
+            #[test]
+            fn ast_prints_the_format(src in expr()) {
+                let e = Eval::default()
+                    .insert("x", "3").unwrap()
+                    .insert("y", "-1.5").unwrap()
+                    .insert("s", "\"foo\"").unwrap()
+                    .insert("b", "true").unwrap();
+                let tree = e.parse(&src).unwrap();
+                prop_assert_eq!(tree.to_string(), format(&src).unwrap());
+                prop_assert_eq!(e.parse(&tree.to_string()).unwrap().to_string(), tree.to_string());
+                match (e.try_eval(&src), e.compile_ast(&tree).unwrap().try_eval(&e)) {
+                    (Ok(a), Ok(b)) => prop_assert_eq!(a.total_cmp(&b), std::cmp::Ordering::Equal),
+                    (a, b) => prop_assert_eq!(a.is_ok(), b.is_ok()),
+                }
+            }
+
+            #[test]
+            fn format_is_stable(src in expr()) {
+                let e = Eval::default()
+                    .insert("x", "3").unwrap()
+                    .insert("y", "-1.5").unwrap()
+                    .insert("s", "\"foo\"").unwrap()
+                    .insert("b", "true").unwrap();
+                let formatted = format(&src).unwrap();
+                prop_assert_eq!(format(&formatted).unwrap(), formatted.clone());
+                match (e.try_eval(&src), e.try_eval(&formatted)) {
+                    (Ok(a), Ok(b)) => prop_assert_eq!(a.total_cmp(&b), std::cmp::Ordering::Equal),
+                    (a, b) => prop_assert_eq!(a.is_ok(), b.is_ok()),
+                }
+            }
+        }
     }
 }
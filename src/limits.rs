@@ -0,0 +1,132 @@
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+/// Resource budgets of a single evaluation, all unlimited by default
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalLimits {
+    pub(crate) depth: Option<usize>,
+    pub(crate) steps: Option<usize>,
+    pub(crate) size: Option<usize>,
+    #[cfg(feature = "std")]
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl EvalLimits {
+    /// Maximum nesting of sub expressions, in the parsed tree and while evaluating
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+
+        self
+    }
+
+    /// Maximum number of evaluation steps
+    pub fn max_steps(mut self, steps: usize) -> Self {
+        self.steps = Some(steps);
+
+        self
+    }
+
+    /// Maximum length of a produced `Str`, in bytes, or `Vec`
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.size = Some(size);
+
+        self
+    }
+
+    /// Maximum wall-clock time, checked between steps, with the `std` feature
+    #[cfg(feature = "std")]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+
+        self
+    }
+}
+
+/// Exceeded limit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Depth,
+    Steps,
+    Size,
+    Time,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::Limit::*;
+        f.write_str(match self {
+            Depth => "depth",
+            Steps => "steps",
+            Size => "size",
+            Time => "time",
+        })
+    }
+}
+
+/// Bracket nesting of a source, quick check before parsing it
+pub(crate) fn nesting(src: &str) -> usize {
+    let mut depth = 0usize;
+    let mut max = 0;
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    max
+}
+
+/// Upper bound of the tree nesting of a source, for a parser that can't
+/// track it: every open group adds a level and its operators since the last
+/// separator
+#[cfg(feature = "rust-syntax")]
+pub(crate) fn height(src: &str) -> usize {
+    let mut groups = alloc::vec![0usize];
+    let mut ops = 0usize;
+    let mut max = 0;
+    let mut chars = src.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => groups.push(0),
+            ')' | ']' | '}' if groups.len() > 1 => ops -= groups.pop().unwrap_or(0),
+            ',' | ';' => ops -= core::mem::take(groups.last_mut().expect("group")),
+            '+' | '-' | '*' | '/' | '%' | '!' | '&' | '|' | '<' | '>' | '=' | '.' | '?' | '@' => {
+                *groups.last_mut().expect("group") += 1;
+                ops += 1;
+            }
+            '"' | '\'' => {
+                while let Some(q) = chars.next() {
+                    match q {
+                        '\\' => {
+                            chars.next();
+                        }
+                        q if q == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+        max = max.max(groups.len() - 1 + ops);
+    }
+
+    max
+}
@@ -1,6 +1,11 @@
-use std::str::FromStr;
+use alloc::vec::Vec;
+use core::{option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
@@ -12,6 +17,7 @@ pub(crate) enum Fun {
     Int,
     Range,
     Str,
+    Tuple,
     Vec,
     Same = 1 << F,
 }
@@ -33,6 +39,7 @@ impl FromStr for Fun {
             "is_range" => Ok(Range),
             "is_same" => Ok(Same),
             "is_str" => Ok(Str),
+            "is_tuple" => Ok(Tuple),
             "is_vec" => Ok(Vec),
             _ => Err(()),
         }
@@ -43,9 +50,9 @@ impl Eval for Fun {
     #[inline]
     fn eval(self, stack: &mut Vec<Value>) -> Result<(), ()> {
         macro_rules! check {
-            ($pat:pat) => {{
+            ($($pat:pat)|+) => {{
                 let op1 = stack.pop().ok_or(())?;
-                if let $pat = op1 {
+                if let $($pat)|+ = op1 {
                     stack.push(true.into());
                 } else {
                     stack.push(false.into());
@@ -58,13 +65,21 @@ impl Eval for Fun {
             Bool => check!(Value::Bool(_)),
             Float => check!(Value::Float(_)),
             Int => check!(Value::Int(_)),
-            Range => check!(Value::Range(_)),
+            Range => check!(
+                Value::Range(_)
+                    | Value::FloatRange(_)
+                    | Value::CharRange(_)
+                    | Value::RangeInclusive(_)
+                    | Value::FloatRangeInclusive(_)
+                    | Value::CharRangeInclusive(_)
+            ),
             Same => {
                 let op2 = stack.pop().ok_or(())?;
                 let op1 = stack.pop().ok_or(())?;
                 stack.push(op1.is_same(&op2).into())
             }
             Str => check!(Value::Str(_)),
+            Tuple => check!(Value::Tuple(_)),
             Vec => check!(Value::Vec(_)),
         }
 
@@ -78,3 +93,9 @@ impl HasArg for Fun {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, _: &Type, _: Option<&Type>) -> Option<Type> {
+        Some(Type::Bool)
+    }
+}
@@ -1,6 +1,11 @@
-use std::str::FromStr;
+use alloc::vec::Vec;
+use core::{option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
@@ -100,72 +105,177 @@ impl FromStr for Fun {
             "trunc" => Ok(Trunc),
             _ => Err(()),
         }
+        .and_then(|f: Fun| {
+            if cfg!(any(feature = "std", feature = "libm")) || !f.is_math() {
+                Ok(f)
+            } else {
+                Err(())
+            }
+        })
+    }
+}
+
+impl Fun {
+    /// Needs the functions of `std` or `libm`
+    fn is_math(self) -> bool {
+        !matches!(
+            self,
+            Abs | Max | Min | Recip | Signum | ToDegrees | ToRadians
+        )
     }
 }
 
 impl Eval for Fun {
-    #[allow(clippy::cognitive_complexity)]
     #[inline]
     fn eval(self, stack: &mut Vec<Value>) -> Result<(), ()> {
-        macro_rules! to_int {
-            ($fun:ident) => {{
-                let v: f64 = fun!($fun, f64, stack);
-
-                (v as i64).into()
-            }};
-        }
         let e = match self {
-            Atan2 => fun_arg_s!(atan2, f64, stack),
-            Hypot => fun_arg_s!(hypot, f64, stack),
-            Log => fun_arg_s!(log, f64, stack),
             Max => fun_arg_s!(max, f64, stack),
             Min => fun_arg_s!(min, f64, stack),
-            PowF => fun_arg_s!(powf, f64, stack),
-            PowI => {
-                let op2: f64 = pop!(stack);
-                let op1: f64 = pop!(stack);
-                op1.powi(op2 as i32).into()
-            }
             Abs => fun!(abs, f64, stack),
-            Acos => fun!(acos, f64, stack),
-            Acosh => fun!(acosh, f64, stack),
-            Asin => fun!(asin, f64, stack),
-            Asinh => fun!(asinh, f64, stack),
-            Atan => fun!(atan, f64, stack),
-            Atanh => fun!(atanh, f64, stack),
-            Cbrt => fun!(cbrt, f64, stack),
-            Cos => fun!(cos, f64, stack),
-            Cosh => fun!(cosh, f64, stack),
-            Exp => fun!(exp, f64, stack),
-            Exp2 => fun!(exp2, f64, stack),
-            ExpM1 => fun!(exp_m1, f64, stack),
-            Fract => fun!(fract, f64, stack),
-            Ln => fun!(ln, f64, stack),
-            Ln1p => fun!(ln_1p, f64, stack),
-            Log10 => fun!(log10, f64, stack),
-            Log2 => fun!(log2, f64, stack),
             Recip => fun!(recip, f64, stack),
             Signum => fun!(signum, f64, stack),
-            Sin => fun!(sin, f64, stack),
-            Sinh => fun!(sinh, f64, stack),
-            Sqrt => fun!(sqrt, f64, stack),
-            Tan => fun!(tan, f64, stack),
-            Tanh => fun!(tanh, f64, stack),
             ToDegrees => fun!(to_degrees, f64, stack),
             ToRadians => fun!(to_radians, f64, stack),
-            Ceil => to_int!(ceil),
-            Floor => to_int!(floor),
-            Round => to_int!(round),
-            Trunc => to_int!(trunc),
+            #[cfg(any(feature = "std", feature = "libm"))]
+            _ => math(self, stack)?,
+            #[cfg(not(any(feature = "std", feature = "libm")))]
+            _ => return Err(()),
         };
         stack.push(e);
         Ok(())
     }
 }
 
+#[cfg(any(feature = "std", feature = "libm"))]
+#[allow(clippy::cognitive_complexity)]
+fn math(f: Fun, stack: &mut Vec<Value>) -> Result<Value, ()> {
+    macro_rules! un {
+        ($fun:ident) => {{
+            let op1: f64 = pop!(stack);
+            float::$fun(op1).into()
+        }};
+    }
+    macro_rules! bin {
+        ($fun:ident) => {{
+            let op2: f64 = pop!(stack);
+            let op1: f64 = pop!(stack);
+            float::$fun(op1, op2).into()
+        }};
+    }
+    macro_rules! to_int {
+        ($fun:ident) => {{
+            let op1: f64 = pop!(stack);
+
+            (float::$fun(op1) as i64).into()
+        }};
+    }
+    Ok(match f {
+        Atan2 => bin!(atan2),
+        Hypot => bin!(hypot),
+        Log => bin!(log),
+        PowF => bin!(powf),
+        PowI => {
+            let op2: f64 = pop!(stack);
+            let op1: f64 = pop!(stack);
+            float::powi(op1, op2 as i32).into()
+        }
+        Acos => un!(acos),
+        Acosh => un!(acosh),
+        Asin => un!(asin),
+        Asinh => un!(asinh),
+        Atan => un!(atan),
+        Atanh => un!(atanh),
+        Cbrt => un!(cbrt),
+        Cos => un!(cos),
+        Cosh => un!(cosh),
+        Exp => un!(exp),
+        Exp2 => un!(exp2),
+        ExpM1 => un!(exp_m1),
+        Fract => un!(fract),
+        Ln => un!(ln),
+        Ln1p => un!(ln_1p),
+        Log10 => un!(log10),
+        Log2 => un!(log2),
+        Sin => un!(sin),
+        Sinh => un!(sinh),
+        Sqrt => un!(sqrt),
+        Tan => un!(tan),
+        Tanh => un!(tanh),
+        Ceil => to_int!(ceil),
+        Floor => to_int!(floor),
+        Round => to_int!(round),
+        Trunc => to_int!(trunc),
+        Abs | Max | Min | Recip | Signum | ToDegrees | ToRadians => return Err(()),
+    })
+}
+
+/// Float functions, the methods of `std` or else the functions of `libm`
+#[cfg(any(feature = "std", feature = "libm"))]
+mod float {
+    macro_rules! float {
+        ($($name:ident($x:ident $(, $arg:ident: $t:ty)*) => $libm:expr;)*) => {$(
+            #[inline]
+            pub(super) fn $name($x: f64 $(, $arg: $t)*) -> f64 {
+                #[cfg(feature = "std")]
+                return $x.$name($($arg),*);
+                #[cfg(not(feature = "std"))]
+                return $libm;
+            }
+        )*};
+    }
+
+    float! {
+        acos(x) => libm::acos(x);
+        acosh(x) => libm::acosh(x);
+        asin(x) => libm::asin(x);
+        asinh(x) => libm::asinh(x);
+        atan(x) => libm::atan(x);
+        atan2(x, y: f64) => libm::atan2(x, y);
+        atanh(x) => libm::atanh(x);
+        cbrt(x) => libm::cbrt(x);
+        ceil(x) => libm::ceil(x);
+        cos(x) => libm::cos(x);
+        cosh(x) => libm::cosh(x);
+        exp(x) => libm::exp(x);
+        exp2(x) => libm::exp2(x);
+        exp_m1(x) => libm::expm1(x);
+        floor(x) => libm::floor(x);
+        fract(x) => x - libm::trunc(x);
+        hypot(x, y: f64) => libm::hypot(x, y);
+        ln(x) => libm::log(x);
+        ln_1p(x) => libm::log1p(x);
+        log(x, base: f64) => libm::log(x) / libm::log(base);
+        log10(x) => libm::log10(x);
+        log2(x) => libm::log2(x);
+        powf(x, n: f64) => libm::pow(x, n);
+        powi(x, n: i32) => libm::pow(x, n.into());
+        round(x) => libm::round(x);
+        sin(x) => libm::sin(x);
+        sinh(x) => libm::sinh(x);
+        sqrt(x) => libm::sqrt(x);
+        tan(x) => libm::tan(x);
+        tanh(x) => libm::tanh(x);
+        trunc(x) => libm::trunc(x);
+    }
+}
+
 impl HasArg for Fun {
     #[inline]
     fn has_arg(self) -> bool {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        let num = |t: &Type| matches!(t, Type::Int | Type::Float);
+        if !num(receiver) || !arg.is_none_or(num) {
+            return None;
+        }
+
+        Some(match self {
+            Ceil | Floor | Round | Trunc => Type::Int,
+            _ => Type::Float,
+        })
+    }
+}
@@ -1,6 +1,11 @@
-use std::{convert::TryInto, str::FromStr};
+use alloc::vec::Vec;
+use core::{convert::TryInto, option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 macro_rules! pop {
     ($stack:ident) => {
@@ -47,6 +52,7 @@ macro_rules! fun_arg_un {
 pub mod dyn_type;
 pub mod f64_t;
 pub mod option_t;
+pub mod range_t;
 pub mod slice_t;
 pub mod str_t;
 pub mod vec_t;
@@ -60,6 +66,7 @@ pub(crate) enum Method {
     DynType(dyn_type::Fun),
     F64(f64_t::Fun),
     Option(option_t::Fun),
+    Range(range_t::Fun),
     Slice(slice_t::Fun),
     Str(str_t::Fun),
     VecT(vec_t::Fun),
@@ -85,7 +92,7 @@ impl FromStr for Method {
                 }
             };
         }
-        Ok(parse!(DynType, Option, VecT, Slice, Str, F64))
+        Ok(parse!(DynType, Option, Range, VecT, Slice, Str, F64))
     }
 }
 
@@ -95,11 +102,19 @@ impl Eval for Method {
             DynType(f) => f.eval(stack),
             F64(f) => f.eval(stack),
             Option(f) => f.eval(stack),
+            Range(f) => f.eval(stack),
             Slice(f) => f.eval(stack),
             Str(f) => f.eval(stack),
             VecT(f) => f.eval(stack),
         }
     }
+
+    fn size_hint(self, stack: &[Value]) -> usize {
+        match self {
+            Range(f) => f.size_hint(stack),
+            _ => 0,
+        }
+    }
 }
 
 impl HasArg for Method {
@@ -108,9 +123,24 @@ impl HasArg for Method {
             DynType(f) => f.has_arg(),
             F64(f) => f.has_arg(),
             Option(f) => f.has_arg(),
+            Range(f) => f.has_arg(),
             Slice(f) => f.has_arg(),
             Str(f) => f.has_arg(),
             VecT(f) => f.has_arg(),
         }
     }
 }
+
+impl Infer for Method {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        match self {
+            DynType(f) => f.infer(receiver, arg),
+            F64(f) => f.infer(receiver, arg),
+            Option(f) => f.infer(receiver, arg),
+            Range(f) => f.infer(receiver, arg),
+            Slice(f) => f.infer(receiver, arg),
+            Str(f) => f.infer(receiver, arg),
+            VecT(f) => f.infer(receiver, arg),
+        }
+    }
+}
@@ -1,6 +1,11 @@
-use std::str::FromStr;
+use alloc::vec::Vec;
+use core::{option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
@@ -12,6 +17,15 @@ pub(crate) enum Fun {
     And = (1 << F) + 1,
     Or = (1 << F) + 2,
     Xor = (1 << F) + 3,
+    UnwrapOr = (1 << F) + 4,
+}
+
+/// Methods taking closures, evaluated by the visitor
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Lazy {
+    Filter,
+    MapOr,
+    UnwrapOrElse,
 }
 
 use Fun::*;
@@ -32,6 +46,21 @@ impl FromStr for Fun {
             "and" => Ok(And),
             "or" => Ok(Or),
             "xor" => Ok(Xor),
+            "unwrap_or" => Ok(UnwrapOr),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Lazy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Lazy::*;
+        match s {
+            "filter" => Ok(Filter),
+            "map_or" => Ok(MapOr),
+            "unwrap_or_else" => Ok(UnwrapOrElse),
             _ => Err(()),
         }
     }
@@ -62,6 +91,11 @@ impl Eval for Fun {
             IsSome => bool!(is_some),
             Or => fun_arg_un!(or, unpack, stack),
             Xor => fun_arg_un!(xor, unpack, stack),
+            UnwrapOr => {
+                let op2 = stack.pop().ok_or(())?;
+                let op1 = stack.pop().ok_or(())?;
+                unpack!(op1).unwrap_or(op2)
+            }
         };
         stack.push(e);
 
@@ -75,3 +109,19 @@ impl HasArg for Fun {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        Some(match (self, arg.cloned()) {
+            (IsNone, _) | (IsSome, _) => Type::Bool,
+            (And, Some(arg)) => match receiver {
+                Type::None => Type::None,
+                Type::Option(_) => Type::option(arg),
+                _ => arg,
+            },
+            (Or, Some(arg)) | (UnwrapOr, Some(arg)) => Type::or(receiver, arg),
+            (Xor, Some(arg)) => Type::option(Type::unify(receiver.clone(), arg)),
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::{
+    convert::{TryFrom, TryInto},
+    option::Option,
+    str::FromStr,
+};
+
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
+
+use super::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u8)]
+pub(crate) enum Fun {
+    /// Collected in a `Vec`, not lazy when other methods are chained
+    StepBy = 1 << F,
+}
+
+use Fun::*;
+
+/// Has arguments flags
+const F: u8 = 6;
+/// Has arguments number of leading zeros
+const L: u8 = 1;
+
+impl FromStr for Fun {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "step_by" => Ok(StepBy),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Eval for Fun {
+    #[inline]
+    fn eval(self, stack: &mut Vec<Value>) -> Result<(), ()> {
+        let e = match self {
+            StepBy => {
+                let hint = self.size_hint(stack);
+                let op2 = stack.pop().ok_or(())?;
+                let op1 = stack.pop().ok_or(())?;
+                match op1 {
+                    Value::Range(op1) => op1.step_by(step(op2)?).collect::<Vec<_>>().into(),
+                    Value::RangeInclusive(op1) => {
+                        op1.step_by(step(op2)?).collect::<Vec<_>>().into()
+                    }
+                    Value::CharRange(op1) => op1
+                        .step_by(step(op2)?)
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .into(),
+                    Value::CharRangeInclusive(op1) => op1
+                        .step_by(step(op2)?)
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .into(),
+                    Value::FloatRange(op1) => {
+                        float_step(op1.start, op2, hint, |x| op1.contains(x))?
+                    }
+                    Value::FloatRangeInclusive(op1) => {
+                        float_step(*op1.start(), op2, hint, |x| op1.contains(x))?
+                    }
+                    _ => return Err(()),
+                }
+            }
+        };
+        stack.push(e);
+
+        Ok(())
+    }
+
+    fn size_hint(self, stack: &[Value]) -> usize {
+        match (self, stack) {
+            (StepBy, [.., range, step]) => {
+                let (len, step) = match (range, step) {
+                    (Value::Range(r), Value::Int(s)) => {
+                        (i128::from(r.end) - i128::from(r.start), i128::from(*s))
+                    }
+                    (Value::RangeInclusive(r), Value::Int(s)) => (
+                        i128::from(*r.end()) - i128::from(*r.start()) + 1,
+                        i128::from(*s),
+                    ),
+                    (Value::CharRange(r), Value::Int(s)) => (
+                        i128::from(r.end as u32) - i128::from(r.start as u32),
+                        i128::from(*s),
+                    ),
+                    (Value::CharRangeInclusive(r), Value::Int(s)) => (
+                        i128::from(*r.end() as u32) - i128::from(*r.start() as u32) + 1,
+                        i128::from(*s),
+                    ),
+                    (Value::FloatRange(r), s) => {
+                        return float_len(r.end - r.start, s, false);
+                    }
+                    (Value::FloatRangeInclusive(r), s) => {
+                        return float_len(r.end() - r.start(), s, true);
+                    }
+                    _ => return 0,
+                };
+                if len <= 0 || step <= 0 {
+                    0
+                } else {
+                    usize::try_from(len / step).unwrap_or(usize::MAX)
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Step of an integer or char range, not zero
+fn step(v: Value) -> Result<usize, ()> {
+    match v.try_into()? {
+        0 => Err(()),
+        step => Ok(step),
+    }
+}
+
+/// Number of steps in a float range of `len`, with the step converted as
+/// `step_by` does
+fn float_len(len: f64, step: &Value, inclusive: bool) -> usize {
+    let step = match step {
+        Value::Int(s) => *s as f64,
+        Value::Float(s) => *s,
+        _ => return 0,
+    };
+    if !(step > 0.0 && len >= 0.0) {
+        return 0;
+    }
+    let n = len / step;
+    // The cast saturates, as the floor of a positive number
+    let steps = n as usize;
+    if inclusive || (steps as f64) < n {
+        steps.saturating_add(1)
+    } else {
+        steps
+    }
+}
+
+/// Values from `start` by `step` while the range contains them, at most one
+/// more than the checked `hint`
+fn float_step(
+    start: f64,
+    step: Value,
+    hint: usize,
+    contains: impl Fn(&f64) -> bool,
+) -> Result<Value, ()> {
+    let step: f64 = step.try_into()?;
+    if step.is_nan() || step <= 0.0 || !start.is_finite() {
+        return Err(());
+    }
+    let mut v = vec![];
+    let mut i = 0;
+    loop {
+        let x = start + step * i as f64;
+        if !contains(&x) {
+            break;
+        }
+        if v.len() > hint {
+            return Err(());
+        }
+        v.push(x);
+        i += 1;
+    }
+
+    Ok(v.into())
+}
+
+impl HasArg for Fun {
+    #[inline]
+    fn has_arg(self) -> bool {
+        (self as u8).leading_zeros() as u8 == L
+    }
+}
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        let e = match (self, receiver, arg?) {
+            (StepBy, Type::Range, Type::Int) => Type::Int,
+            (StepBy, Type::CharRange, Type::Int) => Type::Str,
+            (StepBy, Type::FloatRange, Type::Int | Type::Float) => Type::Float,
+            _ => return None,
+        };
+
+        Some(Type::Vec(Box::new(e)))
+    }
+}
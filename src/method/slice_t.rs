@@ -1,6 +1,11 @@
-use std::str::FromStr;
+use alloc::{string::String, vec::Vec};
+use core::{option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
@@ -76,7 +81,12 @@ impl Eval for Fun {
                     match op1 {
                         Value::Vec(op1) => op1.is_empty(),
                         Value::Str(op1) => op1.is_empty(),
-                        Value::Range(op1) => op1.start == op1.end,
+                        Value::Range(op1) => op1.is_empty(),
+                        Value::FloatRange(op1) => op1.is_empty(),
+                        Value::CharRange(op1) => op1.is_empty(),
+                        Value::RangeInclusive(op1) => op1.is_empty(),
+                        Value::FloatRangeInclusive(op1) => op1.is_empty(),
+                        Value::CharRangeInclusive(op1) => op1.is_empty(),
                         _ => return Err(()),
                     }
                     .into(),
@@ -90,6 +100,15 @@ impl Eval for Fun {
                         Value::Vec(op1) => op1.contains(&op2),
                         Value::Str(op1) => op1.contains(&TryInto::<String>::try_into(op2)?),
                         Value::Range(op1) => op1.contains(&TryInto::<i64>::try_into(op2)?),
+                        Value::FloatRange(op1) => op1.contains(&TryInto::<f64>::try_into(op2)?),
+                        Value::CharRange(op1) => char(op2)?.is_some_and(|c| op1.contains(&c)),
+                        Value::RangeInclusive(op1) => op1.contains(&TryInto::<i64>::try_into(op2)?),
+                        Value::FloatRangeInclusive(op1) => {
+                            op1.contains(&TryInto::<f64>::try_into(op2)?)
+                        }
+                        Value::CharRangeInclusive(op1) => {
+                            char(op2)?.is_some_and(|c| op1.contains(&c))
+                        }
                         _ => return Err(()),
                     }
                     .into(),
@@ -109,3 +128,30 @@ impl HasArg for Fun {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        use Type::{CharRange, Float, FloatRange, Int, Range, Str, Vec};
+        Some(match (self, receiver, arg) {
+            (Len, Vec(_) | Str, None) => Int,
+            (IsEmpty, Vec(_) | Str | Range | FloatRange | CharRange, None) => Type::Bool,
+            (Contains, Vec(_), Some(_))
+            | (Contains, Str | CharRange, Some(Str))
+            | (Contains, Range, Some(Int))
+            | (Contains, FloatRange, Some(Int | Float))
+            | (StartsWith | EndsWith, Vec(_), Some(Vec(_)))
+            | (StartsWith | EndsWith, Str, Some(Str)) => Type::Bool,
+            _ => return None,
+        })
+    }
+}
+
+/// Char of a string operand, `None` unless it has exactly one
+fn char(v: Value) -> Result<Option<char>, ()> {
+    let s: String = v.try_into()?;
+    let mut chars = s.chars();
+    Ok(match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    })
+}
@@ -1,8 +1,14 @@
-use std::{convert::TryInto, str::FromStr};
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::{convert::TryInto, option::Option, str::FromStr};
 
+#[cfg(feature = "regex")]
 use regex::Regex;
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
@@ -19,8 +25,11 @@ pub(crate) enum Fun {
     TrimStart,
     EqIgnoreAsciiCase = 1 << F,
     Find = (1 << F) + 1,
+    #[cfg(feature = "regex")]
     IsMatch = (1 << F) + 2,
     RFind = (1 << F) + 3,
+    Split = (1 << F) + 4,
+    SplitOnce = (1 << F) + 5,
 }
 
 use Fun::*;
@@ -38,6 +47,7 @@ impl FromStr for Fun {
             "eq_ignore_ascii_case" => Ok(EqIgnoreAsciiCase),
             "find" => Ok(Find),
             "is_ascii" => Ok(IsAscii),
+            #[cfg(feature = "regex")]
             "is_match" => Ok(IsMatch),
             "to_lowercase" => Ok(ToLowercase),
             "to_uppercase" => Ok(ToUppercase),
@@ -47,6 +57,8 @@ impl FromStr for Fun {
             "trim_end" => Ok(TrimEnd),
             "trim_start" => Ok(TrimStart),
             "rfind" => Ok(RFind),
+            "split" => Ok(Split),
+            "split_once" => Ok(SplitOnce),
             _ => Err(()),
         }
     }
@@ -67,6 +79,7 @@ impl Eval for Fun {
             EqIgnoreAsciiCase => fun_ref!(eq_ignore_ascii_case),
             Find => fun_ref!(find),
             IsAscii => fun!(is_ascii, String, stack),
+            #[cfg(feature = "regex")]
             IsMatch => {
                 let op2: String = pop!(stack);
                 let op1: String = pop!(stack);
@@ -81,6 +94,16 @@ impl Eval for Fun {
             TrimEnd => fun!(trim_end, String, stack),
             TrimStart => fun!(trim_start, String, stack),
             RFind => fun_ref!(rfind),
+            Split => {
+                let op2: String = pop!(stack);
+                let op1: String = pop!(stack);
+                op1.split(op2.as_str()).collect::<Vec<_>>().into()
+            }
+            SplitOnce => {
+                let op2: String = pop!(stack);
+                let op1: String = pop!(stack);
+                op1.split_once(op2.as_str()).into()
+            }
         };
         stack.push(e);
 
@@ -94,3 +117,23 @@ impl HasArg for Fun {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        match (receiver, arg) {
+            (Type::Str, None | Some(Type::Str)) => (),
+            _ => return None,
+        }
+
+        Some(match self {
+            EqIgnoreAsciiCase | IsAscii => Type::Bool,
+            #[cfg(feature = "regex")]
+            IsMatch => Type::Bool,
+            Find | RFind => Type::option(Type::Int),
+            ToLowercase | ToUppercase | ToAsciiLowercase | ToAsciiUppercase | Trim | TrimEnd
+            | TrimStart => Type::Str,
+            Split => Type::Vec(Box::new(Type::Str)),
+            SplitOnce => Type::option(Type::Tuple(vec![Type::Str, Type::Str])),
+        })
+    }
+}
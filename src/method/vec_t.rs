@@ -1,15 +1,24 @@
-use std::str::FromStr;
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+use core::{convert::TryInto, option::Option, str::FromStr};
 
-use crate::{reflect::Eval, Value};
+use crate::{
+    reflect::Eval,
+    types::{Infer, Type},
+    Value,
+};
 
 use super::*;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(u8)]
 pub(crate) enum Fun {
+    Compact,
     First,
+    FlattenNone,
     Last,
+    Sort,
     Get = 1 << F,
+    NthBack = (1 << F) + 1,
 }
 
 /// Has arguments flags
@@ -23,9 +32,13 @@ impl FromStr for Fun {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use Fun::*;
         match s {
+            "compact" => Ok(Compact),
             "first" => Ok(First),
+            "flatten_none" => Ok(FlattenNone),
             "get" => Ok(Get),
             "last" => Ok(Last),
+            "nth_back" => Ok(NthBack),
+            "sort" => Ok(Sort),
             _ => Err(()),
         }
     }
@@ -36,9 +49,36 @@ impl Eval for Fun {
     fn eval(self, stack: &mut Vec<Value>) -> Result<(), ()> {
         use Fun::*;
         let e = match self {
+            Compact => match stack.pop().ok_or(())? {
+                Value::Map(mut m) => {
+                    m.retain(|_, v| v.is_some());
+                    Value::Map(m)
+                }
+                v => {
+                    let op1: Vec<Value> = v.try_into()?;
+                    op1.into_iter()
+                        .filter(Value::is_some)
+                        .collect::<Vec<_>>()
+                        .into()
+                }
+            },
             First => fun!(first, Vec<Value>, stack),
+            FlattenNone => match stack.pop().ok_or(())? {
+                Value::Map(m) => Value::Map(flatten_map(m)),
+                v => Value::Vec(flatten_none(v.try_into()?)),
+            },
             Get => fun_arg!(get, usize, Vec<Value>, stack),
             Last => fun!(last, Vec<Value>, stack),
+            Sort => {
+                let mut op1: Vec<Value> = pop!(stack);
+                op1.sort_by(Value::total_cmp);
+                op1.into()
+            }
+            NthBack => {
+                let op2: usize = pop!(stack);
+                let op1: Vec<Value> = pop!(stack);
+                op1.into_iter().nth_back(op2).into()
+            }
         };
         stack.push(e);
 
@@ -46,9 +86,81 @@ impl Eval for Fun {
     }
 }
 
+/// Remove `None` elements at any depth of nested `Vec` and `Map`
+fn flatten_none(v: Vec<Value>) -> Vec<Value> {
+    v.into_iter()
+        .filter(Value::is_some)
+        .map(flatten_value)
+        .collect()
+}
+
+/// Remove `None` entries at any depth of nested `Map` and `Vec`
+fn flatten_map(m: BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+    m.into_iter()
+        .filter(|(_, v)| v.is_some())
+        .map(|(k, v)| (k, flatten_value(v)))
+        .collect()
+}
+
+fn flatten_value(v: Value) -> Value {
+    match v {
+        Value::Vec(v) => Value::Vec(flatten_none(v)),
+        Value::Map(m) => Value::Map(flatten_map(m)),
+        v => v,
+    }
+}
+
 impl HasArg for Fun {
     #[inline]
     fn has_arg(self) -> bool {
         (self as u8).leading_zeros() as u8 == L
     }
 }
+
+impl Infer for Fun {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type> {
+        use Fun::*;
+        let e = match receiver {
+            Type::Vec(e) => (**e).clone(),
+            Type::Map(m) => {
+                return match (self, arg) {
+                    (Compact, None) => Some(Type::Map(compact_fields(m, |t| t))),
+                    (FlattenNone, None) => Some(Type::Map(compact_fields(m, flatten_type))),
+                    _ => None,
+                }
+            }
+            _ => return None,
+        };
+
+        Some(match (self, arg) {
+            (Compact, None) => Type::Vec(Box::new(e.some().clone())),
+            (FlattenNone, None) => Type::Vec(Box::new(flatten_type(e))),
+            (First, None) | (Last, None) | (Get, Some(Type::Int)) | (NthBack, Some(Type::Int)) => {
+                Type::option(e)
+            }
+            (Sort, None) => receiver.clone(),
+            _ => return None,
+        })
+    }
+}
+
+/// Type of the elements of `flatten_none`
+fn flatten_type(t: Type) -> Type {
+    match t.some() {
+        Type::Vec(e) => Type::Vec(Box::new(flatten_type((**e).clone()))),
+        Type::Map(m) => Type::Map(compact_fields(m, flatten_type)),
+        t => t.clone(),
+    }
+}
+
+/// Fields of a map without its `None` entries, a nullable field may be
+/// removed and stays nullable
+fn compact_fields(m: &BTreeMap<String, Type>, f: impl Fn(Type) -> Type) -> BTreeMap<String, Type> {
+    m.iter()
+        .filter(|(_, t)| **t != Type::None)
+        .map(|(k, t)| match t {
+            Type::Option(t) => (k.clone(), Type::option(f((**t).clone()))),
+            t => (k.clone(), f(t.clone())),
+        })
+        .collect()
+}
@@ -1,15 +1,11 @@
-use std::{cmp::Ordering, convert::TryFrom};
+use alloc::vec::Vec;
+use core::{cmp::Ordering, convert::TryFrom};
 
-use syn::BinOp;
-
-use crate::{reflect::Eval, Value};
+use crate::{reflect::Eval, types::Type, Value};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
 pub(super) enum Operator {
-    ParenLeft = 1 << 1,
-    ParenRight = (1 << 1) + 1,
-
     Not = 1 << 2,
     Neg = (1 << 2) + 1,
 
@@ -40,40 +36,59 @@ impl Operator {
     }
 
     pub(super) fn gt_preference(self, o: Operator) -> bool {
-        match self.preference(o) {
-            Ordering::Greater => true,
-            _ => false,
-        }
+        matches!(self.preference(o), Ordering::Greater)
     }
 
     pub(super) fn eq_preference(self, o: Operator) -> bool {
-        match self.preference(o) {
-            Ordering::Equal => true,
-            _ => false,
+        matches!(self.preference(o), Ordering::Equal)
+    }
+
+    /// Evaluate with three-valued logic, `None` operands propagate
+    pub(super) fn eval_nullable(self, stack: &mut Vec<Value>) -> Result<(), ()> {
+        let len = stack.len();
+        if len < 2 {
+            return Err(());
         }
+
+        let e = match (self, &stack[len - 2], &stack[len - 1]) {
+            (And, Value::None, Value::Bool(false)) | (And, Value::Bool(false), Value::None) => {
+                false.into()
+            }
+            (Or, Value::None, Value::Bool(true)) | (Or, Value::Bool(true), Value::None) => {
+                true.into()
+            }
+            (And, Value::None, Value::Bool(_))
+            | (And, Value::Bool(_), Value::None)
+            | (Or, Value::None, Value::Bool(_))
+            | (Or, Value::Bool(_), Value::None) => Value::None,
+            (And, _, _) | (Or, _, _) => return self.eval(stack),
+            (_, Value::None, _) | (_, _, Value::None) => Value::None,
+            _ => return self.eval(stack),
+        };
+        stack.truncate(len - 2);
+        stack.push(e);
+
+        Ok(())
     }
 }
 
-impl TryFrom<syn::BinOp> for Operator {
-    type Error = ();
-
-    fn try_from(value: syn::BinOp) -> Result<Self, Self::Error> {
-        match value {
-            BinOp::Add(_) => Ok(Add),
-            BinOp::Sub(_) => Ok(Sub),
-            BinOp::Mul(_) => Ok(Mul),
-            BinOp::Div(_) => Ok(Div),
-            BinOp::Rem(_) => Ok(Rem),
-            BinOp::And(_) => Ok(And),
-            BinOp::Or(_) => Ok(Or),
-            BinOp::Eq(_) => Ok(Eq),
-            BinOp::Ne(_) => Ok(Ne),
-            BinOp::Lt(_) => Ok(Lt),
-            BinOp::Le(_) => Ok(Le),
-            BinOp::Gt(_) => Ok(Gt),
-            BinOp::Ge(_) => Ok(Ge),
-            _ => Err(()),
+impl Operator {
+    /// Result type, checked with the same rules as the evaluation
+    pub(crate) fn infer(self, t1: &Type, t2: &Type) -> Option<Type> {
+        let arithmetic = matches!(self, Add | Sub | Mul | Div | Rem | Neg);
+        if *t1 == Type::Any || *t2 == Type::Any {
+            return Some(if arithmetic { Type::Any } else { Type::Bool });
         }
+        if !check_op(self, &t1.sample()?, &t2.sample()?) {
+            return None;
+        }
+
+        Some(match (self, t1) {
+            (Mul, Type::Int) if *t2 == Type::Str => Type::Str,
+            _ if arithmetic && *t2 == Type::Float => Type::Float,
+            _ if arithmetic => t1.clone(),
+            _ => Type::Bool,
+        })
     }
 }
 
@@ -101,6 +116,23 @@ impl Eval for Operator {
             };
         }
 
+        // Integer overflow and division by zero fail instead of panic
+        if let (Value::Int(a), Value::Int(b)) = (&op1, &op2) {
+            let checked = match self {
+                Add => Some(a.checked_add(*b)),
+                Sub => Some(a.checked_sub(*b)),
+                Mul => Some(a.checked_mul(*b)),
+                Div => Some(a.checked_div(*b)),
+                Rem => Some(a.checked_rem(*b)),
+                Neg => Some(a.checked_neg()),
+                _ => None,
+            };
+            if let Some(n) = checked {
+                stack.push(n.ok_or(())?.into());
+                return Ok(());
+            }
+        }
+
         if check_op(self, &op1, &op2) {
             stack.push(match self {
                 Add => op1 + op2,
@@ -118,13 +150,23 @@ impl Eval for Operator {
                 Or => op1.or(&op2),
                 Not => op1.not(),
                 Neg => op1.neg(),
-                ParenLeft | ParenRight => unreachable!(),
             });
             Ok(())
         } else {
             Err(())
         }
     }
+
+    fn size_hint(self, stack: &[Value]) -> usize {
+        match (self, stack) {
+            (Add, [.., Value::Str(a), Value::Str(b)]) => a.len().saturating_add(b.len()),
+            (Mul, [.., Value::Str(s), Value::Int(n)])
+            | (Mul, [.., Value::Int(n), Value::Str(s)]) => {
+                s.len().saturating_mul(usize::try_from(*n).unwrap_or(0))
+            }
+            _ => 0,
+        }
+    }
 }
 
 #[inline]
@@ -145,19 +187,27 @@ fn check_op(op: Operator, op1: &Value, op2: &Value) -> bool {
             _ => false,
         },
         Value::Str(_) => match op {
-            Mul => match op2 {
-                Value::Int(_) => true,
-                _ => false,
-            },
-            Add | Eq | Ne => op1.is_same(op2),
+            Mul => matches!(op2, Value::Int(_)),
+            Add | Eq | Ne | Gt | Ge | Lt | Le => op1.is_same(op2),
+            _ => false,
+        },
+        Value::Range(_)
+        | Value::FloatRange(_)
+        | Value::CharRange(_)
+        | Value::RangeInclusive(_)
+        | Value::FloatRangeInclusive(_)
+        | Value::CharRangeInclusive(_)
+        | Value::Vec(_)
+        | Value::Tuple(_) => match op {
+            Eq | Ne | Gt | Ge | Lt | Le => op1.is_same(op2),
             _ => false,
         },
-        Value::Range(_) | Value::Vec(_) => match op {
+        Value::Map(_) => match op {
             Eq | Ne => op1.is_same(op2),
             _ => false,
         },
         Value::Bool(_) => match op {
-            Eq | Ne | And | Or => op1.is_same(op2),
+            Eq | Ne | Gt | Ge | Lt | Le | And | Or => op1.is_same(op2),
             Not => *op2 == Value::Bool(false),
             _ => false,
         },
@@ -0,0 +1,232 @@
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+use crate::{
+    ast::{fold_expr, Arm, BinOp, Expr, Fold, Lit, Pat, UnOp},
+    deps::{binds, Dependencies},
+    reflect::{eval_with, Options},
+    Value,
+};
+
+/// Longest `Str` or `Vec` folded into a literal
+const MAX_LEN: usize = 256;
+
+/// Constant folding and simplification
+///
+/// Sub-expressions whose names are all in the context are folded, in partial
+/// evaluation the other names are unknown, assumed to be valid operands,
+/// and the context expressions using them are inlined
+pub(crate) struct Optimizer<'a> {
+    ctx: &'a BTreeMap<String, Expr>,
+    opts: Options,
+    partial: bool,
+    /// Local bindings
+    scopes: Vec<Vec<String>>,
+    /// Context variables being inlined
+    path: Vec<String>,
+}
+
+impl<'a> Optimizer<'a> {
+    pub(crate) fn new(ctx: &'a BTreeMap<String, Expr>, opts: Options, partial: bool) -> Self {
+        Optimizer {
+            ctx,
+            opts,
+            partial,
+            scopes: vec![],
+            path: vec![],
+        }
+    }
+
+    fn bound(&self, name: &str) -> bool {
+        self.scopes.iter().any(|s| s.iter().any(|b| b == name))
+    }
+
+    /// Literal of a sub-expression depending only on the context
+    fn constant(&self, e: &Expr) -> Option<Expr> {
+        if let Expr::Lit(_) = e {
+            return None;
+        }
+        let deps = Dependencies::new(self.ctx, e);
+        if deps
+            .variables
+            .iter()
+            .any(|v| self.bound(v) || !self.ctx.contains_key(v))
+        {
+            return None;
+        }
+
+        eval_with(self.ctx, self.opts, e)
+            .ok()
+            .and_then(|v| literal(&v, self.partial))
+    }
+
+    /// Context expression of a name using unknown names
+    fn inline(&mut self, e: &Expr) -> Option<Expr> {
+        let name = match e {
+            Expr::Var(name) => name,
+            _ => return None,
+        };
+        let src = self.ctx.get(name)?;
+        if self.bound(name)
+            || self.path.contains(name)
+            || Dependencies::new(self.ctx, src)
+                .variables
+                .iter()
+                .any(|v| self.bound(v))
+        {
+            return None;
+        }
+
+        self.path.push(name.clone());
+        let scopes = core::mem::take(&mut self.scopes);
+        let e = self.fold_expr(src.clone());
+        self.scopes = scopes;
+        self.path.pop();
+
+        Some(e)
+    }
+
+    /// Fold in a new scope with the bindings of some patterns
+    fn scoped<'p, T>(
+        &mut self,
+        pats: impl IntoIterator<Item = &'p Pat>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        self.scopes.push(pats.into_iter().flat_map(binds).collect());
+        let t = f(self);
+        self.scopes.pop();
+
+        t
+    }
+}
+
+impl<'a> Fold for Optimizer<'a> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        if let Some(lit) = self.constant(&e) {
+            return lit;
+        }
+        if self.partial {
+            if let Some(inlined) = self.inline(&e) {
+                return inlined;
+            }
+        }
+        let e = match e {
+            Expr::Closure(pats, body) => {
+                let body = self.scoped(&pats, |s| s.fold_expr(*body));
+                Expr::Closure(pats, Box::new(body))
+            }
+            Expr::Block(lets, e) => self.scoped(None, |s| {
+                let lets = lets
+                    .into_iter()
+                    .map(|(pat, init)| {
+                        let init = s.fold_expr(init);
+                        if let Some(scope) = s.scopes.last_mut() {
+                            scope.extend(binds(&pat));
+                        }
+                        (pat, init)
+                    })
+                    .collect();
+                Expr::Block(lets, Box::new(s.fold_expr(*e)))
+            }),
+            Expr::Match(e, arms) => {
+                let e = self.fold_expr(*e);
+                let arms = arms
+                    .into_iter()
+                    .map(|Arm { pat, guard, body }| {
+                        let (guard, body) = self.scoped(Some(&pat), |s| {
+                            (guard.map(|g| s.fold_expr(g)), s.fold_expr(body))
+                        });
+                        Arm { pat, guard, body }
+                    })
+                    .collect();
+                Expr::Match(Box::new(e), arms)
+            }
+            e => fold_expr(self, e),
+        };
+
+        simplify(e, self.partial)
+    }
+
+    // Patterns are matched by their syntax
+    fn fold_pat(&mut self, p: Pat) -> Pat {
+        p
+    }
+}
+
+/// Expression of a value, with compound values only when `compound`
+fn literal(v: &Value, compound: bool) -> Option<Expr> {
+    if foldable(v, compound) {
+        Some(v.clone().into())
+    } else {
+        None
+    }
+}
+
+fn foldable(v: &Value, compound: bool) -> bool {
+    let list = |v: &[Value]| v.len() <= MAX_LEN && v.iter().all(|v| foldable(v, compound));
+
+    match v {
+        Value::Bool(_) => true,
+        Value::Int(n) => *n != i64::MIN,
+        Value::Float(n) => n.is_finite(),
+        Value::Str(s) if s.len() <= MAX_LEN => true,
+        _ if !compound => false,
+        Value::None => true,
+        Value::Vec(v) | Value::Tuple(v) => list(v),
+        Value::Map(m) => m.len() <= MAX_LEN && m.values().all(|v| foldable(v, compound)),
+        Value::Range(r) => r.start != i64::MIN && r.end != i64::MIN,
+        Value::FloatRange(r) => r.start.is_finite() && r.end.is_finite(),
+        Value::CharRange(_) => true,
+        Value::RangeInclusive(r) => *r.start() != i64::MIN && *r.end() != i64::MIN,
+        Value::FloatRangeInclusive(r) => r.start().is_finite() && r.end().is_finite(),
+        Value::CharRangeInclusive(_) => true,
+        Value::Str(_) => false,
+    }
+}
+
+/// Remove double negations and boolean identities of a `Bool` operand,
+/// in partial evaluation `&&` and `||` short-circuit
+fn simplify(e: Expr, partial: bool) -> Expr {
+    match e {
+        Expr::Unary(UnOp::Not, e) => match *e {
+            Expr::Unary(UnOp::Not, e) if is_bool(&e) => *e,
+            e => !e,
+        },
+        Expr::Binary(left, op @ BinOp::And, right) | Expr::Binary(left, op @ BinOp::Or, right) => {
+            let identity = op == BinOp::And;
+            match (bool_lit(&left), bool_lit(&right)) {
+                (Some(b), _) | (_, Some(b)) if partial && b != identity => b.into(),
+                (Some(b), _) if b == identity && (partial || is_bool(&right)) => *right,
+                (_, Some(b)) if b == identity && (partial || is_bool(&left)) => *left,
+                _ => Expr::Binary(left, op, right),
+            }
+        }
+        e => e,
+    }
+}
+
+fn bool_lit(e: &Expr) -> Option<bool> {
+    match e {
+        Expr::Lit(Lit::Bool(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+/// Expression giving a `Bool`, `None` in three-valued logic, or failing
+fn is_bool(e: &Expr) -> bool {
+    match e {
+        Expr::Lit(Lit::Bool(_)) | Expr::Unary(UnOp::Not, _) => true,
+        Expr::Binary(_, op, _) => matches!(
+            op,
+            BinOp::And
+                | BinOp::Or
+                | BinOp::Eq
+                | BinOp::Ne
+                | BinOp::Lt
+                | BinOp::Le
+                | BinOp::Gt
+                | BinOp::Ge
+        ),
+        _ => false,
+    }
+}
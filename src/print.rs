@@ -0,0 +1,41 @@
+use crate::{ast::Expr, operator::Operator, Error};
+use alloc::string::{String, ToString};
+
+/// Canonical form of an expression source, see [`CompiledExpr`](crate::CompiledExpr)
+/// to print a compiled one
+///
+/// Binary operators are grouped by the evaluation precedence and printed with
+/// the minimal parentheses, except around chained comparisons and mixed `&&`
+/// and `||`, which have the same precedence in the evaluation but not in Rust.
+/// Equivalent sources share the same form, printing it again gives the same
+///
+/// ```rust
+///# fn main() -> Result<(), v_eval::Error> {
+/// let src = v_eval::format("((a+1)) * 0x10 >= b.len( )&&!(c)||d")?;
+///
+/// assert_eq!(src, "((a + 1) * 16 >= b.len() && !c) || d");
+/// assert_eq!(v_eval::format(&src)?, src);
+/// assert_eq!(v_eval::format("a || (b && c)")?, "a || (b && c)");
+/// assert_eq!(v_eval::format("(a || b) && c")?, "(a || b) && c");
+/// assert_eq!(v_eval::format("1_000.50f64 + -(-x)")?, "1000.5 + -(-x)");
+///# Ok(())
+///# }
+/// ```
+pub fn format(src: &str) -> Result<String, Error> {
+    Expr::parse(src).map(|e| e.to_string())
+}
+
+/// Child operator of a binary operator printed in parentheses
+///
+/// Same precedence chains are grouped when Rust would read them otherwise,
+/// as it does not chain comparisons and gives `&&` a higher precedence than `||`
+pub(crate) fn grouped(op: Operator, child: Operator, right: bool) -> bool {
+    use Operator::*;
+    let ambiguous = match op {
+        Eq | Ne | Gt | Lt | Ge | Le => true,
+        And | Or => op != child,
+        _ => false,
+    };
+
+    op.gt_preference(child) || op.eq_preference(child) && (right || ambiguous)
+}
@@ -0,0 +1,242 @@
+//! Python bindings with PyO3, with the `python` feature
+//!
+//! ```python
+//! from v_eval import Eval, EvalError
+//!
+//! e = Eval({"age": 20, "tags": ["a", "b"]})
+//! e.insert("adult", "age >= 18")
+//! e.eval("adult && tags.len() == 2")  # True
+//! e.eval("1 +")  # raises EvalError
+//! ```
+//!
+//! Values are converted from and to Python as:
+//! - `None`, `bool`, `int`, `float` and `str`: the same
+//! - `Vec`: `list`
+//! - `Tuple`: `tuple`
+//! - `Range`: `range`, with step 1, an inclusive range only to Python
+//! - `FloatRange` and `CharRange`: `(start, end)` tuples, only to Python,
+//!   the end of an inclusive range is in the range
+//! - `Map`: `dict`, with `str` keys
+//!
+//! The module is built with `maturin develop`, see `pyproject.toml`
+use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::ToString, vec::Vec};
+use core::mem;
+
+use pyo3::{
+    create_exception,
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyRange, PyRangeMethods, PyString, PyTuple},
+    IntoPyObjectExt, PyTypeInfo,
+};
+
+use crate::{Error, Eval, Value};
+
+create_exception!(
+    v_eval,
+    EvalError,
+    PyValueError,
+    "Expression can't be parsed or evaluated"
+);
+
+fn error(e: Error) -> PyErr {
+    EvalError::new_err(e.to_string())
+}
+
+/// Evaluator with context, `Eval` in Python
+#[pyclass(name = "Eval", module = "v_eval")]
+#[derive(Default)]
+pub struct PyEval(Eval);
+
+#[pymethods]
+impl PyEval {
+    /// New evaluator with a `dict` of values as context
+    #[new]
+    #[pyo3(signature = (context = None))]
+    fn new(context: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut e = PyEval::default();
+        for (name, value) in context.into_iter().flatten() {
+            e.set(name.extract()?, &value)?;
+        }
+
+        Ok(e)
+    }
+
+    /// Parse and insert in context name - expression
+    fn insert(&mut self, name: &str, src: &str) -> PyResult<()> {
+        let e = self.0.parse(src).map_err(error)?;
+        self.0 .0.insert(name.to_owned(), e);
+
+        Ok(())
+    }
+
+    /// Insert in context name - value
+    fn set(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.0 .0.insert(name.to_owned(), from_py(value)?.into());
+
+        Ok(())
+    }
+
+    /// Remove name in context
+    fn remove(&mut self, name: &str) {
+        self.0 .0.remove(name);
+    }
+
+    /// Negative indexes count from the end, see [`Eval::negative_index`]
+    fn negative_index(&mut self, on: bool) {
+        self.0 = mem::take(&mut self.0).negative_index(on);
+    }
+
+    /// Evaluate expression with current context
+    fn eval<'py>(&self, py: Python<'py>, src: &str) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.0.try_eval(src).map_err(error)?)
+    }
+}
+
+/// Evaluate expression without context
+#[pyfunction]
+fn eval<'py>(py: Python<'py>, src: &str) -> PyResult<Bound<'py, PyAny>> {
+    PyEval::default().eval(py, src)
+}
+
+/// Python value of a value
+pub fn to_py<'py>(py: Python<'py>, v: &Value) -> PyResult<Bound<'py, PyAny>> {
+    let list = |v: &[Value]| v.iter().map(|v| to_py(py, v)).collect::<PyResult<Vec<_>>>();
+    match v {
+        Value::None => Ok(py.None().into_bound(py)),
+        Value::Bool(b) => b.into_bound_py_any(py),
+        Value::Int(n) => n.into_bound_py_any(py),
+        Value::Float(n) => n.into_bound_py_any(py),
+        Value::Str(s) => s.into_bound_py_any(py),
+        Value::Vec(v) => PyList::new(py, list(v)?)?.into_bound_py_any(py),
+        Value::Tuple(v) => PyTuple::new(py, list(v)?)?.into_bound_py_any(py),
+        Value::Map(m) => {
+            let d = PyDict::new(py);
+            for (k, v) in m {
+                d.set_item(k, to_py(py, v)?)?;
+            }
+            d.into_bound_py_any(py)
+        }
+        Value::Range(r) => PyRange::type_object(py).call1((r.start, r.end)),
+        Value::FloatRange(r) => (r.start, r.end).into_bound_py_any(py),
+        Value::CharRange(r) => (r.start, r.end).into_bound_py_any(py),
+        Value::RangeInclusive(r) => {
+            PyRange::type_object(py).call1((r.start(), i128::from(*r.end()) + 1))
+        }
+        Value::FloatRangeInclusive(r) => (r.start(), r.end()).into_bound_py_any(py),
+        Value::CharRangeInclusive(r) => (r.start(), r.end()).into_bound_py_any(py),
+    }
+}
+
+/// Value of a Python value, `TypeError` without equivalent
+pub fn from_py(o: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let list = |o: Bound<'_, PyAny>| {
+        o.try_iter()?
+            .map(|o| from_py(&o?))
+            .collect::<PyResult<Vec<_>>>()
+    };
+    Ok(if o.is_none() {
+        Value::None
+    } else if let Ok(b) = o.cast::<PyBool>() {
+        b.is_true().into()
+    } else if o.is_instance_of::<PyInt>() {
+        Value::Int(o.extract()?)
+    } else if let Ok(n) = o.cast::<PyFloat>() {
+        n.value().into()
+    } else if let Ok(s) = o.cast::<PyString>() {
+        s.to_str()?.into()
+    } else if o.is_instance_of::<PyList>() {
+        Value::Vec(list(o.clone())?)
+    } else if o.is_instance_of::<PyTuple>() {
+        Value::Tuple(list(o.clone())?)
+    } else if let Ok(r) = o.cast::<PyRange>() {
+        if r.step()? != 1 {
+            return Err(PyValueError::new_err("range step is not 1"));
+        }
+        Value::Range(r.start()? as i64..r.stop()? as i64)
+    } else if let Ok(d) = o.cast::<PyDict>() {
+        Value::Map(
+            d.iter()
+                .map(|(k, v)| {
+                    let k = k
+                        .cast::<PyString>()
+                        .map_err(|_| PyTypeError::new_err("`dict` key is not `str`"))?;
+                    Ok((k.to_str()?.to_owned(), from_py(&v)?))
+                })
+                .collect::<PyResult<BTreeMap<_, _>>>()?,
+        )
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "`{}` has no equivalent value",
+            o.get_type().name()?
+        )));
+    })
+}
+
+#[pymodule]
+fn v_eval(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEval>()?;
+    m.add("EvalError", m.py().get_type::<EvalError>())?;
+    m.add_function(wrap_pyfunction!(eval, m)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_python() {
+        Python::initialize();
+        Python::attach(|py| -> PyResult<()> {
+            let context = PyDict::new(py);
+            context.set_item("age", 20)?;
+            context.set_item("tags", vec!["a", "b"])?;
+            let mut e = PyEval::new(Some(&context))?;
+            e.insert("adult", "age >= 18")?;
+
+            let eval = |e: &PyEval, src| e.eval(py, src).and_then(|v| from_py(&v));
+            assert_eq!(eval(&e, "adult && tags.len() == 2")?, Value::Bool(true));
+            assert_eq!(
+                eval(&e, "(tags[0], 0..2, 1.5)")?,
+                Value::Tuple(vec!["a".into(), (0..2).into(), 1.5.into()])
+            );
+            assert!(e
+                .eval(py, "1 +")
+                .unwrap_err()
+                .is_instance_of::<EvalError>(py));
+            assert!(e.insert("b", "1 +").is_err());
+
+            e.set("x", &py.None().into_bound(py))?;
+            assert_eq!(eval(&e, "x.is_none()")?, Value::Bool(true));
+            e.remove("x");
+            e.negative_index(true);
+            assert_eq!(eval(&e, "tags[-1]")?, Value::Str("b".into()));
+
+            let v = e.eval(py, "[1, 2]")?;
+            assert_eq!(v.str()?.to_str()?, "[1, 2]");
+            let v = e.eval(py, r#"{"t": tags, "n": 1}"#)?;
+            assert_eq!(v.str()?.to_str()?, "{'n': 1, 't': ['a', 'b']}");
+            let mut m = BTreeMap::new();
+            m.insert("n".to_owned(), Value::Int(1));
+            m.insert("t".to_owned(), Value::Vec(vec!["a".into(), "b".into()]));
+            assert_eq!(from_py(&v)?, Value::Map(m));
+
+            let user = PyDict::new(py);
+            user.set_item("name", "Ana")?;
+            e.set("user", &user.into_any())?;
+            assert_eq!(eval(&e, "user.name")?, Value::Str("Ana".into()));
+            assert_eq!(
+                from_py(&PyDict::new(py).into_any())?,
+                Value::Map(BTreeMap::new())
+            );
+            let d = PyDict::new(py);
+            d.set_item(1, 2)?;
+            assert!(from_py(&d.into_any())
+                .unwrap_err()
+                .is_instance_of::<PyTypeError>(py));
+
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -1,18 +1,246 @@
-use std::{collections::BTreeMap, convert::TryFrom, ops, option::Option};
-
-use syn::{
-    visit::Visit, BinOp, Expr, ExprArray, ExprBinary, ExprField, ExprIndex, ExprMethodCall,
-    ExprParen, ExprPath, ExprRange, ExprReference, ExprUnary, Lit,
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use alloc::{collections::BTreeMap, rc::Rc};
+use core::{
+    cell::{Cell, RefCell},
+    convert::{TryFrom, TryInto},
+    ops::Bound,
+    option::Option,
 };
+#[cfg(feature = "std")]
+use std::time::Instant;
 
 use crate::{
-    method::{HasArg, Method},
+    ast::{Arm, Expr, Lit, Member, Pat, UnOp, Visit},
+    limits::{EvalLimits, Limit},
+    method::{option_t::Lazy, HasArg, Method},
     operator::Operator,
-    Value,
+    Error, Value,
 };
 
-pub fn eval(ctx: &BTreeMap<String, syn::Expr>, expr: &Expr) -> Option<Value> {
-    Reflect::new(ctx).eval(expr).and_then(|v| v.unwrap().ok())
+pub fn eval(ctx: &BTreeMap<String, Expr>, expr: &Expr) -> Option<Value> {
+    eval_with(ctx, Options::default(), expr).ok()
+}
+
+pub(crate) fn eval_with<'a>(
+    ctx: impl Into<Ctx<'a>>,
+    opts: Options,
+    expr: &Expr,
+) -> Result<Value, Error> {
+    let state = State::new(opts.limits);
+    match Reflect::new(ctx.into(), opts, &state).eval(expr) {
+        Some(v) => opts.unwrap(v).ok_or(Error::None),
+        None => Err(state.error.into_inner().unwrap_or(Error::Eval)),
+    }
+}
+
+/// Context names of an evaluation, looked up in the scope, values and then
+/// expressions, and then in the base
+#[derive(Clone, Copy)]
+pub(crate) struct Ctx<'a> {
+    base: &'a BTreeMap<String, Expr>,
+    values: Option<&'a BTreeMap<String, Value>>,
+    exprs: Option<&'a BTreeMap<String, Expr>>,
+}
+
+impl<'a> Ctx<'a> {
+    pub(crate) fn scoped(
+        base: &'a BTreeMap<String, Expr>,
+        values: &'a BTreeMap<String, Value>,
+    ) -> Self {
+        Self {
+            base,
+            values: Some(values),
+            exprs: None,
+        }
+    }
+
+    pub(crate) fn with_exprs(self, exprs: &'a BTreeMap<String, Expr>) -> Self {
+        Self {
+            exprs: Some(exprs),
+            ..self
+        }
+    }
+
+    fn value(self, name: &str) -> Option<&'a Value> {
+        self.values.and_then(|values| values.get(name))
+    }
+
+    fn get(self, name: &str) -> Option<&'a Expr> {
+        self.exprs
+            .and_then(|exprs| exprs.get(name))
+            .or_else(|| self.base.get(name))
+    }
+}
+
+impl<'a> From<&'a BTreeMap<String, Expr>> for Ctx<'a> {
+    fn from(base: &'a BTreeMap<String, Expr>) -> Self {
+        Self {
+            base,
+            values: None,
+            exprs: None,
+        }
+    }
+}
+
+/// State shared by a whole evaluation
+#[derive(Default)]
+struct State {
+    /// Values of the context variables already evaluated
+    memo: RefCell<BTreeMap<String, Value>>,
+    /// Context variables being evaluated
+    path: RefCell<Vec<String>>,
+    /// First error with more detail than `Error::Eval`
+    error: RefCell<Option<Error>>,
+    /// Current nesting of sub expressions
+    depth: Cell<usize>,
+    /// Evaluation steps done
+    steps: Cell<usize>,
+    /// Wall-clock deadline
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+    limits: EvalLimits,
+}
+
+impl State {
+    fn new(limits: EvalLimits) -> State {
+        State {
+            #[cfg(feature = "std")]
+            deadline: limits.timeout.and_then(|t| Instant::now().checked_add(t)),
+            limits,
+            ..State::default()
+        }
+    }
+
+    /// Record an error
+    fn fail(&self, e: Error) {
+        let mut error = self.error.borrow_mut();
+        if error.is_none() {
+            *error = Some(e);
+        }
+    }
+
+    /// Count one evaluation step
+    fn step(&self) -> Result<(), Limit> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if self.limits.steps.is_some_and(|max| steps > max) {
+            return Err(Limit::Steps);
+        }
+        #[cfg(feature = "std")]
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() > deadline)
+        {
+            return Err(Limit::Time);
+        }
+
+        Ok(())
+    }
+
+    /// Enter a sub expression
+    fn enter(&self) -> Result<(), Limit> {
+        let depth = self.depth.get() + 1;
+        self.depth.set(depth);
+        if self.limits.depth.is_some_and(|max| depth > max) {
+            return Err(Limit::Depth);
+        }
+        self.step()
+    }
+
+    fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Check the size of a value to be produced
+    fn size(&self, size: usize) -> Result<(), Limit> {
+        if self.limits.size.is_some_and(|max| size > max) {
+            Err(Limit::Size)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Evaluate an operation checking the size of its result
+    fn checked(
+        &self,
+        hint: usize,
+        stack: &mut Vec<Value>,
+        f: impl FnOnce(&mut Vec<Value>) -> Result<(), ()>,
+    ) -> Result<(), ()> {
+        self.check(self.step().and_then(|_| self.size(hint)))?;
+        f(stack)?;
+        self.check(self.size(stack.last().map_or(0, size)))
+    }
+
+    /// Record an exceeded limit
+    fn check(&self, limit: Result<(), Limit>) -> Result<(), ()> {
+        limit.map_err(|l| self.fail(Error::Limit(l)))
+    }
+}
+
+/// Length of a `Str`, in bytes, `Vec` or `Tuple`
+fn size(v: &Value) -> usize {
+    match v {
+        Value::Str(s) => s.len(),
+        Value::Vec(v) | Value::Tuple(v) => v.len(),
+        _ => 0,
+    }
+}
+
+/// Local bindings introduced by `let` and `match`
+type Locals = Rc<BTreeMap<String, Value>>;
+
+/// Evaluation options
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Options {
+    /// Negative indexes count from the end
+    pub(crate) negative_index: bool,
+    /// `None` semantics
+    pub(crate) none: NoneMode,
+    /// `Vec`, `Tuple` and `Map` results may hold `None` elements
+    pub(crate) nullable_elements: bool,
+    /// Resource budgets
+    pub(crate) limits: EvalLimits,
+}
+
+impl Options {
+    /// Result of a complete expression
+    fn unwrap(self, v: Value) -> Option<Value> {
+        if self.nullable_elements || self.none == NoneMode::Propagate || v.is_complete() {
+            v.unwrap().ok()
+        } else {
+            None
+        }
+    }
+}
+
+/// How `None` values and undefined identifiers are evaluated
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NoneMode {
+    /// Undefined identifiers are `None`, operators fail on `None` operands
+    /// and a `Vec` holding `None` is not a result
+    #[default]
+    Lenient,
+    /// As `Lenient` but an undefined identifier fails the evaluation
+    Strict,
+    /// SQL-like three-valued logic, undefined identifiers are `None`, which
+    /// propagates through arithmetic and comparison operators, `&&` and `||`
+    /// only give `None` when the other operand does not decide the result
+    /// and a `Vec` may hold `None` values
+    Propagate,
+}
+
+/// Resolved index
+enum Index {
+    At(usize),
+    /// Start and end, the end may be inclusive
+    Range(usize, Bound<usize>),
 }
 
 #[derive(Debug)]
@@ -23,20 +251,14 @@ enum Output {
 }
 
 struct Reflect<'a> {
-    ctx: &'a BTreeMap<String, syn::Expr>,
-    operators: Vec<Operator>,
+    ctx: Ctx<'a>,
+    opts: Options,
+    state: &'a State,
+    locals: Locals,
     output: Vec<Output>,
     on_err: bool,
 }
 
-macro_rules! err_some {
-    ($_self:ident, $some:expr) => {
-        if $some.is_some() {
-            return $_self.on_err = true;
-        }
-    };
-}
-
 macro_rules! on_err {
     ($_self:ident) => {
         if $_self.on_err {
@@ -46,98 +268,373 @@ macro_rules! on_err {
 }
 
 impl<'a> Reflect<'a> {
-    fn new(ctx: &BTreeMap<String, syn::Expr>) -> Reflect {
+    fn new(ctx: Ctx<'a>, opts: Options, state: &'a State) -> Reflect<'a> {
         Reflect {
             ctx,
-            operators: vec![],
+            opts,
+            state,
+            locals: Locals::default(),
             output: vec![],
             on_err: false,
         }
     }
 
-    fn eval(mut self, e: &'a Expr) -> Option<Value> {
+    fn eval(mut self, e: &Expr) -> Option<Value> {
         self.visit_expr(e);
 
         if self.on_err {
             None
         } else {
-            self.output.extend(
-                self.operators
-                    .drain(..)
-                    .rev()
-                    .map(Output::Op)
-                    .collect::<Vec<Output>>(),
-            );
-            evaluate(self.output).ok()
+            evaluate_with(self.output, self.opts.none, self.state).ok()
         }
     }
 
-    fn push_op(&mut self, op: Operator) {
-        on_err!(self);
-        if Operator::ParenLeft.eq_preference(op) {
-            if op == Operator::ParenRight {
-                loop {
-                    if let Some(last) = self.operators.pop() {
-                        if last == Operator::ParenLeft {
-                            break;
-                        }
-                        self.output.push(Output::Op(last));
-                    } else {
-                        break self.on_err = true;
+    /// Child evaluator with the same context and options
+    fn scoped<'b>(&self, locals: Locals) -> Reflect<'b>
+    where
+        'a: 'b,
+    {
+        Reflect {
+            ctx: self.ctx,
+            opts: self.opts,
+            state: self.state,
+            locals,
+            output: vec![],
+            on_err: false,
+        }
+    }
+
+    /// Child evaluator with the same local bindings
+    fn sub<'b>(&self) -> Reflect<'b>
+    where
+        'a: 'b,
+    {
+        self.scoped(self.locals.clone())
+    }
+
+    /// Record an error and fail
+    fn fail(&mut self, e: Error) {
+        self.state.fail(e);
+        self.on_err = true;
+    }
+
+    /// Evaluate a context variable once per evaluation
+    fn var(&mut self, name: &str, src: &'a Expr) {
+        if let Some(v) = self.state.memo.borrow().get(name) {
+            return self.output.push(Output::V(v.clone()));
+        }
+
+        let cycle = {
+            let path = self.state.path.borrow();
+            path.iter().position(|p| p == name).map(|i| {
+                let mut cycle = path[i..].to_vec();
+                cycle.push(name.to_owned());
+                cycle
+            })
+        };
+        if let Some(cycle) = cycle {
+            return self.fail(Error::Cycle(cycle));
+        }
+
+        self.state.path.borrow_mut().push(name.to_owned());
+        let value = self.scoped(Locals::default()).eval(src);
+        self.state.path.borrow_mut().pop();
+
+        match value {
+            Some(v) => {
+                self.state
+                    .memo
+                    .borrow_mut()
+                    .insert(name.to_owned(), v.clone());
+                self.output.push(Output::V(v));
+            }
+            None => self.on_err = true,
+        }
+    }
+
+    /// Evaluate a sub expression and unwrap it
+    fn value(&self, e: &Expr) -> Option<Value> {
+        self.sub().eval(e).and_then(|v| self.opts.unwrap(v))
+    }
+
+    /// Match value against pattern, collecting its bindings
+    fn pattern(
+        &self,
+        pat: &Pat,
+        value: &Value,
+        bindings: &mut BTreeMap<String, Value>,
+    ) -> Option<bool> {
+        match pat {
+            Pat::Wild => Some(true),
+            Pat::Ident(name) => {
+                bindings.insert(name.clone(), value.clone());
+                Some(true)
+            }
+            Pat::Lit(l) => Some(lit(l) == *value),
+            Pat::Range { lo, hi, closed } => {
+                let range = range(lit(lo), lit(hi), *closed)?;
+                Some(in_range(&range, value))
+            }
+            Pat::Tuple(pats) => match value {
+                Value::Tuple(v) => self.patterns(pats, v, Value::Tuple, bindings),
+                _ => Some(false),
+            },
+            Pat::Slice(pats) => match value {
+                Value::Vec(v) => self.patterns(pats, v, Value::Vec, bindings),
+                _ => Some(false),
+            },
+            Pat::Or(cases) => {
+                for case in cases {
+                    let mut case_bindings = BTreeMap::new();
+                    if self.pattern(case, value, &mut case_bindings)? {
+                        bindings.extend(case_bindings);
+                        return Some(true);
                     }
                 }
-            } else {
-                self.operators.push(op);
+                Some(false)
             }
-        } else {
-            while let Some(last) = self.operators.last() {
-                if op.gt_preference(*last) || *last == Operator::ParenLeft {
-                    break;
+            Pat::Bind(name, pat) => {
+                if !self.pattern(pat, value, bindings)? {
+                    return Some(false);
+                }
+                bindings.insert(name.clone(), value.clone());
+                Some(true)
+            }
+            // Only in a tuple or a slice
+            Pat::Rest => None,
+        }
+    }
+
+    /// Match a sequence of values, a rest pattern takes the values between
+    /// the patterns before and after it, bound as a sequence by `seq`
+    fn patterns(
+        &self,
+        pats: &[Pat],
+        values: &[Value],
+        seq: fn(Vec<Value>) -> Value,
+        bindings: &mut BTreeMap<String, Value>,
+    ) -> Option<bool> {
+        let (before, rest, after) = match pats.iter().position(Pat::is_rest) {
+            Some(r) if pats[r + 1..].iter().any(Pat::is_rest) => return None,
+            Some(r) => (&pats[..r], Some(&pats[r]), &pats[r + 1..]),
+            None => (pats, None, &[][..]),
+        };
+        let len = before.len() + after.len();
+        if values.len() < len || (rest.is_none() && values.len() != len) {
+            return Some(false);
+        }
+
+        let end = values.len() - after.len();
+        let pairs = before
+            .iter()
+            .zip(values)
+            .chain(after.iter().zip(&values[end..]));
+        for (pat, value) in pairs {
+            if !self.pattern(pat, value, bindings)? {
+                return Some(false);
+            }
+        }
+        if let Some(Pat::Bind(name, _)) = rest {
+            bindings.insert(name.clone(), seq(values[before.len()..end].to_vec()));
+        }
+        Some(true)
+    }
+
+    /// Evaluate a method taking closures
+    fn lazy(&self, method: Lazy, receiver: Value, args: &[Expr]) -> Option<Value> {
+        match (method, args.len()) {
+            (Lazy::UnwrapOrElse, 1) => match receiver {
+                Value::None => self.closure(&args[0], &[]),
+                v => Some(v),
+            },
+            (Lazy::MapOr, 2) => match receiver {
+                Value::None => self.sub().eval(&args[0]),
+                v => self.closure(&args[1], &[v]),
+            },
+            (Lazy::Filter, 1) => match receiver {
+                Value::None => Some(Value::None),
+                v => match self.closure(&args[0], core::slice::from_ref(&v))? {
+                    Value::Bool(true) => Some(v),
+                    Value::Bool(false) => Some(Value::None),
+                    _ => None,
+                },
+            },
+            _ => None,
+        }
+    }
+
+    /// Call a closure expression with its arguments bound by pattern
+    fn closure(&self, e: &Expr, args: &[Value]) -> Option<Value> {
+        let (pats, body) = match e {
+            Expr::Closure(pats, body) => (pats, body),
+            _ => return None,
+        };
+        let mut bindings = BTreeMap::new();
+        if !self.patterns(pats, args, Value::Tuple, &mut bindings)? {
+            return None;
+        }
+        let mut locals = self.locals.clone();
+        Rc::make_mut(&mut locals).extend(bindings);
+
+        self.scoped(locals).eval(body)
+    }
+
+    fn index(&self, value: Value, index: &Expr) -> Option<Value> {
+        match value {
+            Value::Vec(a) => match self.resolve_index(index, a.len())? {
+                Index::At(i) => a.get(i).cloned(),
+                Index::Range(start, end) => a
+                    .get((Bound::Included(start), end))
+                    .map(|x| Value::Vec(x.to_vec())),
+            },
+            Value::Str(a) => match self.resolve_index(index, a.len())? {
+                Index::Range(start, end) => a
+                    .get((Bound::Included(start), end))
+                    .map(|x| Value::Str(x.to_owned())),
+                _ => None,
+            },
+            Value::Range(a) => {
+                let len = if a.start < a.end {
+                    TryFrom::try_from(i128::from(a.end) - i128::from(a.start)).ok()?
                 } else {
-                    self.output
-                        .push(Output::Op(self.operators.pop().expect("Some Operator")));
+                    0
+                };
+                self.index_range(a.start, len, index)
+            }
+            Value::Map(mut a) => match self.value(index)? {
+                Value::Str(k) => match a.remove(&k) {
+                    Some(v) => Some(v),
+                    None if self.opts.none == NoneMode::Strict => None,
+                    None => Some(Value::None),
+                },
+                _ => None,
+            },
+            Value::RangeInclusive(a) => {
+                let len = if a.start() <= a.end() {
+                    TryFrom::try_from(i128::from(*a.end()) - i128::from(*a.start()) + 1).ok()?
+                } else {
+                    0
+                };
+                self.index_range(*a.start(), len, index)
+            }
+            _ => None,
+        }
+    }
+
+    /// Index in the `len` integers from `start`
+    fn index_range(&self, start: i64, len: usize, index: &Expr) -> Option<Value> {
+        let offset = |i: i128| i64::try_from(i128::from(start) + i).ok();
+        let len = len as i128;
+        match self.resolve_index(index, len as usize)? {
+            Index::At(i) if (i as i128) < len => offset(i as i128).map(Value::Int),
+            Index::Range(from, to) => {
+                let from = from as i128;
+                let to = match to {
+                    Bound::Included(to) => to as i128 + 1,
+                    Bound::Excluded(to) => to as i128,
+                    Bound::Unbounded => len,
+                };
+                if to < from || len < to {
+                    return None;
                 }
+                // A range up to `i64::MAX` has no half-open end
+                Some(match offset(to) {
+                    Some(to) => Value::Range(offset(from)?..to),
+                    None => Value::RangeInclusive(offset(from)?..=offset(to - 1)?),
+                })
             }
-            self.operators.push(op);
+            _ => None,
         }
     }
-}
 
-impl<'a> Visit<'a> for Reflect<'a> {
-    fn visit_attribute(&mut self, _a: &'a syn::Attribute) {
-        self.on_err = true;
+    fn resolve_index(&self, index: &Expr, len: usize) -> Option<Index> {
+        let int = |e: &Expr| match self.value(e) {
+            Some(Value::Int(i)) => self.offset(i, len),
+            _ => None,
+        };
+
+        if let Expr::Range { from, to, closed } = index {
+            let start = match from {
+                Some(from) => int(from)?,
+                None => 0,
+            };
+            let end = match to {
+                Some(to) if *closed => Bound::Included(int(to)?),
+                Some(to) => Bound::Excluded(int(to)?),
+                None => Bound::Unbounded,
+            };
+            Some(Index::Range(start, end))
+        } else {
+            match self.value(index)? {
+                Value::Int(i) => self.offset(i, len).map(Index::At),
+                Value::Range(i) => Some(Index::Range(
+                    self.offset(i.start, len)?,
+                    Bound::Excluded(self.offset(i.end, len)?),
+                )),
+                Value::RangeInclusive(i) => Some(Index::Range(
+                    self.offset(*i.start(), len)?,
+                    Bound::Included(self.offset(*i.end(), len)?),
+                )),
+                _ => None,
+            }
+        }
     }
 
-    fn visit_bin_op(&mut self, op: &'a BinOp) {
-        match TryFrom::try_from(*op) {
-            Ok(op) => self.push_op(op),
-            _ => self.on_err = true,
+    /// Index to offset, negatives count from the end when enabled
+    fn offset(&self, i: i64, len: usize) -> Option<usize> {
+        if i < 0 {
+            if self.opts.negative_index {
+                i.checked_neg()
+                    .and_then(|i| TryFrom::try_from(i).ok())
+                    .and_then(|i| len.checked_sub(i))
+            } else {
+                None
+            }
+        } else {
+            TryFrom::try_from(i).ok()
         }
     }
+}
 
-    fn visit_expr(&mut self, e: &'a Expr) {
+impl<'a> Visit for Reflect<'a> {
+    fn visit_expr(&mut self, e: &Expr) {
         on_err!(self);
-        use syn::Expr::*;
+        if let Err(l) = self.state.enter() {
+            self.state.leave();
+            return self.fail(Error::Limit(l));
+        }
         match e {
-            Binary(i) => self.visit_expr_binary(i),
-            Lit(i) => self.visit_expr_lit(i),
-            Paren(i) => self.visit_expr_paren(i),
-            Path(i) => self.visit_expr_path(i),
-            Unary(i) => self.visit_expr_unary(i),
-            Array(i) => self.visit_expr_array(i),
-            Range(i) => self.visit_expr_range(i),
-            Index(i) => self.visit_expr_index(i),
-            Reference(i) => self.visit_expr_reference(i),
-            MethodCall(i) => self.visit_expr_method_call(i),
-            Field(i) => self.visit_expr_field(i),
-            _ => self.on_err = true,
+            Expr::Binary(left, op, right) => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+                self.output.push(Output::Op(op.operator()));
+            }
+            Expr::Lit(l) => self.output.push(Output::V(lit(l))),
+            Expr::Var(name) => self.visit_var(name),
+            Expr::Unary(op, e) => self.visit_unary(*op, e),
+            Expr::Array(elems) => self.visit_list(elems, Value::Vec),
+            Expr::Range { from, to, closed } => self.visit_range(from, to, *closed),
+            Expr::Index(e, index) => self.visit_index(e, index),
+            Expr::MethodCall(receiver, method, args) => {
+                self.visit_method_call(receiver, method, args)
+            }
+            Expr::Field(base, member) => self.visit_field(base, member),
+            Expr::Tuple(elems) => self.visit_list(elems, Value::Tuple),
+            Expr::Map(entries) => self.visit_map(entries),
+            Expr::Block(lets, e) => self.visit_block(lets, e),
+            Expr::Match(e, arms) => self.visit_match(e, arms),
+            Expr::Try(e) => self.visit_expr(e),
+            Expr::Closure(..) => self.on_err = true,
         }
+        self.state.leave();
     }
+}
 
-    fn visit_expr_array(&mut self, ExprArray { elems, .. }: &'a ExprArray) {
+impl<'a> Reflect<'a> {
+    fn visit_list(&mut self, elems: &[Expr], f: impl FnOnce(Vec<Value>) -> Value) {
         let mut v = Vec::with_capacity(elems.len());
         for elem in elems {
-            if let Some(val) = Reflect::new(self.ctx).eval(elem) {
+            if let Some(val) = self.sub().eval(elem) {
                 v.push(val)
             } else {
                 self.on_err = true;
@@ -145,83 +642,129 @@ impl<'a> Visit<'a> for Reflect<'a> {
             }
         }
 
-        self.output.push(Output::V(v.into()));
-    }
-
-    fn visit_expr_binary(
-        &mut self,
-        ExprBinary {
-            left, op, right, ..
-        }: &'a ExprBinary,
-    ) {
-        self.visit_expr(left);
-        self.visit_bin_op(op);
-        self.visit_expr(right);
-    }
-
-    fn visit_expr_field(&mut self, _: &'a ExprField) {
-        self.output.push(Output::V(Value::None));
-    }
-
-    fn visit_expr_index(&mut self, ExprIndex { expr, index, .. }: &'a ExprIndex) {
-        use syn::Expr::*;
-        match **expr {
-            Paren(_) | Lit(_) | Array(_) | Path(_) => match eval(self.ctx, &*expr) {
-                Some(Value::Vec(a)) => {
-                    match eval(self.ctx, index).and_then(|v| match v {
-                        Value::Int(i) => TryFrom::try_from(i)
-                            .ok()
-                            .and_then(|i: usize| a.get(i).cloned()),
-                        Value::Range(i) => TryFrom::try_from(i.start)
-                            .and_then(|start| {
-                                TryFrom::try_from(i.end).map(|end| ops::Range { start, end })
-                            })
-                            .ok()
-                            .and_then(|i: ops::Range<usize>| a.get(i))
-                            .map(|x| Value::Vec(x.to_vec())),
-                        _ => None,
-                    }) {
-                        Some(i) => self.output.push(Output::V(i)),
-                        _ => self.on_err = true,
-                    }
-                }
-                Some(Value::Str(a)) => {
-                    match eval(self.ctx, index)
-                        .and_then(|v| match v {
-                            Value::Range(i) => TryFrom::try_from(i.start).ok().and_then(|start| {
-                                TryFrom::try_from(i.end).ok().map(|end| start..end)
-                            }),
-                            _ => None,
-                        })
-                        .and_then(|i: ops::Range<usize>| a.get(i))
-                    {
-                        Some(i) => self.output.push(Output::V(Value::Str(i.to_owned()))),
-                        _ => self.on_err = true,
-                    }
-                }
-                _ => self.on_err = true,
+        self.output.push(Output::V(f(v)));
+    }
+
+    fn visit_map(&mut self, entries: &[(String, Expr)]) {
+        let mut m = BTreeMap::new();
+        for (k, e) in entries {
+            if let Some(val) = self.sub().eval(e) {
+                m.insert(k.clone(), val);
+            } else {
+                self.on_err = true;
+                return;
+            }
+        }
+
+        self.output.push(Output::V(Value::Map(m)));
+    }
+
+    fn visit_block(&mut self, lets: &[(Pat, Expr)], e: &Expr) {
+        let mut locals = self.locals.clone();
+        for (pat, init) in lets {
+            let mut bindings = BTreeMap::new();
+            match self
+                .scoped(locals.clone())
+                .eval(init)
+                .and_then(|v| self.pattern(pat, &v, &mut bindings))
+            {
+                Some(true) => Rc::make_mut(&mut locals).extend(bindings),
+                _ => return self.on_err = true,
+            }
+        }
+
+        match self.scoped(locals).eval(e) {
+            Some(v) => self.output.push(Output::V(v)),
+            None => self.on_err = true,
+        }
+    }
+
+    fn visit_field(&mut self, base: &Expr, member: &Member) {
+        let (base, chain) = optional(base);
+        let value = match self.sub().eval(base) {
+            Some(Value::None) if chain => return self.output.push(Output::V(Value::None)),
+            Some(v) => v,
+            None => return self.on_err = true,
+        };
+
+        match (member, value) {
+            (Member::Named(k), Value::Map(mut m)) => match m.remove(k) {
+                Some(v) => self.output.push(Output::V(v)),
+                None if self.opts.none == NoneMode::Strict => self.on_err = true,
+                None => self.output.push(Output::V(Value::None)),
             },
+            (Member::Named(_), _) if self.opts.none == NoneMode::Strict => self.on_err = true,
+            (Member::Named(_), _) => self.output.push(Output::V(Value::None)),
+            (Member::Unnamed(i), Value::Tuple(mut t)) if (*i as usize) < t.len() => {
+                self.output.push(Output::V(t.swap_remove(*i as usize)))
+            }
             _ => self.on_err = true,
         }
     }
 
+    fn visit_index(&mut self, e: &Expr, index: &Expr) {
+        let (e, chain) = optional(e);
+        match self.sub().eval(e) {
+            Some(Value::None) if chain => self.output.push(Output::V(Value::None)),
+            Some(v) => match self.opts.unwrap(v).and_then(|v| self.index(v, index)) {
+                Some(v) => self.output.push(Output::V(v)),
+                None => self.on_err = true,
+            },
+            None => self.on_err = true,
+        }
+    }
+
+    fn visit_match(&mut self, e: &Expr, arms: &[Arm]) {
+        let value = match self.sub().eval(e) {
+            Some(v) => v,
+            None => return self.on_err = true,
+        };
+
+        for Arm { pat, guard, body } in arms {
+            let mut bindings = BTreeMap::new();
+            match self.pattern(pat, &value, &mut bindings) {
+                Some(true) => (),
+                Some(false) => continue,
+                None => return self.on_err = true,
+            }
+            let mut locals = self.locals.clone();
+            Rc::make_mut(&mut locals).extend(bindings);
+
+            if let Some(guard) = guard {
+                match self.scoped(locals.clone()).eval(guard) {
+                    Some(Value::Bool(true)) => (),
+                    Some(Value::Bool(false)) => continue,
+                    _ => return self.on_err = true,
+                }
+            }
+
+            return match self.scoped(locals).eval(body) {
+                Some(v) => self.output.push(Output::V(v)),
+                None => self.on_err = true,
+            };
+        }
+
+        self.on_err = true;
+    }
+
     #[inline]
-    fn visit_expr_method_call(
-        &mut self,
-        ExprMethodCall {
-            receiver,
-            method,
-            args,
-            ..
-        }: &'a ExprMethodCall,
-    ) {
-        if let Some(v) = Reflect::new(self.ctx).eval(receiver) {
-            self.output.push(Output::V(v));
-        } else {
-            return self.on_err = true;
+    fn visit_method_call(&mut self, receiver: &Expr, method: &str, args: &[Expr]) {
+        let (receiver, chain) = optional(receiver);
+        let receiver = match self.sub().eval(receiver) {
+            Some(Value::None) if chain => return self.output.push(Output::V(Value::None)),
+            Some(v) => v,
+            None => return self.on_err = true,
+        };
+
+        if let Ok(method) = method.parse::<Lazy>() {
+            return match self.lazy(method, receiver, args) {
+                Some(v) => self.output.push(Output::V(v)),
+                None => self.on_err = true,
+            };
         }
+        self.output.push(Output::V(receiver));
 
-        let method: Method = match method.to_string().parse() {
+        let method: Method = match method.parse() {
             Ok(m) => m,
             Err(_) => return self.on_err = true,
         };
@@ -230,7 +773,7 @@ impl<'a> Visit<'a> for Reflect<'a> {
             if args.len() != 1 {
                 return self.on_err = true;
             }
-            if let Some(v) = Reflect::new(self.ctx).eval(&args[0]) {
+            if let Some(v) = self.sub().eval(&args[0]) {
                 self.output.push(Output::V(v));
                 self.output.push(Output::Fn(method));
             } else {
@@ -244,117 +787,149 @@ impl<'a> Visit<'a> for Reflect<'a> {
         }
     }
 
-    fn visit_expr_paren(&mut self, ExprParen { expr, .. }: &'a ExprParen) {
-        self.push_op(Operator::ParenLeft);
-        self.visit_expr(expr);
-        self.push_op(Operator::ParenRight);
-    }
-
-    fn visit_expr_path(&mut self, ExprPath { qself, path, .. }: &'a ExprPath) {
-        err_some!(self, qself);
-        let path = match path.get_ident() {
-            Some(i) => i.to_string(),
-            _ => return self.on_err = true,
-        };
-
-        if path.as_str() == "None" {
-            self.output.push(Output::V(Value::None));
-            return;
-        }
-
-        if let Some(src) = self.ctx.get(&path) {
-            if let Some(v) = Reflect::new(self.ctx).eval(src) {
-                self.output.push(Output::V(v));
-            } else {
-                self.on_err = true;
-            }
+    fn visit_var(&mut self, name: &str) {
+        if let Some(v) = self.locals.get(name).or_else(|| self.ctx.value(name)) {
+            self.output.push(Output::V(v.clone()));
+        } else if let Some(src) = self.ctx.get(name) {
+            self.var(name, src);
+        } else if self.opts.none == NoneMode::Strict {
+            self.fail(Error::Undefined(name.to_owned()));
         } else {
             self.output.push(Output::V(Value::None));
         }
     }
 
-    fn visit_expr_range(&mut self, ExprRange { from, to, .. }: &'a ExprRange) {
+    fn visit_range(&mut self, from: &Option<Box<Expr>>, to: &Option<Box<Expr>>, closed: bool) {
         if let Some(range) = from
             .as_ref()
-            .and_then(|from| Reflect::new(self.ctx).eval(&*from))
-            .and_then(|from| {
-                if let Value::Int(from) = from {
-                    Some(from)
-                } else {
-                    None
-                }
-            })
+            .and_then(|from| self.sub().eval(from))
             .and_then(|from| {
                 to.as_ref()
-                    .and_then(|to| {
-                        Reflect::new(self.ctx).eval(&*to).and_then(|to| {
-                            if let Value::Int(to) = to {
-                                Some(to)
-                            } else {
-                                None
-                            }
-                        })
-                    })
-                    .map(|to| from..to)
+                    .and_then(|to| self.sub().eval(to))
+                    .and_then(|to| range(from, to, closed))
             })
         {
-            self.output.push(Output::V(range.into()));
+            self.output.push(Output::V(range));
         } else {
             self.on_err = true;
         }
     }
 
-    fn visit_expr_reference(&mut self, ExprReference { expr, .. }: &'a ExprReference) {
-        self.visit_expr(expr);
-    }
-
-    fn visit_expr_unary(&mut self, ExprUnary { op, expr, .. }: &'a ExprUnary) {
-        self.visit_expr(expr);
-        use syn::UnOp::*;
+    /// Unary operators are evaluated as binary with a dummy right operand
+    fn visit_unary(&mut self, op: UnOp, e: &Expr) {
+        self.visit_expr(e);
         match op {
-            Not(_) => {
-                self.push_op(Operator::Not);
+            UnOp::Not => {
                 self.output.push(Output::V(false.into()));
+                self.output.push(Output::Op(Operator::Not));
             }
-            Neg(_) => {
-                self.push_op(Operator::Neg);
+            UnOp::Neg => {
                 self.output.push(Output::V(0.into()));
+                self.output.push(Output::Op(Operator::Neg));
             }
-            _ => self.on_err = true,
         }
     }
+}
 
-    fn visit_lit(&mut self, l: &'a Lit) {
-        use syn::Lit::*;
-        match l {
-            Int(a) => match a.base10_parse::<i64>() {
-                Ok(n) => self.output.push(Output::V(n.into())),
-                _ => self.on_err = true,
-            },
-            Float(a) => match a.base10_parse::<f64>() {
-                Ok(n) => self.output.push(Output::V(n.into())),
-                _ => self.on_err = true,
-            },
-            Bool(a) => self.output.push(Output::V(a.value.into())),
-            Str(a) => self.output.push(Output::V(a.value().into())),
-            Char(a) => self.output.push(Output::V(a.value().to_string().into())),
-            _ => self.on_err = true,
+fn lit(l: &Lit) -> Value {
+    match l {
+        Lit::None => Value::None,
+        Lit::Bool(b) => (*b).into(),
+        Lit::Int(n) => (*n).into(),
+        Lit::Float(n) => (*n).into(),
+        Lit::Str(s) => s.clone().into(),
+        Lit::Char(c) => c.to_string().into(),
+    }
+}
+
+/// Build a range value, half-open or inclusive
+fn range(from: Value, to: Value, closed: bool) -> Option<Value> {
+    macro_rules! range {
+        ($from:expr, $to:expr) => {
+            if closed {
+                ($from..=$to).into()
+            } else {
+                ($from..$to).into()
+            }
+        };
+    }
+
+    Some(match (from, to) {
+        (Value::Int(from), Value::Int(to)) => range!(from, to),
+        (Value::Str(from), Value::Str(to)) => range!(to_char(&from)?, to_char(&to)?),
+        (from, to) => {
+            let from: f64 = from.try_into().ok()?;
+            let to: f64 = to.try_into().ok()?;
+            range!(from, to)
+        }
+    })
+}
+
+/// Strip the `?` of optional chaining
+pub(crate) fn optional(e: &Expr) -> (&Expr, bool) {
+    match e {
+        Expr::Try(e) => (e, true),
+        e => (e, false),
+    }
+}
+
+fn in_range(range: &Value, value: &Value) -> bool {
+    match (range, value) {
+        (Value::Range(r), Value::Int(i)) => r.contains(i),
+        (Value::Range(r), Value::Float(f)) => (r.start as f64) <= *f && *f < (r.end as f64),
+        (Value::FloatRange(r), Value::Float(f)) => r.contains(f),
+        (Value::FloatRange(r), Value::Int(i)) => r.contains(&(*i as f64)),
+        (Value::CharRange(r), Value::Str(s)) => to_char(s).is_some_and(|c| r.contains(&c)),
+        (Value::RangeInclusive(r), Value::Int(i)) => r.contains(i),
+        (Value::RangeInclusive(r), Value::Float(f)) => {
+            (*r.start() as f64) <= *f && *f <= (*r.end() as f64)
         }
+        (Value::FloatRangeInclusive(r), Value::Float(f)) => r.contains(f),
+        (Value::FloatRangeInclusive(r), Value::Int(i)) => r.contains(&(*i as f64)),
+        (Value::CharRangeInclusive(r), Value::Str(s)) => to_char(s).is_some_and(|c| r.contains(&c)),
+        _ => false,
+    }
+}
+
+fn to_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
     }
 }
 
 pub(crate) trait Eval {
     fn eval(self, stack: &mut Vec<Value>) -> Result<(), ()>;
+
+    /// Size of the value to be produced when it can be too large to allocate
+    fn size_hint(self, _stack: &[Value]) -> usize
+    where
+        Self: Sized,
+    {
+        0
+    }
 }
 
-#[inline]
+#[cfg(test)]
 fn evaluate(output: Vec<Output>) -> Result<Value, ()> {
+    evaluate_with(output, NoneMode::Lenient, &State::default())
+}
+
+#[inline]
+fn evaluate_with(output: Vec<Output>, none: NoneMode, state: &State) -> Result<Value, ()> {
     let mut stack = Vec::new();
     for o in output {
         match o {
-            Output::V(v) => stack.push(v),
-            Output::Fn(m) => m.eval(&mut stack)?,
-            Output::Op(op) => op.eval(&mut stack)?,
+            Output::V(v) => {
+                state.check(state.size(size(&v)))?;
+                stack.push(v)
+            }
+            Output::Fn(m) => state.checked(m.size_hint(&stack), &mut stack, |s| m.eval(s))?,
+            Output::Op(op) if none == NoneMode::Propagate => {
+                state.checked(op.size_hint(&stack), &mut stack, |s| op.eval_nullable(s))?
+            }
+            Output::Op(op) => state.checked(op.size_hint(&stack), &mut stack, |s| op.eval(s))?,
         }
     }
 
@@ -367,8 +942,6 @@ fn evaluate(output: Vec<Output>) -> Result<Value, ()> {
 
 #[cfg(test)]
 mod test {
-    use syn::parse_str;
-
     use super::{
         super::{operator::Operator::*, Value::*},
         Output::*,
@@ -471,7 +1044,7 @@ mod test {
         assert_eq!(evaluate(o_float).unwrap(), Bool(true));
 
         let o_bool = vec![V(Bool(true)), V(Bool(false)), Op(Ge)];
-        assert!(evaluate(o_bool).is_err());
+        assert_eq!(evaluate(o_bool).unwrap(), Bool(true));
     }
 
     #[test]
@@ -483,7 +1056,7 @@ mod test {
         assert_eq!(evaluate(o_float).unwrap(), Bool(true));
 
         let o_bool = vec![V(Bool(true)), V(Bool(true)), Op(Le)];
-        assert!(evaluate(o_bool).is_err());
+        assert_eq!(evaluate(o_bool).unwrap(), Bool(true));
     }
 
     #[test]
@@ -495,7 +1068,7 @@ mod test {
         assert_eq!(evaluate(o_float).unwrap(), Bool(true));
 
         let o_bool = vec![V(Bool(true)), V(Bool(false)), Op(Gt)];
-        assert!(evaluate(o_bool).is_err());
+        assert_eq!(evaluate(o_bool).unwrap(), Bool(true));
     }
 
     #[test]
@@ -507,33 +1080,42 @@ mod test {
         assert_eq!(evaluate(o_float).unwrap(), Bool(true));
 
         let o_bool = vec![V(Bool(true)), V(Bool(true)), Op(Lt)];
-        assert!(evaluate(o_bool).is_err());
+        assert_eq!(evaluate(o_bool).unwrap(), Bool(false));
+
+        let o = vec![V(Str("a".into())), V(Str("b".into())), Op(Lt)];
+        assert_eq!(evaluate(o).unwrap(), Bool(true));
+
+        let o = vec![V(Range(0..1)), V(Range(0..2)), Op(Lt)];
+        assert_eq!(evaluate(o).unwrap(), Bool(true));
+
+        let o = vec![V(Str("a".into())), V(Int(1)), Op(Lt)];
+        assert!(evaluate(o).is_err());
     }
 
     #[test]
     fn test_eval_literal() {
         let src = "true";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(true));
 
         let src = "-1";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Int(-1));
 
         let src = "-1.0";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Float(-1.0));
 
         let src = "foo";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let mut ctx = BTreeMap::new();
-        let arg = parse_str::<syn::Expr>("-1").unwrap();
+        let arg = Expr::parse("-1").unwrap();
         ctx.insert("foo".to_string(), arg);
 
         assert_eq!(eval(&ctx, &e).unwrap(), Int(-1));
@@ -542,13 +1124,13 @@ mod test {
     #[test]
     fn test_eval_one() {
         let src = "true + true";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e), Option::None);
 
         let src = "1 + 1";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Int(2));
@@ -557,69 +1139,69 @@ mod test {
     #[test]
     fn test_eval() {
         let src = "true && true || false == true";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(true));
 
         let src = "1 + 1 - 6 % 5";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Int(1));
 
         let src = "1 + 1 - 10 / 5";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Int(0));
 
         let src = "1 * 1 - 10 / 5 == -1";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(true));
 
         let src = "-1 == 1 * 1 - 10 / 5";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(true));
 
         let src = "!(1 * 1 - 10 / 5 == -1)";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e), Some(Bool(false)));
 
         let src = "!(-1 == 1 * 1 - 10 / 5)";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let ctx = BTreeMap::new();
 
         assert_eq!(eval(&ctx, &e), Some(Bool(false)));
 
         let src = "!(1 * 1 - 10 / 5 == foo)";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let mut ctx = BTreeMap::new();
-        let arg = parse_str::<syn::Expr>("-1").unwrap();
+        let arg = Expr::parse("-1").unwrap();
 
         ctx.insert("foo".to_string(), arg);
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(false));
 
         let src = "!(foo == 1 * 1 - 10 / 5)";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let mut ctx = BTreeMap::new();
-        let arg = parse_str::<syn::Expr>("-1").unwrap();
+        let arg = Expr::parse("-1").unwrap();
 
         ctx.insert("foo".to_string(), arg);
 
         assert_eq!(eval(&ctx, &e).unwrap(), Bool(false));
 
         let src = "(foo * 2) + 1";
-        let e = parse_str::<syn::Expr>(src).unwrap();
+        let e = Expr::parse(src).unwrap();
         let mut ctx = BTreeMap::new();
-        let arg = parse_str::<syn::Expr>("-1 + 1").unwrap();
+        let arg = Expr::parse("-1 + 1").unwrap();
 
         ctx.insert("foo".to_string(), arg);
 
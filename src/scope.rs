@@ -0,0 +1,116 @@
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, sync::Arc};
+
+use crate::{
+    ast::Expr,
+    reflect::{eval_with, Ctx},
+    Error, Eval, Value,
+};
+
+/// Names layered over the context of a shared evaluator, see [`Eval::scope`]
+///
+/// Lookups fall through to the evaluator, so the scope only holds its own
+/// names, values as they are and expressions evaluated with the evaluator
+/// options
+#[derive(Clone)]
+pub struct Scope {
+    pub(crate) base: Arc<Eval>,
+    pub(crate) values: BTreeMap<String, Value>,
+    pub(crate) vars: BTreeMap<String, Expr>,
+}
+
+impl Scope {
+    /// Empty scope over an evaluator, an `Eval` or a shared `Arc<Eval>`
+    pub fn new(base: impl Into<Arc<Eval>>) -> Self {
+        Self {
+            base: base.into(),
+            values: BTreeMap::new(),
+            vars: BTreeMap::new(),
+        }
+    }
+
+    /// Insert in scope name - value
+    pub fn set(mut self, k: &str, v: impl Into<Value>) -> Self {
+        self.vars.remove(k);
+        self.values.insert(k.to_owned(), v.into());
+
+        self
+    }
+
+    /// Parse and insert in scope name - ast::Expr
+    #[allow(clippy::result_unit_err)]
+    pub fn insert(mut self, k: &str, v: &str) -> Result<Self, ()> {
+        let e = self.base.parse(v).map_err(|_| ())?;
+        self.values.remove(k);
+        self.vars.insert(k.to_owned(), e);
+
+        Ok(self)
+    }
+
+    /// Remove key in scope, a key of the evaluator is visible again
+    pub fn remove(mut self, k: &str) -> Self {
+        self.values.remove(k);
+        self.vars.remove(k);
+
+        self
+    }
+
+    /// Evaluate expression with the scope over the evaluator context
+    pub fn eval(&self, src: &str) -> Option<Value> {
+        self.try_eval(src).ok()
+    }
+
+    /// Evaluate expression with the scope over the evaluator context, with
+    /// the reason of failure
+    pub fn try_eval(&self, src: &str) -> Result<Value, Error> {
+        self.base
+            .parse(src)
+            .and_then(|e| eval_with(self.ctx(), self.base.1, &e))
+    }
+
+    pub(crate) fn ctx(&self) -> Ctx<'_> {
+        Ctx::scoped(&self.base.0, &self.values).with_exprs(&self.vars)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scope() {
+        let base = Arc::new(
+            Eval::default()
+                .insert("a", "1")
+                .unwrap()
+                .insert("b", "a + c")
+                .unwrap()
+                .negative_index(true),
+        );
+        assert_eq!(base.eval("b"), None);
+
+        let s = base.scope().set("c", 2).set("a", 10);
+        assert_eq!(s.eval("b"), Some(Value::Int(12)));
+        assert_eq!(s.eval("[a, c][-1]"), Some(Value::Int(2)));
+        let s = s.remove("a").insert("d", "b * 2").unwrap();
+        assert_eq!(s.eval("d"), Some(Value::Int(6)));
+        assert!(s.clone().insert("e", "1 +").is_err());
+        let s = s.insert("c", "a + 2").unwrap();
+        assert_eq!(
+            s.eval("(c, d)"),
+            Some(Value::Tuple(alloc::vec![3.into(), 8.into()]))
+        );
+        let s = s
+            .set("c", "x")
+            .set("v", Value::Vec(alloc::vec![Value::None]));
+        assert_eq!(s.eval("c + \"y\""), Some(Value::Str("xy".into())));
+        assert_eq!(s.eval("v.len()"), Some(Value::Int(1)));
+        let s = s.set("c", 2);
+        assert_eq!(
+            base.compile("(a, c)").unwrap().try_eval_scoped(&s),
+            Ok(Value::Tuple(alloc::vec![1.into(), 2.into()]))
+        );
+
+        assert_eq!(base.eval("a"), Some(Value::Int(1)));
+        assert_eq!(base.eval("c"), None);
+    }
+}
@@ -0,0 +1,314 @@
+use alloc::collections::BTreeMap;
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+use crate::Value;
+
+/// Static type of an expression
+///
+/// Parsed from its display form, e.g. `Int`, `Vec<Float>`, `Option<Str>`,
+/// `(Int, Str)` or `{name: Str, tags: Vec<Str>}`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    /// Unknown, any operation is accepted
+    Any,
+    /// Type of `None`
+    None,
+    Bool,
+    Int,
+    Float,
+    Str,
+    Range,
+    FloatRange,
+    CharRange,
+    Vec(Box<Type>),
+    Tuple(Vec<Type>),
+    /// Value or `None`
+    Option(Box<Type>),
+    /// Record with named fields, typing field access
+    Map(BTreeMap<String, Type>),
+}
+
+impl Type {
+    /// Type of a value
+    pub fn of(v: &Value) -> Type {
+        match v {
+            Value::None => Type::None,
+            Value::Bool(_) => Type::Bool,
+            Value::Int(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Str(_) => Type::Str,
+            Value::Range(_) | Value::RangeInclusive(_) => Type::Range,
+            Value::FloatRange(_) | Value::FloatRangeInclusive(_) => Type::FloatRange,
+            Value::CharRange(_) | Value::CharRangeInclusive(_) => Type::CharRange,
+            Value::Vec(v) => Type::Vec(Box::new(
+                v.iter()
+                    .map(Type::of)
+                    .reduce(Type::unify)
+                    .unwrap_or(Type::Any),
+            )),
+            Value::Tuple(v) => Type::Tuple(v.iter().map(Type::of).collect()),
+            Value::Map(m) => Type::Map(m.iter().map(|(k, v)| (k.clone(), Type::of(v))).collect()),
+        }
+    }
+
+    /// Value or `None`
+    pub fn option(t: Type) -> Type {
+        match t {
+            Type::Option(_) | Type::None | Type::Any => t,
+            t => Type::Option(Box::new(t)),
+        }
+    }
+
+    /// Can be `None`
+    pub fn is_nullable(&self) -> bool {
+        matches!(self, Type::Option(_) | Type::None)
+    }
+
+    /// Type of the value when it is not `None`
+    pub fn some(&self) -> &Type {
+        match self {
+            Type::Option(t) => t,
+            t => t,
+        }
+    }
+
+    /// Type of values of either type
+    pub(crate) fn unify(a: Type, b: Type) -> Type {
+        let nullable = a.is_nullable() || b.is_nullable();
+        let t = match (a.some(), b.some()) {
+            (Type::None, t) | (t, Type::None) => t.clone(),
+            (a, b) if a == b => a.clone(),
+            _ => Type::Any,
+        };
+        if nullable {
+            Type::option(t)
+        } else {
+            t
+        }
+    }
+
+    /// Type of `a.or(b)`, `b` when `a` is `None`
+    pub(crate) fn or(a: &Type, b: Type) -> Type {
+        match a {
+            Type::None => b,
+            Type::Option(a) => Type::unify((**a).clone(), b),
+            a => a.clone(),
+        }
+    }
+
+    /// Representative value, to check operations with the evaluation rules
+    pub(crate) fn sample(&self) -> Option<Value> {
+        Some(match self {
+            Type::Any | Type::Option(_) => return None,
+            Type::None => Value::None,
+            Type::Bool => Value::Bool(false),
+            Type::Int => Value::Int(0),
+            Type::Float => Value::Float(0.0),
+            Type::Str => Value::Str(String::new()),
+            Type::Range => Value::Range(0..0),
+            Type::FloatRange => Value::FloatRange(0.0..0.0),
+            Type::CharRange => Value::CharRange('a'..'a'),
+            Type::Vec(_) => Value::Vec(vec![]),
+            Type::Tuple(_) => Value::Tuple(vec![]),
+            Type::Map(_) => Value::Map(BTreeMap::new()),
+        })
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Type::Any => f.write_str("Any"),
+            Type::None => f.write_str("None"),
+            Type::Bool => f.write_str("Bool"),
+            Type::Int => f.write_str("Int"),
+            Type::Float => f.write_str("Float"),
+            Type::Str => f.write_str("Str"),
+            Type::Range => f.write_str("Range"),
+            Type::FloatRange => f.write_str("FloatRange"),
+            Type::CharRange => f.write_str("CharRange"),
+            Type::Vec(t) => write!(f, "Vec<{}>", t),
+            Type::Option(t) => write!(f, "Option<{}>", t),
+            Type::Tuple(t) if t.len() == 1 => write!(f, "({},)", t[0]),
+            Type::Tuple(t) => {
+                f.write_str("(")?;
+                for (i, t) in t.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", t)?;
+                }
+                f.write_str(")")
+            }
+            Type::Map(m) => {
+                f.write_str("{")?;
+                for (i, (k, t)) in m.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", k, t)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().peekable();
+        let t = parse(&mut chars)?;
+        skip_ws(&mut chars);
+        if chars.next().is_some() {
+            Err(())
+        } else {
+            Ok(t)
+        }
+    }
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn ident(chars: &mut Peekable<Chars>) -> String {
+    skip_ws(chars);
+    let mut s = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '_' {
+            s.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+fn expect(chars: &mut Peekable<Chars>, c: char) -> Result<(), ()> {
+    skip_ws(chars);
+    if chars.next() == Some(c) {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+/// Comma separated items until `close`, with optional trailing comma
+fn list<T>(
+    chars: &mut Peekable<Chars>,
+    close: char,
+    mut item: impl FnMut(&mut Peekable<Chars>) -> Result<T, ()>,
+) -> Result<Vec<T>, ()> {
+    let mut v = vec![];
+    loop {
+        skip_ws(chars);
+        if chars.peek() == Some(&close) {
+            chars.next();
+            return Ok(v);
+        }
+        v.push(item(chars)?);
+        skip_ws(chars);
+        match chars.next() {
+            Some(',') => (),
+            Some(c) if c == close => return Ok(v),
+            _ => return Err(()),
+        }
+    }
+}
+
+fn parse(chars: &mut Peekable<Chars>) -> Result<Type, ()> {
+    skip_ws(chars);
+    match chars.peek() {
+        Some('(') => {
+            chars.next();
+            return list(chars, ')', parse).map(Type::Tuple);
+        }
+        Some('{') => {
+            chars.next();
+            return list(chars, '}', |chars| {
+                let k = ident(chars);
+                if k.is_empty() {
+                    return Err(());
+                }
+                expect(chars, ':')?;
+                Ok((k, parse(chars)?))
+            })
+            .map(|m| Type::Map(m.into_iter().collect()));
+        }
+        _ => (),
+    }
+
+    let generic = |chars: &mut Peekable<Chars>| -> Result<Box<Type>, ()> {
+        expect(chars, '<')?;
+        let t = parse(chars)?;
+        expect(chars, '>')?;
+        Ok(Box::new(t))
+    };
+    Ok(match ident(chars).as_str() {
+        "Any" => Type::Any,
+        "None" => Type::None,
+        "Bool" => Type::Bool,
+        "Int" => Type::Int,
+        "Float" => Type::Float,
+        "Str" => Type::Str,
+        "Range" => Type::Range,
+        "FloatRange" => Type::FloatRange,
+        "CharRange" => Type::CharRange,
+        "Vec" => Type::Vec(generic(chars)?),
+        "Option" => Type::option(*generic(chars)?),
+        _ => return Err(()),
+    })
+}
+
+/// Types of the context names
+///
+/// ```rust
+///# use v_eval::{Schema, Type};
+///# fn main() -> Result<(), ()> {
+/// let schema = Schema::default()
+///     .insert("age", "Int")?
+///     .insert("tags", "Vec<Str>")?
+///     .var("score", Type::option(Type::Float));
+///
+/// assert_eq!(schema.get("tags"), Some(&Type::Vec(Box::new(Type::Str))));
+/// assert_eq!(schema.get("score").unwrap().to_string(), "Option<Float>");
+///# Ok(())
+///# }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema(BTreeMap<String, Type>);
+
+impl Schema {
+    /// Parse and insert the type of a name
+    #[allow(clippy::result_unit_err)]
+    pub fn insert(self, k: &str, t: &str) -> Result<Self, ()> {
+        Ok(self.var(k, t.parse()?))
+    }
+
+    /// Insert the type of a name
+    pub fn var(mut self, k: &str, t: Type) -> Self {
+        self.0.insert(k.to_owned(), t);
+
+        self
+    }
+
+    /// Type of a name
+    pub fn get(&self, k: &str) -> Option<&Type> {
+        self.0.get(k)
+    }
+}
+
+/// Result type of a method
+pub(crate) trait Infer {
+    fn infer(self, receiver: &Type, arg: Option<&Type>) -> Option<Type>;
+}
@@ -1,8 +1,14 @@
-use std::{
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
     cmp::Ordering,
     convert::TryInto,
     fmt::{self, Display, Formatter},
-    ops::{Add, Div, Mul, Range, Rem, Sub},
+    ops::{Add, Div, Mul, Range, RangeInclusive, Rem, Sub},
 };
 
 #[derive(Clone, Debug)]
@@ -17,7 +23,14 @@ pub enum Value {
     Int(i64),
     Str(String),
     Range(Range<i64>),
+    FloatRange(Range<f64>),
+    CharRange(Range<char>),
+    RangeInclusive(RangeInclusive<i64>),
+    FloatRangeInclusive(RangeInclusive<f64>),
+    CharRangeInclusive(RangeInclusive<char>),
     Vec(Vec<Value>),
+    Tuple(Vec<Value>),
+    Map(BTreeMap<String, Value>),
     None,
 }
 
@@ -25,18 +38,25 @@ impl Value {
     /// Is same type
     pub fn is_same(&self, other: &Value) -> bool {
         use Value::*;
-        match (self, other) {
+        matches!(
+            (self, other),
             (Float(_), Float(_))
-            | (Int(_), Int(_))
-            | (Float(_), Int(_))
-            | (Int(_), Float(_))
-            | (Bool(_), Bool(_))
-            | (Str(_), Str(_))
-            | (Range(_), Range(_))
-            | (Vec(_), Vec(_))
-            | (None, None) => true,
-            _ => false,
-        }
+                | (Int(_), Int(_))
+                | (Float(_), Int(_))
+                | (Int(_), Float(_))
+                | (Bool(_), Bool(_))
+                | (Str(_), Str(_))
+                | (Range(_), Range(_))
+                | (FloatRange(_), FloatRange(_))
+                | (CharRange(_), CharRange(_))
+                | (RangeInclusive(_), RangeInclusive(_))
+                | (FloatRangeInclusive(_), FloatRangeInclusive(_))
+                | (CharRangeInclusive(_), CharRangeInclusive(_))
+                | (Vec(_), Vec(_))
+                | (Tuple(_), Tuple(_))
+                | (Map(_), Map(_))
+                | (None, None)
+        )
     }
 
     pub fn not(&self) -> Value {
@@ -72,6 +92,91 @@ impl Value {
         }
     }
 
+    /// Total order between any two values
+    ///
+    /// Values of the same type compare as with `<`, and values of different
+    /// types by type in this order:
+    /// `None < Bool < Int, Float < Str < Range < FloatRange < CharRange <
+    /// RangeInclusive < FloatRangeInclusive < CharRangeInclusive < Vec < Tuple < Map`.
+    ///
+    /// `Int` and `Float` compare by numeric value and `NaN` is greater than
+    /// any other number. `Vec` and `Tuple` compare element-wise with this same order,
+    /// and `Map` entry-wise in key order.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        use self::Value::*;
+
+        fn float(a: f64, b: f64) -> Ordering {
+            a.partial_cmp(&b)
+                .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+        }
+
+        fn slice(a: &[Value], b: &[Value]) -> Ordering {
+            for (a, b) in a.iter().zip(b) {
+                match a.total_cmp(b) {
+                    Ordering::Equal => (),
+                    o => return o,
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+
+        match (self, other) {
+            (Float(a), Float(b)) => float(*a, *b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (Float(a), Int(b)) => int_float(*b, *a).map_or(Ordering::Greater, Ordering::reverse),
+            (Int(a), Float(b)) => int_float(*a, *b).unwrap_or(Ordering::Less),
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Str(a), Str(b)) => a.cmp(b),
+            (Range(a), Range(b)) => (a.start, a.end).cmp(&(b.start, b.end)),
+            (FloatRange(a), FloatRange(b)) => {
+                float(a.start, b.start).then_with(|| float(a.end, b.end))
+            }
+            (CharRange(a), CharRange(b)) => (a.start, a.end).cmp(&(b.start, b.end)),
+            (RangeInclusive(a), RangeInclusive(b)) => {
+                (a.start(), a.end()).cmp(&(b.start(), b.end()))
+            }
+            (FloatRangeInclusive(a), FloatRangeInclusive(b)) => {
+                float(*a.start(), *b.start()).then_with(|| float(*a.end(), *b.end()))
+            }
+            (CharRangeInclusive(a), CharRangeInclusive(b)) => {
+                (a.start(), a.end()).cmp(&(b.start(), b.end()))
+            }
+            (Vec(a), Vec(b)) | (Tuple(a), Tuple(b)) => slice(a, b),
+            (Map(a), Map(b)) => {
+                for ((ka, a), (kb, b)) in a.iter().zip(b) {
+                    match ka.cmp(kb).then_with(|| a.total_cmp(b)) {
+                        Ordering::Equal => (),
+                        o => return o,
+                    }
+                }
+                a.len().cmp(&b.len())
+            }
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+
+    /// Type position in the total order
+    fn rank(&self) -> u8 {
+        use self::Value::*;
+        match self {
+            None => 0,
+            Bool(_) => 1,
+            Int(_) | Float(_) => 2,
+            Str(_) => 3,
+            Range(_) => 4,
+            FloatRange(_) => 5,
+            CharRange(_) => 6,
+            RangeInclusive(_) => 7,
+            FloatRangeInclusive(_) => 8,
+            CharRangeInclusive(_) => 9,
+            Vec(_) => 10,
+            Tuple(_) => 11,
+            Map(_) => 12,
+        }
+    }
+
+    /// `Err` when is `None`
+    #[allow(clippy::result_unit_err)]
     pub fn unwrap(self) -> Result<Self, ()> {
         if self.is_some() {
             Ok(self)
@@ -80,10 +185,22 @@ impl Value {
         }
     }
 
+    /// Is not `None`, a `Vec`, `Tuple` or `Map` holding `None` elements is still some
     pub fn is_some(&self) -> bool {
+        !self.is_none()
+    }
+
+    /// Is `None`
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    /// Is not `None` and has no `None` element at any depth
+    pub fn is_complete(&self) -> bool {
         match self {
             Value::None => false,
-            Value::Vec(a) => a.iter().all(|v| v.is_some()),
+            Value::Vec(a) | Value::Tuple(a) => a.iter().all(|v| v.is_complete()),
+            Value::Map(m) => m.values().all(|v| v.is_complete()),
             _ => true,
         }
     }
@@ -153,12 +270,69 @@ impl From<Range<i64>> for Value {
     }
 }
 
+impl From<Range<f64>> for Value {
+    #[inline]
+    fn from(t: Range<f64>) -> Self {
+        Value::FloatRange(t)
+    }
+}
+
+impl From<Range<char>> for Value {
+    #[inline]
+    fn from(t: Range<char>) -> Self {
+        Value::CharRange(t)
+    }
+}
+
+impl From<RangeInclusive<i64>> for Value {
+    #[inline]
+    fn from(t: RangeInclusive<i64>) -> Self {
+        Value::RangeInclusive(t)
+    }
+}
+
+impl From<RangeInclusive<f64>> for Value {
+    #[inline]
+    fn from(t: RangeInclusive<f64>) -> Self {
+        Value::FloatRangeInclusive(t)
+    }
+}
+
+impl From<RangeInclusive<char>> for Value {
+    #[inline]
+    fn from(t: RangeInclusive<char>) -> Self {
+        Value::CharRangeInclusive(t)
+    }
+}
+
+impl<V: Into<Value>> From<BTreeMap<String, V>> for Value {
+    fn from(t: BTreeMap<String, V>) -> Self {
+        Value::Map(t.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
 impl<V: Into<Value>> From<Vec<V>> for Value {
     fn from(t: Vec<V>) -> Self {
         Value::Vec(t.into_iter().map(Into::into).collect())
     }
 }
 
+macro_rules! from_tuple {
+    ($($t:ident)+) => {
+        impl<$($t: Into<Value>),+> From<($($t,)+)> for Value {
+            #[allow(non_snake_case)]
+            fn from(($($t,)+): ($($t,)+)) -> Self {
+                Value::Tuple(vec![$($t.into()),+])
+            }
+        }
+    };
+}
+
+from_tuple!(A);
+from_tuple!(A B);
+from_tuple!(A B C);
+from_tuple!(A B C D);
+
 impl<V: Into<Value>> From<Option<V>> for Value {
     fn from(t: Option<V>) -> Self {
         t.map_or(Value::None, Into::into)
@@ -189,8 +363,14 @@ try_into!(
     i64, Int,
     String, Str,
     Range<i64>, Range,
+    Range<f64>, FloatRange,
+    Range<char>, CharRange,
+    RangeInclusive<i64>, RangeInclusive,
+    RangeInclusive<f64>, FloatRangeInclusive,
+    RangeInclusive<char>, CharRangeInclusive,
     bool, Bool,
-    Vec<Value>, Vec
+    Vec<Value>, Vec,
+    BTreeMap<String, Value>, Map
 );
 
 impl TryInto<f64> for Value {
@@ -225,6 +405,11 @@ impl Display for Value {
             Bool(a) => a.fmt(f),
             Str(a) => fmt::Debug::fmt(a, f),
             Range(a) => fmt::Debug::fmt(a, f),
+            FloatRange(a) => fmt::Debug::fmt(a, f),
+            CharRange(a) => fmt::Debug::fmt(a, f),
+            RangeInclusive(a) => fmt::Debug::fmt(a, f),
+            FloatRangeInclusive(a) => fmt::Debug::fmt(a, f),
+            CharRangeInclusive(a) => fmt::Debug::fmt(a, f),
             Vec(a) => {
                 f.write_str("[")?;
                 for i in a {
@@ -233,23 +418,66 @@ impl Display for Value {
                 }
                 f.write_str("]")
             }
+            Tuple(a) => {
+                f.write_str("(")?;
+                for i in a {
+                    i.fmt(f)?;
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Map(a) => {
+                f.write_str("{")?;
+                for (k, v) in a {
+                    fmt::Debug::fmt(k, f)?;
+                    f.write_str(":")?;
+                    v.fmt(f)?;
+                    f.write_str(",")?;
+                }
+                f.write_str("}")
+            }
             None => f.write_str("None"),
         }
     }
 }
 
+/// Exact order of an integer and a float, the `as f64` conversion rounds
+/// integers over 2^53, `None` with `NaN`
+fn int_float(a: i64, b: f64) -> Option<Ordering> {
+    // -2^63 and 2^63, the bounds of `i64` as floats
+    const MIN: f64 = -9_223_372_036_854_775_808.0;
+    if b.is_nan() {
+        Option::None
+    } else if b >= -MIN {
+        Some(Ordering::Less)
+    } else if b < MIN {
+        Some(Ordering::Greater)
+    } else {
+        // In range the conversions are exact, `b - i` is the fraction
+        let i = b as i64;
+        0.0.partial_cmp(&(b - i as f64)).map(|o| a.cmp(&i).then(o))
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Value) -> bool {
         use self::Value::*;
         match (self, other) {
             (Float(a), Float(b)) => a == b,
             (Int(a), Int(b)) => a == b,
-            (Float(a), Int(b)) => *a == (*b as f64),
-            (Int(a), Float(b)) => (*a as f64) == *b,
+            (Float(a), Int(b)) => int_float(*b, *a) == Some(Ordering::Equal),
+            (Int(a), Float(b)) => int_float(*a, *b) == Some(Ordering::Equal),
             (Bool(a), Bool(b)) => a == b,
             (Str(a), Str(b)) => a == b,
             (Vec(a), Vec(b)) => a == b,
+            (Tuple(a), Tuple(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
             (Range(a), Range(b)) => a == b,
+            (FloatRange(a), FloatRange(b)) => a == b,
+            (CharRange(a), CharRange(b)) => a == b,
+            (RangeInclusive(a), RangeInclusive(b)) => a == b,
+            (FloatRangeInclusive(a), FloatRangeInclusive(b)) => a == b,
+            (CharRangeInclusive(a), CharRangeInclusive(b)) => a == b,
             (None, None) => true,
             _ => false,
         }
@@ -258,12 +486,30 @@ impl PartialEq for Value {
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        use self::Value::*;
         match (self, other) {
-            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
-            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
-            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
-            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
-            _ => None,
+            (Float(a), Float(b)) => a.partial_cmp(b),
+            (Int(a), Int(b)) => a.partial_cmp(b),
+            (Float(a), Int(b)) => int_float(*b, *a).map(Ordering::reverse),
+            (Int(a), Float(b)) => int_float(*a, *b),
+            (Bool(a), Bool(b)) => a.partial_cmp(b),
+            (Str(a), Str(b)) => a.partial_cmp(b),
+            (Range(a), Range(b)) => (a.start, a.end).partial_cmp(&(b.start, b.end)),
+            (FloatRange(a), FloatRange(b)) => (a.start, a.end).partial_cmp(&(b.start, b.end)),
+            (CharRange(a), CharRange(b)) => (a.start, a.end).partial_cmp(&(b.start, b.end)),
+            (RangeInclusive(a), RangeInclusive(b)) => {
+                (a.start(), a.end()).partial_cmp(&(b.start(), b.end()))
+            }
+            (FloatRangeInclusive(a), FloatRangeInclusive(b)) => {
+                (a.start(), a.end()).partial_cmp(&(b.start(), b.end()))
+            }
+            (CharRangeInclusive(a), CharRangeInclusive(b)) => {
+                (a.start(), a.end()).partial_cmp(&(b.start(), b.end()))
+            }
+            (Vec(a), Vec(b)) | (Tuple(a), Tuple(b)) => a.partial_cmp(b),
+            (Map(a), Map(b)) => a.partial_cmp(b),
+            (None, None) => Some(Ordering::Equal),
+            _ => Option::None,
         }
     }
 }
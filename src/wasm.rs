@@ -0,0 +1,239 @@
+//! JavaScript bindings with `wasm-bindgen`, with the `wasm` feature
+//!
+//! ```js
+//! import { Eval, evaluate } from "v_eval";
+//!
+//! const e = new Eval();
+//! e.insert("adult", "age >= 18");
+//! e.set("age", 20);
+//! e.eval("adult && [1, 2].len() == 2"); // true
+//! evaluate("0..3"); // { start: 0, end: 3 }
+//! ```
+//!
+//! Values are mapped to JavaScript as:
+//! - `None`: `null`
+//! - `Bool`, `Float` and `Str`: primitives
+//! - `Int`: numbers, or `BigInt` out of the safe integers
+//! - `Vec` and `Tuple`: arrays
+//! - `Map`: objects
+//! - ranges: `{ start, end }` objects, with `inclusive: true` when the end is
+//!   in the range
+//!
+//! And from JavaScript, numbers are `Float`, `BigInt` are `Int` and
+//! `undefined` is also `None`
+//!
+//! Tests run under node with `wasm-pack test --node -- --features wasm --lib`,
+//! or with `cargo test --target wasm32-unknown-unknown --features wasm --lib`
+//! when `wasm-bindgen-test-runner` is the target runner
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::ToString, vec::Vec};
+use core::{convert::TryFrom, mem};
+
+use js_sys::{Array, BigInt, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{Eval, Value};
+
+/// Evaluator with context, `Eval` in JavaScript
+#[wasm_bindgen(js_name = Eval)]
+#[derive(Clone, Default)]
+pub struct JsEval(Eval);
+
+#[wasm_bindgen(js_class = Eval)]
+impl JsEval {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsEval {
+        JsEval::default()
+    }
+
+    /// Parse and insert in context name - expression
+    pub fn insert(&mut self, name: &str, src: &str) -> Result<(), JsError> {
+        let e = self.0.parse(src)?;
+        self.0 .0.insert(name.to_owned(), e);
+
+        Ok(())
+    }
+
+    /// Insert in context name - value
+    pub fn set(&mut self, name: &str, value: JsValue) -> Result<(), JsError> {
+        let v = from_js(&value).ok_or_else(|| JsError::new("value without equivalent"))?;
+        self.0 .0.insert(name.to_owned(), v.into());
+
+        Ok(())
+    }
+
+    /// Remove name in context
+    pub fn remove(&mut self, name: &str) {
+        self.0 .0.remove(name);
+    }
+
+    /// Negative indexes count from the end, see [`Eval::negative_index`]
+    #[wasm_bindgen(js_name = negativeIndex)]
+    pub fn negative_index(&mut self, on: bool) {
+        self.0 = mem::take(&mut self.0).negative_index(on);
+    }
+
+    /// Evaluate expression with current context
+    pub fn eval(&self, src: &str) -> Result<JsValue, JsError> {
+        Ok(to_js(&self.0.try_eval(src)?))
+    }
+}
+
+/// Evaluate expression without context, `eval` is reserved in JavaScript
+#[wasm_bindgen]
+pub fn evaluate(src: &str) -> Result<JsValue, JsError> {
+    JsEval::new().eval(src)
+}
+
+/// Largest integer of the JavaScript numbers, `Number.MAX_SAFE_INTEGER`
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// JavaScript number of an integer, `BigInt` when it is not exact as a number
+fn int(n: i64) -> JsValue {
+    if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&n) {
+        (n as f64).into()
+    } else {
+        BigInt::from(n).into()
+    }
+}
+
+/// JavaScript value of a value
+pub fn to_js(v: &Value) -> JsValue {
+    let range = |start: JsValue, end: JsValue, inclusive: bool| {
+        let o = Object::new();
+        // Properties of a new object are always set
+        let _ = Reflect::set(&o, &"start".into(), &start);
+        let _ = Reflect::set(&o, &"end".into(), &end);
+        if inclusive {
+            let _ = Reflect::set(&o, &"inclusive".into(), &true.into());
+        }
+        o.into()
+    };
+
+    match v {
+        Value::None => JsValue::NULL,
+        Value::Bool(b) => (*b).into(),
+        Value::Int(n) => int(*n),
+        Value::Float(n) => (*n).into(),
+        Value::Str(s) => s.into(),
+        Value::Vec(v) | Value::Tuple(v) => v.iter().map(to_js).collect::<Array>().into(),
+        Value::Map(m) => {
+            let o = Object::new();
+            for (k, v) in m {
+                let _ = Reflect::set(&o, &k.into(), &to_js(v));
+            }
+            o.into()
+        }
+        Value::Range(r) => range(int(r.start), int(r.end), false),
+        Value::FloatRange(r) => range(r.start.into(), r.end.into(), false),
+        Value::CharRange(r) => range(r.start.to_string().into(), r.end.to_string().into(), false),
+        Value::RangeInclusive(r) => range(int(*r.start()), int(*r.end()), true),
+        Value::FloatRangeInclusive(r) => range((*r.start()).into(), (*r.end()).into(), true),
+        Value::CharRangeInclusive(r) => range(
+            r.start().to_string().into(),
+            r.end().to_string().into(),
+            true,
+        ),
+    }
+}
+
+/// Value of a JavaScript value, `None` without equivalent
+pub fn from_js(v: &JsValue) -> Option<Value> {
+    if v.is_null() || v.is_undefined() {
+        Some(Value::None)
+    } else if let Some(b) = v.as_bool() {
+        Some(b.into())
+    } else if v.is_bigint() {
+        i64::try_from(v.clone()).ok().map(Value::Int)
+    } else if let Some(n) = v.as_f64() {
+        Some(Value::Float(n))
+    } else if let Some(s) = v.as_string() {
+        Some(s.into())
+    } else if Array::is_array(v) {
+        Array::from(v)
+            .iter()
+            .map(|v| from_js(&v))
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Vec)
+    } else if v.is_object() && !v.is_function() {
+        Object::entries(v.unchecked_ref())
+            .iter()
+            .map(|entry| {
+                let entry = Array::from(&entry);
+                Some((entry.get(0).as_string()?, from_js(&entry.get(1))?))
+            })
+            .collect::<Option<BTreeMap<_, _>>>()
+            .map(Value::Map)
+    } else {
+        None
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use wasm_bindgen_test::*;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn test_eval() {
+        let mut e = JsEval::new();
+        e.insert("adult", "age >= 18").unwrap();
+        e.set("age", BigInt::from(20).into()).unwrap();
+        assert_eq!(e.eval("adult").unwrap(), JsValue::TRUE);
+        assert_eq!(e.eval("age / 3").unwrap(), JsValue::from(6));
+        assert_eq!(e.eval("age / 3.0").unwrap().as_f64(), Some(20.0 / 3.0));
+        e.set("score", 20.into()).unwrap();
+        assert_eq!(e.eval("score / 3").unwrap().as_f64(), Some(20.0 / 3.0));
+        assert!(e.insert("b", "1 +").is_err());
+        assert!(e.eval("1 + true").is_err());
+
+        e.remove("age");
+        assert!(e.eval("adult").is_err());
+        assert!(e.eval("[1][-1]").is_err());
+        e.negative_index(true);
+        assert_eq!(e.eval("[1][-1]").unwrap(), JsValue::from(1));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_values() {
+        let mut m = BTreeMap::new();
+        m.insert("a".to_owned(), Value::Vec(vec![Value::Float(1.0)]));
+        m.insert("b".to_owned(), Value::Int(i64::MIN));
+        let values = [
+            Value::None,
+            Value::Bool(true),
+            Value::Int(i64::MAX),
+            Value::Int(-(1 << 53)),
+            Value::Float(0.5),
+            Value::Float(-3.0),
+            Value::Str("foo".into()),
+            Value::Vec(vec![Value::Float(1.0), Value::Vec(vec![Value::None])]),
+            Value::Map(m),
+        ];
+        for v in values.iter() {
+            assert_eq!(from_js(&to_js(v)).as_ref(), Some(v));
+        }
+        assert_eq!(from_js(&to_js(&Value::Int(-3))), Some(Value::Float(-3.0)));
+        assert_eq!(
+            to_js(&Value::Int(MAX_SAFE_INTEGER)).as_f64(),
+            Some(9007199254740991.0)
+        );
+        assert!(to_js(&Value::Int(MAX_SAFE_INTEGER + 1)).is_bigint());
+        assert_eq!(
+            from_js(&to_js(&Value::Tuple(vec!["a".into()]))),
+            Some(Value::Vec(vec!["a".into()]))
+        );
+        assert_eq!(from_js(&JsValue::UNDEFINED), Some(Value::None));
+        assert_eq!(
+            from_js(&Object::new().into()),
+            Some(Value::Map(BTreeMap::new()))
+        );
+        assert_eq!(from_js(&BigInt::from(u64::MAX).into()), None);
+
+        let range = evaluate("0..3").unwrap();
+        assert_eq!(
+            Reflect::get(&range, &"end".into()).unwrap(),
+            JsValue::from(3)
+        );
+    }
+}